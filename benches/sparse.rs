@@ -0,0 +1,112 @@
+//! Measures iteration over lists where most slots are vacant. Each list is
+//! compared against a `Vec<Option<_>>` of the same shape, which has to look at
+//! every slot to find the occupied ones.
+//!
+//! Run with `cargo bench --bench sparse`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use slotlist::{
+  AllocationPolicy, PackedStorage, SkipfieldStorage, SlotList, Storage,
+};
+
+const SLOTS: usize = 1 << 20;
+const ROUNDS: u32 = 20;
+
+/// Run `f` several times, returning the fastest run
+fn time<F: FnMut() -> u64>(mut f: F) -> Duration {
+  (0..ROUNDS)
+    .map(|_| {
+      let start = Instant::now();
+      black_box(f());
+      start.elapsed()
+    })
+    .min()
+    .unwrap()
+}
+
+fn report(name: &str, baseline: Duration, measured: Duration) {
+  println!(
+    "  {:<32} {:>10.3?}  ({:.1}x vs scan)",
+    name,
+    measured,
+    baseline.as_secs_f64() / measured.as_secs_f64(),
+  );
+}
+
+/// Fill a list, then remove every value except one in every `stride` slots
+fn sparse_list<S: Storage<u64>>(
+  mut list: SlotList<u64, usize, S>,
+  stride: usize,
+) -> SlotList<u64, usize, S> {
+  for i in 0..SLOTS {
+    list.insert(i as u64);
+  }
+  for i in 0..SLOTS {
+    if i % stride != 0 {
+      list.remove(i);
+    }
+  }
+  list
+}
+
+fn compare(stride: usize) {
+  println!("1 in {} of {} slots occupied:", stride, SLOTS);
+  let scan: Vec<Option<u64>> = (0..SLOTS)
+    .map(|i| if i % stride == 0 { Some(i as u64) } else { None })
+    .collect();
+  let vec_list = sparse_list(SlotList::new(), stride);
+  let packed_list =
+    sparse_list(SlotList::with_storage(PackedStorage::new()), stride);
+  let skipfield_list =
+    sparse_list(SlotList::with_storage(SkipfieldStorage::new()), stride);
+
+  let baseline = time(|| scan.iter().flatten().sum());
+  println!("  {:<32} {:>10.3?}", "Vec<Option<u64>> scan", baseline);
+  report("SlotList::values", baseline, time(|| vec_list.values().sum()));
+  report("SlotList::values (packed)", baseline, time(|| {
+    packed_list.values().sum()
+  }));
+  report("SlotList::values().rev()", baseline, time(|| {
+    vec_list.values().rev().sum()
+  }));
+  report("SlotList::values (skipfield)", baseline, time(|| {
+    skipfield_list.values().sum()
+  }));
+  report("SlotList::find_next_occupied", baseline, time(|| {
+    let mut sum = 0;
+    let mut next = vec_list.find_next_occupied(0);
+    while let Some(index) = next {
+      sum += vec_list.get(index).unwrap();
+      next = vec_list.find_next_occupied(index + 1);
+    }
+    sum
+  }));
+}
+
+/// Time refilling the gaps of a sparse list, which with `LowestIndex` means
+/// repeatedly searching for the lowest vacant slot
+fn lowest_index_refill(stride: usize) {
+  let policy = AllocationPolicy::LowestIndex;
+  let mut list = sparse_list(SlotList::with_policy(policy), stride);
+  let vacant = list.vacant_count();
+  let start = Instant::now();
+  for i in 0..vacant {
+    black_box(list.insert(i as u64));
+  }
+  let elapsed = start.elapsed();
+  println!(
+    "LowestIndex refill of {} slots: {:.3?} ({:.1} ns per insert)",
+    vacant,
+    elapsed,
+    elapsed.as_nanos() as f64 / vacant as f64,
+  );
+}
+
+fn main() {
+  for stride in [1000, 100, 10, 1] {
+    compare(stride);
+  }
+  lowest_index_refill(100);
+}
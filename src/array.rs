@@ -0,0 +1,180 @@
+use core::ops::{Deref, DerefMut};
+
+use crate::error::InsertError;
+use crate::index::SlotIndex;
+use crate::iter::{Iter, IterMut};
+use crate::list::SlotList;
+use crate::policy::AllocationPolicy;
+use crate::storage::ArrayStorage;
+
+/// A SlotList with room for a fixed number of values, stored inline instead of
+/// on the heap. It never allocates, so it can be used where no allocator is
+/// available, such as during early boot or in an interrupt handler.
+/// Apart from construction and `insert`, every method is the same as the one
+/// on SlotList, and shares its implementation.
+pub struct ArraySlotList<T, const N: usize, I = usize> {
+  list: SlotList<T, I, ArrayStorage<T, N, I>>,
+}
+
+impl<T, const N: usize, I: SlotIndex> ArraySlotList<T, N, I> {
+  /// Construct a new, empty ArraySlotList. This can be a `const` value.
+  pub const fn new() -> ArraySlotList<T, N, I> {
+    ArraySlotList::with_policy(AllocationPolicy::Fifo)
+  }
+
+  /// Construct a new, empty ArraySlotList that re-uses empty slots according
+  /// to the provided policy.
+  pub const fn with_policy(policy: AllocationPolicy) -> ArraySlotList<T, N, I> {
+    ArraySlotList {
+      list: SlotList::from_storage(ArrayStorage::new(), policy, N),
+    }
+  }
+
+  /// Insert a new value into the list, returning its index. Since the list
+  /// can't grow, this fails once all `N` slots are occupied, handing the value
+  /// back inside of the error.
+  pub fn insert(&mut self, item: T) -> Result<usize, InsertError<T>> {
+    self.list.try_insert(item)
+  }
+}
+
+impl<T, const N: usize, I> Deref for ArraySlotList<T, N, I> {
+  type Target = SlotList<T, I, ArrayStorage<T, N, I>>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.list
+  }
+}
+
+impl<T, const N: usize, I> DerefMut for ArraySlotList<T, N, I> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.list
+  }
+}
+
+impl<'a, T, const N: usize, I> IntoIterator for &'a ArraySlotList<T, N, I>
+where
+  I: SlotIndex,
+{
+  type Item = (usize, &'a T);
+  type IntoIter = Iter<'a, T, I, ArrayStorage<T, N, I>>;
+
+  fn into_iter(self) -> Iter<'a, T, I, ArrayStorage<T, N, I>> {
+    self.list.iter_indexed()
+  }
+}
+
+impl<'a, T, const N: usize, I> IntoIterator for &'a mut ArraySlotList<T, N, I>
+where
+  I: SlotIndex,
+{
+  type Item = (usize, &'a mut T);
+  type IntoIter = IterMut<'a, T, I, ArrayStorage<T, N, I>>;
+
+  fn into_iter(self) -> IterMut<'a, T, I, ArrayStorage<T, N, I>> {
+    self.list.iter_indexed_mut()
+  }
+}
+
+impl<T, const N: usize, I: SlotIndex> Default for ArraySlotList<T, N, I> {
+  fn default() -> ArraySlotList<T, N, I> {
+    ArraySlotList::new()
+  }
+}
+
+impl<T: Clone, const N: usize, I: Copy> Clone for ArraySlotList<T, N, I> {
+  fn clone(&self) -> Self {
+    ArraySlotList {
+      list: self.list.clone(),
+    }
+  }
+}
+
+impl<T, const N: usize, I> core::fmt::Debug for ArraySlotList<T, N, I>
+where
+  T: core::fmt::Debug,
+  I: SlotIndex,
+{
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    core::fmt::Debug::fmt(&self.list, formatter)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ArraySlotList;
+  use crate::error::SlotListError;
+  use crate::policy::AllocationPolicy;
+
+  const EMPTY: ArraySlotList<u32, 4> = ArraySlotList::new();
+
+  #[test]
+  fn filling_the_array() {
+    let mut list = EMPTY;
+    assert_eq!(list.capacity(), 4);
+    for i in 0..4 {
+      assert_eq!(list.insert(i * 10), Ok(i as usize));
+    }
+    let err = list.insert(40).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::CapacityExceeded);
+    assert_eq!(err.into_inner(), 40);
+    assert_eq!(list.len(), 4);
+    assert_eq!(list.slot_count(), 4);
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn sharing_list_operations() {
+    let mut list: ArraySlotList<&str, 8> = ArraySlotList::new();
+    let a = list.insert("a").unwrap();
+    let b = list.insert("b").unwrap();
+    list.insert("c").unwrap();
+    assert_eq!(list.get(b), Some(&"b"));
+    assert_eq!(list.remove(a), Some("a"));
+    assert_eq!(list.replace(b, "B"), Some("b"));
+    // FIFO re-uses the spare slot before the one that was just emptied
+    assert_eq!(list.insert("d"), Ok(3));
+    assert_eq!(list.insert("e"), Ok(0));
+    let expected = [(0, &"e"), (1, &"B"), (2, &"c"), (3, &"d")];
+    assert!((&list).into_iter().eq(expected));
+    for (_, value) in &mut list {
+      *value = "x";
+    }
+    assert_eq!(list.values().filter(|value| **value == "x").count(), 4);
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn array_policies() {
+    let mut list: ArraySlotList<u32, 8> =
+      ArraySlotList::with_policy(AllocationPolicy::LowestIndex);
+    for i in 0..6 {
+      list.insert(i).unwrap();
+    }
+    list.remove(4);
+    list.remove(1);
+    assert_eq!(list.insert(10), Ok(1));
+    assert_eq!(list.insert(11), Ok(4));
+    assert_eq!(list.check_invariants(), Ok(()));
+
+    let mut list: ArraySlotList<u32, 4> =
+      ArraySlotList::with_policy(AllocationPolicy::Cyclic);
+    for i in 0..4 {
+      list.insert(i).unwrap();
+    }
+    list.remove(2);
+    list.remove(0);
+    assert_eq!(list.insert(4), Ok(0));
+    assert_eq!(list.insert(5), Ok(2));
+  }
+
+  #[test]
+  fn reserving_in_an_array() {
+    let mut list: ArraySlotList<u32, 2> = ArraySlotList::new();
+    let reservation = list.reserve();
+    list.insert(1).unwrap();
+    assert!(list.insert(2).is_err());
+    list.commit(reservation, 0);
+    assert!(list.iter_indexed().eq([(0, &0), (1, &1)]));
+  }
+}
@@ -0,0 +1,547 @@
+#[cfg(feature = "std")]
+use std::{collections::TryReserveError, vec::Vec};
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::{collections::TryReserveError, vec::Vec};
+
+use core::iter::FusedIterator;
+
+const BITS: usize = u64::BITS as usize;
+
+/// Tracks which slots of a SlotList are in use, one bit per slot. This mirrors
+/// the `open_fds` / `full_fds_bits` pair used by Linux descriptor tables: a
+/// second, smaller bitmap records which words of the first one are completely
+/// full, so that the search for the lowest free slot can skip 4096 slots at a
+/// time instead of visiting each one.
+/// A third bitmap records which words have any bit set, so that searching for
+/// used slots, and iterating over them, skips long runs of vacant slots the
+/// same way.
+#[derive(Clone, Debug)]
+pub struct OccupancyBitmap {
+  open: Vec<u64>,
+  full: Vec<u64>,
+  nonempty: Vec<u64>,
+}
+
+impl OccupancyBitmap {
+  pub const fn new() -> OccupancyBitmap {
+    OccupancyBitmap {
+      open: Vec::new(),
+      full: Vec::new(),
+      nonempty: Vec::new(),
+    }
+  }
+
+  pub fn with_capacity(slots: usize) -> OccupancyBitmap {
+    let words = slots.div_ceil(BITS);
+    OccupancyBitmap {
+      open: Vec::with_capacity(words),
+      full: Vec::with_capacity(words.div_ceil(BITS)),
+      nonempty: Vec::with_capacity(words.div_ceil(BITS)),
+    }
+  }
+
+  /// Ensure that the bitmap can track the requested number of slots without
+  /// allocating any more memory
+  pub fn try_reserve(&mut self, slots: usize) -> Result<(), TryReserveError> {
+    let words = slots.div_ceil(BITS);
+    self.open.try_reserve(words.saturating_sub(self.open.len()))?;
+    let summary_words = words.div_ceil(BITS);
+    self.full.try_reserve(summary_words.saturating_sub(self.full.len()))?;
+    self.nonempty.try_reserve(summary_words.saturating_sub(self.nonempty.len()))
+  }
+
+  /// Mark every slot as vacant, without releasing any memory
+  pub fn clear_all(&mut self) {
+    self.open.iter_mut().for_each(|word| *word = 0);
+    self.full.iter_mut().for_each(|word| *word = 0);
+    self.nonempty.iter_mut().for_each(|word| *word = 0);
+  }
+
+  /// Mark a slot as occupied, growing the bitmap if necessary
+  pub fn set(&mut self, index: usize) {
+    let word = index / BITS;
+    if word >= self.open.len() {
+      self.open.resize(word + 1, 0);
+      self.full.resize(self.open.len().div_ceil(BITS), 0);
+      self.nonempty.resize(self.open.len().div_ceil(BITS), 0);
+    }
+    self.open[word] |= 1 << (index % BITS);
+    self.nonempty[word / BITS] |= 1 << (word % BITS);
+    if self.open[word] == !0 {
+      self.full[word / BITS] |= 1 << (word % BITS);
+    }
+  }
+
+  /// Mark a slot as vacant
+  pub fn clear(&mut self, index: usize) {
+    let word = index / BITS;
+    if word >= self.open.len() {
+      return;
+    }
+    self.open[word] &= !(1 << (index % BITS));
+    self.full[word / BITS] &= !(1 << (word % BITS));
+    if self.open[word] == 0 {
+      self.nonempty[word / BITS] &= !(1 << (word % BITS));
+    }
+  }
+
+  /// Release the memory used to track slots at or after `slots`. Every one
+  /// of those slots must already be vacant.
+  pub fn truncate(&mut self, slots: usize) {
+    let words = slots.div_ceil(BITS);
+    self.open.truncate(words);
+    self.open.shrink_to_fit();
+    self.full.truncate(words.div_ceil(BITS));
+    self.full.shrink_to_fit();
+    self.nonempty.truncate(words.div_ceil(BITS));
+    self.nonempty.shrink_to_fit();
+  }
+
+  pub fn is_set(&self, index: usize) -> bool {
+    match self.open.get(index / BITS) {
+      Some(word) => word & (1 << (index % BITS)) != 0,
+      None => false,
+    }
+  }
+
+  /// Find the lowest vacant slot with an index less than `limit`
+  pub fn first_clear(&self, limit: usize) -> Option<usize> {
+    self.next_clear(0, limit)
+  }
+
+  /// Find the lowest vacant slot at or after `from`, with an index less than
+  /// `limit`
+  pub fn next_clear(&self, from: usize, limit: usize) -> Option<usize> {
+    let word = from / BITS;
+    let index = match self.open.get(word) {
+      // Words past the end of the bitmap have never had a bit set
+      None => from,
+      Some(bits) => {
+        // Treat the bits below `from` as occupied
+        let bits = bits | ((1 << (from % BITS)) - 1);
+        if bits != !0 {
+          word * BITS + (!bits).trailing_zeros() as usize
+        } else {
+          let word = self.next_non_full_word(word + 1);
+          match self.open.get(word) {
+            Some(bits) => word * BITS + (!bits).trailing_zeros() as usize,
+            None => word * BITS,
+          }
+        }
+      },
+    };
+    if index < limit {
+      Some(index)
+    } else {
+      None
+    }
+  }
+
+  /// Find the lowest used slot at or after `from`, with an index less than
+  /// `limit`
+  pub fn next_set(&self, from: usize, limit: usize) -> Option<usize> {
+    let word = from / BITS;
+    // Ignore the bits below `from`
+    let bits = self.open.get(word)? & !((1 << (from % BITS)) - 1);
+    let index = if bits != 0 {
+      word * BITS + bits.trailing_zeros() as usize
+    } else {
+      let word = self.next_nonempty_word(word + 1)?;
+      word * BITS + self.open[word].trailing_zeros() as usize
+    };
+    if index < limit {
+      Some(index)
+    } else {
+      None
+    }
+  }
+
+  /// Find the highest used slot below `end`, with an index of at least `from`
+  pub fn prev_set(&self, from: usize, end: usize) -> Option<usize> {
+    let last = end.checked_sub(1)?;
+    let word = last / BITS;
+    let index = match self.open.get(word) {
+      // Ignore the bits above `last`
+      Some(bits) if bits & (!0 >> (BITS - 1 - last % BITS)) != 0 => {
+        let bits = bits & (!0 >> (BITS - 1 - last % BITS));
+        word * BITS + highest_bit(bits)
+      },
+      _ => {
+        let word = self.prev_nonempty_word(word)?;
+        word * BITS + highest_bit(self.open[word])
+      },
+    };
+    if index >= from {
+      Some(index)
+    } else {
+      None
+    }
+  }
+
+  /// Count the slots that are marked as used
+  pub fn count_set(&self) -> usize {
+    self.open.iter().map(|bits| bits.count_ones() as usize).sum()
+  }
+
+  /// Find the first slot whose bit disagrees with the rest of the bitmap:
+  /// either it is set at or after `slots`, past the end of the list, or the
+  /// summaries are wrong about its word. A wrong summary is reported at the
+  /// first slot of the word.
+  pub fn first_inconsistency(&self, slots: usize) -> Option<usize> {
+    for (word, &bits) in self.open.iter().enumerate() {
+      let summary_bit = 1 << (word % BITS);
+      let full = self.full[word / BITS] & summary_bit != 0;
+      let nonempty = self.nonempty[word / BITS] & summary_bit != 0;
+      if full != (bits == !0) || nonempty != (bits != 0) {
+        return Some(word * BITS);
+      }
+      let past_end = bits_past_end(word, bits, slots);
+      if past_end != 0 {
+        return Some(word * BITS + past_end.trailing_zeros() as usize);
+      }
+    }
+    None
+  }
+
+  /// Clear any bits at or after `slots`, and recompute the summaries from the
+  /// bits that are left
+  pub fn rebuild_summaries(&mut self, slots: usize) {
+    self.full.iter_mut().for_each(|word| *word = 0);
+    self.nonempty.iter_mut().for_each(|word| *word = 0);
+    for (word, bits) in self.open.iter_mut().enumerate() {
+      *bits &= !bits_past_end(word, *bits, slots);
+      if *bits == !0 {
+        self.full[word / BITS] |= 1 << (word % BITS);
+      }
+      if *bits != 0 {
+        self.nonempty[word / BITS] |= 1 << (word % BITS);
+      }
+    }
+  }
+
+  /// Iterate over the used slots with an index less than `limit`, in either
+  /// direction
+  pub fn set_bits(&self, limit: usize) -> SetBits<'_> {
+    SetBits::new(self, limit)
+  }
+
+  /// Use the summary bitmap to find the first word at or after `from` that
+  /// has at least one bit set
+  fn next_nonempty_word(&self, from: usize) -> Option<usize> {
+    let mut summary_index = from / BITS;
+    let mut at_or_after_from = !((1 << (from % BITS)) - 1);
+    while let Some(summary) = self.nonempty.get(summary_index) {
+      let summary = summary & at_or_after_from;
+      if summary != 0 {
+        return Some(summary_index * BITS + summary.trailing_zeros() as usize);
+      }
+      at_or_after_from = !0;
+      summary_index += 1;
+    }
+    None
+  }
+
+  /// Use the summary bitmap to find the last word before `end` that has at
+  /// least one bit set
+  fn prev_nonempty_word(&self, end: usize) -> Option<usize> {
+    let last = core::cmp::min(end, self.open.len()).checked_sub(1)?;
+    let mut summary_index = last / BITS;
+    let mut at_or_before_last = !0 >> (BITS - 1 - last % BITS);
+    loop {
+      let summary = self.nonempty[summary_index] & at_or_before_last;
+      if summary != 0 {
+        return Some(summary_index * BITS + highest_bit(summary));
+      }
+      summary_index = summary_index.checked_sub(1)?;
+      at_or_before_last = !0;
+    }
+  }
+
+  /// Use the summary bitmap to find the first word at or after `from` that
+  /// has at least one clear bit
+  fn next_non_full_word(&self, from: usize) -> usize {
+    let mut summary_index = from / BITS;
+    let mut below_from = (1 << (from % BITS)) - 1;
+    while let Some(summary) = self.full.get(summary_index) {
+      let summary = summary | below_from;
+      if summary != !0 {
+        return summary_index * BITS + (!summary).trailing_zeros() as usize;
+      }
+      below_from = 0;
+      summary_index += 1;
+    }
+    core::cmp::max(from, summary_index * BITS)
+  }
+}
+
+/// The position of the highest set bit in a non-zero word
+fn highest_bit(word: u64) -> usize {
+  BITS - 1 - word.leading_zeros() as usize
+}
+
+/// The bits of a word of `open` that belong to slots at or after `slots`
+fn bits_past_end(word: usize, bits: u64, slots: usize) -> u64 {
+  match slots.checked_sub(word * BITS) {
+    Some(in_word) if in_word >= BITS => 0,
+    Some(in_word) => bits & !((1 << in_word) - 1),
+    None => bits,
+  }
+}
+
+/// Iterator over the positions of the set bits in an OccupancyBitmap. Each end
+/// holds on to the word it is working through, so that stepping to the next
+/// bit only needs to clear the one just yielded, and words with no bits set
+/// are skipped using the summary bitmap.
+#[derive(Clone)]
+pub struct SetBits<'a> {
+  bitmap: &'a OccupancyBitmap,
+  /// Every bit before this position has been yielded or skipped
+  front: usize,
+  /// Every bit at or after this position has been yielded or skipped
+  back: usize,
+  /// The word of `open` that the front end is working through
+  front_word: usize,
+  /// The bits of `front_word` that haven't been visited from the front
+  front_bits: u64,
+  /// The word of `open` that the back end is working through
+  back_word: usize,
+  /// The bits of `back_word` that haven't been visited from the back
+  back_bits: u64,
+}
+
+impl<'a> SetBits<'a> {
+  fn new(bitmap: &'a OccupancyBitmap, limit: usize) -> SetBits<'a> {
+    let front_bits = bitmap.open.first().copied().unwrap_or(0);
+    let (back_word, back_bits) = match limit.checked_sub(1) {
+      Some(last) if last / BITS < bitmap.open.len() => {
+        let mask: u64 = !0 >> (BITS - 1 - last % BITS);
+        (last / BITS, bitmap.open[last / BITS] & mask)
+      },
+      _ => (bitmap.open.len(), 0),
+    };
+    SetBits {
+      bitmap,
+      front: 0,
+      back: limit,
+      front_word: 0,
+      front_bits,
+      back_word,
+      back_bits,
+    }
+  }
+}
+
+impl<'a> Iterator for SetBits<'a> {
+  type Item = usize;
+
+  #[inline]
+  fn next(&mut self) -> Option<usize> {
+    while self.front_bits == 0 {
+      match self.bitmap.next_nonempty_word(self.front_word + 1) {
+        Some(word) => {
+          self.front_word = word;
+          self.front_bits = self.bitmap.open[word];
+        },
+        None => {
+          self.front = self.back;
+          return None;
+        },
+      }
+    }
+    let index =
+      self.front_word * BITS + self.front_bits.trailing_zeros() as usize;
+    self.front_bits &= self.front_bits - 1;
+    if index >= self.back {
+      self.front = self.back;
+      self.front_bits = 0;
+      return None;
+    }
+    self.front = index + 1;
+    Some(index)
+  }
+}
+
+impl<'a> DoubleEndedIterator for SetBits<'a> {
+  #[inline]
+  fn next_back(&mut self) -> Option<usize> {
+    while self.back_bits == 0 {
+      match self.bitmap.prev_nonempty_word(self.back_word) {
+        Some(word) => {
+          self.back_word = word;
+          self.back_bits = self.bitmap.open[word];
+        },
+        None => {
+          self.back = self.front;
+          return None;
+        },
+      }
+    }
+    let bit = highest_bit(self.back_bits);
+    let index = self.back_word * BITS + bit;
+    self.back_bits &= !(1 << bit);
+    if index < self.front {
+      self.back = self.front;
+      self.back_bits = 0;
+      return None;
+    }
+    self.back = index;
+    Some(index)
+  }
+}
+
+impl<'a> FusedIterator for SetBits<'a> {}
+
+#[cfg(test)]
+mod tests {
+  use super::{OccupancyBitmap, Vec};
+
+  #[test]
+  fn set_and_clear() {
+    let mut bitmap = OccupancyBitmap::new();
+    assert!(!bitmap.is_set(3));
+    bitmap.set(3);
+    bitmap.set(200);
+    assert!(bitmap.is_set(3));
+    assert!(bitmap.is_set(200));
+    assert!(!bitmap.is_set(4));
+    bitmap.clear(3);
+    assert!(!bitmap.is_set(3));
+  }
+
+  #[test]
+  fn lowest_clear_bit() {
+    let mut bitmap = OccupancyBitmap::new();
+    assert_eq!(bitmap.first_clear(0), None);
+    assert_eq!(bitmap.first_clear(10), Some(0));
+    for i in 0..130 {
+      bitmap.set(i);
+    }
+    assert_eq!(bitmap.first_clear(130), None);
+    assert_eq!(bitmap.first_clear(131), Some(130));
+    bitmap.clear(70);
+    assert_eq!(bitmap.first_clear(131), Some(70));
+    bitmap.clear(2);
+    assert_eq!(bitmap.first_clear(131), Some(2));
+  }
+
+  #[test]
+  fn skips_full_words() {
+    let mut bitmap = OccupancyBitmap::new();
+    for i in 0..(64 * 64 + 10) {
+      bitmap.set(i);
+    }
+    assert_eq!(bitmap.first_clear(10000), Some(64 * 64 + 10));
+    bitmap.clear(64 * 64 - 1);
+    assert_eq!(bitmap.first_clear(10000), Some(64 * 64 - 1));
+  }
+
+  #[test]
+  fn clear_bit_after_position() {
+    let mut bitmap = OccupancyBitmap::new();
+    for i in 0..200 {
+      bitmap.set(i);
+    }
+    bitmap.clear(10);
+    bitmap.clear(150);
+    assert_eq!(bitmap.next_clear(0, 300), Some(10));
+    assert_eq!(bitmap.next_clear(10, 300), Some(10));
+    assert_eq!(bitmap.next_clear(11, 300), Some(150));
+    assert_eq!(bitmap.next_clear(151, 300), Some(200));
+    assert_eq!(bitmap.next_clear(151, 200), None);
+    assert_eq!(bitmap.next_clear(500, 1000), Some(500));
+  }
+
+  #[test]
+  fn truncating() {
+    let mut bitmap = OccupancyBitmap::new();
+    for i in 0..(64 * 64 + 10) {
+      bitmap.set(i);
+    }
+    for i in 100..(64 * 64 + 10) {
+      bitmap.clear(i);
+    }
+    bitmap.truncate(100);
+    assert!(bitmap.is_set(99));
+    assert!(!bitmap.is_set(100));
+    assert_eq!(bitmap.first_clear(1000), Some(100));
+    bitmap.set(64 * 64);
+    assert!(bitmap.is_set(64 * 64));
+  }
+
+  #[test]
+  fn set_bit_after_position() {
+    let mut bitmap = OccupancyBitmap::new();
+    assert_eq!(bitmap.next_set(0, 100), None);
+    bitmap.set(5);
+    bitmap.set(64);
+    bitmap.set(64 * 64 * 3 + 7);
+    assert_eq!(bitmap.next_set(0, 100), Some(5));
+    assert_eq!(bitmap.next_set(5, 100), Some(5));
+    assert_eq!(bitmap.next_set(6, 100), Some(64));
+    assert_eq!(bitmap.next_set(65, 100), None);
+    assert_eq!(bitmap.next_set(65, usize::MAX), Some(64 * 64 * 3 + 7));
+    bitmap.clear(64);
+    assert_eq!(bitmap.next_set(6, usize::MAX), Some(64 * 64 * 3 + 7));
+    assert_eq!(bitmap.next_set(64 * 64 * 3 + 8, usize::MAX), None);
+  }
+
+  #[test]
+  fn set_bit_before_position() {
+    let mut bitmap = OccupancyBitmap::new();
+    assert_eq!(bitmap.prev_set(0, 100), None);
+    bitmap.set(5);
+    bitmap.set(63);
+    bitmap.set(64 * 64 * 3 + 7);
+    assert_eq!(bitmap.prev_set(0, usize::MAX), Some(64 * 64 * 3 + 7));
+    assert_eq!(bitmap.prev_set(0, 64 * 64 * 3 + 7), Some(63));
+    assert_eq!(bitmap.prev_set(0, 63), Some(5));
+    assert_eq!(bitmap.prev_set(6, 63), None);
+    assert_eq!(bitmap.prev_set(0, 5), None);
+    bitmap.clear(63);
+    assert_eq!(bitmap.prev_set(0, 64 * 64 * 3), Some(5));
+  }
+
+  #[test]
+  fn iterating_set_bits() {
+    let mut bitmap = OccupancyBitmap::new();
+    let positions = [0, 1, 63, 64, 700, 4095, 4096, 9000];
+    for position in positions.iter() {
+      bitmap.set(*position);
+    }
+    let forward: Vec<usize> = bitmap.set_bits(10000).collect();
+    assert_eq!(forward, positions);
+    let mut backward: Vec<usize> = bitmap.set_bits(10000).rev().collect();
+    backward.reverse();
+    assert_eq!(backward, positions);
+    let limited: Vec<usize> = bitmap.set_bits(4096).collect();
+    assert_eq!(limited, [0, 1, 63, 64, 700, 4095]);
+    let mut bits = bitmap.set_bits(10000);
+    assert_eq!(bits.next(), Some(0));
+    assert_eq!(bits.next_back(), Some(9000));
+    assert_eq!(bits.next_back(), Some(4096));
+    assert_eq!(bits.next(), Some(1));
+  }
+
+  #[test]
+  fn repairing_summaries() {
+    let mut bitmap = OccupancyBitmap::new();
+    for i in 0..64 {
+      bitmap.set(i);
+    }
+    bitmap.set(200);
+    assert_eq!(bitmap.count_set(), 65);
+    assert_eq!(bitmap.first_inconsistency(201), None);
+    // Slot 200 is past the end of a list of 150 slots
+    assert_eq!(bitmap.first_inconsistency(150), Some(200));
+    bitmap.full[0] = 0;
+    bitmap.nonempty[0] &= !(1 << 3);
+    assert_eq!(bitmap.first_inconsistency(201), Some(0));
+    bitmap.rebuild_summaries(150);
+    assert_eq!(bitmap.first_inconsistency(150), None);
+    assert_eq!(bitmap.count_set(), 64);
+    assert_eq!(bitmap.first_clear(100), Some(64));
+    assert_eq!(bitmap.next_set(64, 1000), None);
+  }
+}
@@ -0,0 +1,701 @@
+#[cfg(feature = "std")]
+use std::{sync::Arc, vec::{self, Vec}};
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::{sync::Arc, vec::{self, Vec}};
+
+use core::iter::{Enumerate, FusedIterator};
+use core::slice;
+
+use crate::error::SlotListError;
+use crate::index::SlotIndex;
+use crate::list::Slot;
+use crate::storage::{sealed, Storage};
+
+const CHUNK_BITS: u32 = 6;
+/// Each chunk tracks which of its slots are in use with a single word
+const CHUNK_SIZE: usize = 1 << CHUNK_BITS;
+const CHUNK_MASK: usize = CHUNK_SIZE - 1;
+
+struct Chunk<T, I> {
+  slots: Vec<Slot<T, I>>,
+  /// One bit for each slot that is occupied or reserved
+  used: u64,
+}
+
+impl<T, I> Chunk<T, I> {
+  fn new() -> Chunk<T, I> {
+    Chunk {
+      slots: Vec::with_capacity(CHUNK_SIZE),
+      used: 0,
+    }
+  }
+
+  /// Allocate an empty chunk, failing rather than aborting if memory runs out
+  fn try_new() -> Result<Chunk<T, I>, SlotListError> {
+    let mut slots = Vec::new();
+    slots
+      .try_reserve_exact(CHUNK_SIZE)
+      .map_err(|_| SlotListError::AllocFailed)?;
+    Ok(Chunk { slots, used: 0 })
+  }
+}
+
+impl<T: Clone, I: Clone> Clone for Chunk<T, I> {
+  /// Copies keep room for every slot, so that filling them never reallocates
+  fn clone(&self) -> Self {
+    let mut slots = Vec::with_capacity(CHUNK_SIZE);
+    slots.extend(self.slots.iter().cloned());
+    Chunk {
+      slots,
+      used: self.used,
+    }
+  }
+}
+
+/// Heap-allocated storage that keeps its slots in fixed-size chunks, each
+/// shared through an `Arc`, so that cloning a list copies only a pointer per
+/// chunk. The first change to a shared chunk copies that chunk alone, leaving
+/// the clone it came from untouched. Since the empty chain lives in the slots,
+/// a cloned list keeps every index and re-uses slots in the same order as the
+/// original.
+/// Changing a value, or linking an empty slot, may need to copy its chunk, so
+/// the values have to be `Clone`. Reserving room allocates whole chunks ahead
+/// of time, and copies a shared chunk that new slots would go into, so adding
+/// slots after a successful reservation never allocates.
+pub struct ChunkedStorage<T, I = usize> {
+  chunks: Vec<Arc<Chunk<T, I>>>,
+  len: usize,
+}
+
+impl<T, I> ChunkedStorage<T, I> {
+  pub const fn new() -> ChunkedStorage<T, I> {
+    ChunkedStorage {
+      chunks: Vec::new(),
+      len: 0,
+    }
+  }
+
+  pub fn with_capacity(capacity: usize) -> ChunkedStorage<T, I> {
+    ChunkedStorage {
+      chunks: Vec::with_capacity(capacity.div_ceil(CHUNK_SIZE)),
+      len: 0,
+    }
+  }
+}
+
+impl<T: Clone, I: Clone> ChunkedStorage<T, I> {
+  /// Get a chunk that can be changed, copying it first if it is shared
+  fn chunk_mut(&mut self, chunk: usize) -> &mut Chunk<T, I> {
+    Arc::make_mut(&mut self.chunks[chunk])
+  }
+}
+
+impl<T, I> Default for ChunkedStorage<T, I> {
+  fn default() -> ChunkedStorage<T, I> {
+    ChunkedStorage::new()
+  }
+}
+
+impl<T, I> Clone for ChunkedStorage<T, I> {
+  /// Chunks reserved past the end aren't shared with the clone
+  fn clone(&self) -> Self {
+    ChunkedStorage {
+      chunks: self.chunks[..self.len.div_ceil(CHUNK_SIZE)].to_vec(),
+      len: self.len,
+    }
+  }
+}
+
+impl<T, I> core::fmt::Debug for ChunkedStorage<T, I>
+where
+  T: core::fmt::Debug,
+  I: SlotIndex,
+{
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    formatter.debug_list()
+      .entries(self.chunks.iter().flat_map(|chunk| chunk.slots.iter()))
+      .finish()
+  }
+}
+
+impl<T, I> sealed::Sealed for ChunkedStorage<T, I> {}
+
+impl<T: Clone, I: SlotIndex> Storage<T, I> for ChunkedStorage<T, I> {
+  type Iter<'a> = ChunkedIter<'a, T, I> where T: 'a, I: 'a;
+  type IterMut<'a> = ChunkedIterMut<'a, T, I> where T: 'a, I: 'a;
+  type IntoIter = ChunkedIntoIter<T, I>;
+
+  fn slot_count(&self) -> usize {
+    self.len
+  }
+
+  fn get(&self, index: usize) -> Option<Slot<&T, I>> {
+    let chunk = self.chunks.get(index >> CHUNK_BITS)?;
+    chunk.slots.get(index & CHUNK_MASK).map(Slot::as_ref)
+  }
+
+  fn get_value(&self, index: usize) -> Option<&T> {
+    let chunk = self.chunks.get(index >> CHUNK_BITS)?;
+    chunk.slots.get(index & CHUNK_MASK)?.as_option_of_ref()
+  }
+
+  fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    // Only copy the chunk if there is a value to hand out
+    self.get_value(index)?;
+    self.chunk_mut(index >> CHUNK_BITS).slots[index & CHUNK_MASK].as_mut()
+  }
+
+  fn replace(&mut self, index: usize, slot: Slot<T, I>) -> Slot<T, I> {
+    assert!(index < self.len, "slot index out of bounds");
+    let bit = 1 << (index & CHUNK_MASK);
+    let chunk = self.chunk_mut(index >> CHUNK_BITS);
+    if slot.is_vacant() {
+      chunk.used &= !bit;
+    } else {
+      chunk.used |= bit;
+    }
+    core::mem::replace(&mut chunk.slots[index & CHUNK_MASK], slot)
+  }
+
+  fn set_next_empty(
+    &mut self,
+    index: usize,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    if index >= self.len {
+      return Err(SlotListError::OutOfBounds);
+    }
+    let chunk = self.chunk_mut(index >> CHUNK_BITS);
+    chunk.slots[index & CHUNK_MASK].set_next_empty(next)
+  }
+
+  fn set_prev_empty(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    if index >= self.len {
+      return Err(SlotListError::OutOfBounds);
+    }
+    let chunk = self.chunk_mut(index >> CHUNK_BITS);
+    chunk.slots[index & CHUNK_MASK].set_prev_empty(prev)
+  }
+
+  fn push(&mut self, slot: Slot<T, I>) {
+    if self.len >> CHUNK_BITS == self.chunks.len() {
+      // Without a reserved chunk, one is allocated here, and like `Vec::push`,
+      // this aborts rather than failing if memory runs out
+      self.chunks.push(Arc::new(Chunk::new()));
+    }
+    let offset = self.len & CHUNK_MASK;
+    let chunk = self.chunk_mut(self.len >> CHUNK_BITS);
+    if !slot.is_vacant() {
+      chunk.used |= 1 << offset;
+    }
+    chunk.slots.push(slot);
+    self.len += 1;
+  }
+
+  fn pop(&mut self) -> Option<Slot<T, I>> {
+    self.len = self.len.checked_sub(1)?;
+    let offset = self.len & CHUNK_MASK;
+    if offset == 0 {
+      // The slot is alone in its chunk, so the chunk is dropped rather than
+      // copied. Any chunks after it are empty reserved ones, so which of them
+      // takes its place doesn't matter.
+      let chunk = self.chunks.swap_remove(self.len >> CHUNK_BITS);
+      return match Arc::try_unwrap(chunk) {
+        Ok(mut chunk) => chunk.slots.pop(),
+        Err(chunk) => chunk.slots.first().cloned(),
+      };
+    }
+    let chunk = self.chunk_mut(self.len >> CHUNK_BITS);
+    chunk.used &= !(1 << offset);
+    chunk.slots.pop()
+  }
+
+  fn reserve(&mut self, additional: usize) -> Result<(), SlotListError> {
+    if additional == 0 {
+      return Ok(());
+    }
+    let chunks = self.len.saturating_add(additional).div_ceil(CHUNK_SIZE);
+    self.chunks
+      .try_reserve(chunks.saturating_sub(self.chunks.len()))
+      .map_err(|_| SlotListError::AllocFailed)?;
+    // The next slot goes into this chunk, so copy it now if it is shared
+    if let Some(chunk) = self.chunks.get_mut(self.len >> CHUNK_BITS) {
+      if Arc::get_mut(chunk).is_none() {
+        let mut copy = Chunk::try_new()?;
+        copy.slots.extend(chunk.slots.iter().cloned());
+        copy.used = chunk.used;
+        *chunk = Arc::new(copy);
+      }
+    }
+    while self.chunks.len() < chunks {
+      self.chunks.push(Arc::new(Chunk::try_new()?));
+    }
+    Ok(())
+  }
+
+  fn capacity(&self) -> usize {
+    self.chunks.len() << CHUNK_BITS
+  }
+
+  fn shrink_to_fit(&mut self) {
+    self.chunks.truncate(self.len.div_ceil(CHUNK_SIZE));
+    self.chunks.shrink_to_fit();
+  }
+
+  fn rebuild_occupancy(&mut self) {
+    for chunk in self.chunks.iter_mut() {
+      let used = chunk.slots
+        .iter()
+        .enumerate()
+        .filter(|(_, slot)| !slot.is_vacant())
+        .fold(0, |used, (offset, _)| used | 1 << offset);
+      // Chunks that are already correct are left shared
+      if chunk.used != used {
+        Arc::make_mut(chunk).used = used;
+      }
+    }
+  }
+
+  fn is_marked_used(&self, index: usize) -> bool {
+    match self.chunks.get(index >> CHUNK_BITS) {
+      Some(chunk) => chunk.used & (1 << (index & CHUNK_MASK)) != 0,
+      None => false,
+    }
+  }
+
+  fn next_vacant(&self, from: usize, limit: usize) -> Option<usize> {
+    // Slots past the end of the last chunk have no bit set, so they are found
+    // along with the vacant slots. Past the last chunk, every index is vacant.
+    self.find(from, limit, |chunk| !chunk.used).or_else(|| {
+      let past_chunks = core::cmp::max(from, self.chunks.len() << CHUNK_BITS);
+      if past_chunks < limit {
+        Some(past_chunks)
+      } else {
+        None
+      }
+    })
+  }
+
+  fn next_used(&self, from: usize, limit: usize) -> Option<usize> {
+    self.find(from, core::cmp::min(limit, self.len), |chunk| chunk.used)
+  }
+
+  fn iter(&self) -> ChunkedIter<'_, T, I> {
+    ChunkedIter {
+      slots: ChunkedSlots::new(self.chunks.iter(), |chunk| {
+        // Chunks with nothing in them are passed over without visiting slots
+        let end = if chunk.used == 0 { 0 } else { chunk.slots.len() };
+        chunk.slots[..end].iter()
+      }),
+    }
+  }
+
+  fn iter_mut(&mut self) -> ChunkedIterMut<'_, T, I> {
+    ChunkedIterMut {
+      slots: ChunkedSlots::new(self.chunks.iter_mut(), |chunk| {
+        // A shared chunk is only copied once the iterator reaches it, and
+        // chunks with nothing in them are never copied
+        if chunk.used == 0 {
+          return <&mut [Slot<T, I>]>::default().iter_mut();
+        }
+        Arc::make_mut(chunk).slots.iter_mut()
+      }),
+    }
+  }
+
+  fn into_iter(self) -> Self::IntoIter {
+    ChunkedIntoIter {
+      slots: ChunkedSlots::new(self.chunks.into_iter(), |chunk| {
+        // Values in chunks still shared with another list are copied out
+        let chunk =
+          Arc::try_unwrap(chunk).unwrap_or_else(|chunk| (*chunk).clone());
+        chunk.slots.into_iter()
+      }),
+    }
+  }
+}
+
+impl<T, I> ChunkedStorage<T, I> {
+  /// Find the lowest index at or after `from`, and before `limit`, whose bit
+  /// is set in the word chosen from its chunk
+  fn find<F: Fn(&Chunk<T, I>) -> u64>(
+    &self,
+    from: usize,
+    limit: usize,
+    word: F,
+  ) -> Option<usize> {
+    let mut chunk = from >> CHUNK_BITS;
+    let mut bits = word(self.chunks.get(chunk)?) & (!0 << (from & CHUNK_MASK));
+    loop {
+      if bits != 0 {
+        let found = (chunk << CHUNK_BITS) + bits.trailing_zeros() as usize;
+        return if found < limit { Some(found) } else { None };
+      }
+      chunk += 1;
+      if chunk << CHUNK_BITS >= limit {
+        return None;
+      }
+      bits = word(self.chunks.get(chunk)?);
+    }
+  }
+}
+
+type ChunkIter<'a, T, I> = slice::Iter<'a, Arc<Chunk<T, I>>>;
+type ChunkIterMut<'a, T, I> = slice::IterMut<'a, Arc<Chunk<T, I>>>;
+type ChunkIntoIter<T, I> = vec::IntoIter<Arc<Chunk<T, I>>>;
+
+/// Walks the slots of a sequence of chunks from either end, along with their
+/// indices. Every chunk but the last is full, so the index of a slot is its
+/// chunk's position times the chunk size, plus its offset in the chunk.
+struct ChunkedSlots<C: Iterator, S> {
+  chunks: Enumerate<C>,
+  open: fn(C::Item) -> S,
+  front: Option<(usize, Enumerate<S>)>,
+  back: Option<(usize, Enumerate<S>)>,
+}
+
+impl<C, S> ChunkedSlots<C, S>
+where
+  C: DoubleEndedIterator + ExactSizeIterator,
+  S: DoubleEndedIterator + ExactSizeIterator,
+{
+  fn new(chunks: C, open: fn(C::Item) -> S) -> ChunkedSlots<C, S> {
+    ChunkedSlots {
+      chunks: chunks.enumerate(),
+      open,
+      front: None,
+      back: None,
+    }
+  }
+
+  fn next_slot(&mut self) -> Option<(usize, S::Item)> {
+    loop {
+      if let Some((base, slots)) = &mut self.front {
+        if let Some((offset, slot)) = slots.next() {
+          return Some((*base + offset, slot));
+        }
+        self.front = None;
+      }
+      match self.chunks.next() {
+        Some((chunk, slots)) => {
+          let slots = (self.open)(slots).enumerate();
+          self.front = Some((chunk << CHUNK_BITS, slots));
+        },
+        None => {
+          let (base, slots) = self.back.as_mut()?;
+          return slots.next().map(|(offset, slot)| (*base + offset, slot));
+        },
+      }
+    }
+  }
+
+  fn next_slot_back(&mut self) -> Option<(usize, S::Item)> {
+    loop {
+      if let Some((base, slots)) = &mut self.back {
+        if let Some((offset, slot)) = slots.next_back() {
+          return Some((*base + offset, slot));
+        }
+        self.back = None;
+      }
+      match self.chunks.next_back() {
+        Some((chunk, slots)) => {
+          let slots = (self.open)(slots).enumerate();
+          self.back = Some((chunk << CHUNK_BITS, slots));
+        },
+        None => {
+          let (base, slots) = self.front.as_mut()?;
+          return slots.next_back().map(|(offset, slot)| (*base + offset, slot));
+        },
+      }
+    }
+  }
+}
+
+impl<C: Iterator + Clone, S: Clone> Clone for ChunkedSlots<C, S> {
+  fn clone(&self) -> Self {
+    ChunkedSlots {
+      chunks: self.chunks.clone(),
+      open: self.open,
+      front: self.front.clone(),
+      back: self.back.clone(),
+    }
+  }
+}
+
+/// Iterator over the occupied slots of a ChunkedStorage
+pub struct ChunkedIter<'a, T, I> {
+  slots: ChunkedSlots<ChunkIter<'a, T, I>, slice::Iter<'a, Slot<T, I>>>,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for ChunkedIter<'a, T, I> {
+  type Item = (usize, &'a T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while let Some((index, slot)) = self.slots.next_slot() {
+      if let Some(value) = slot.as_option_of_ref() {
+        return Some((index, value));
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for ChunkedIter<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    while let Some((index, slot)) = self.slots.next_slot_back() {
+      if let Some(value) = slot.as_option_of_ref() {
+        return Some((index, value));
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for ChunkedIter<'a, T, I> {}
+
+impl<'a, T, I> Clone for ChunkedIter<'a, T, I> {
+  fn clone(&self) -> Self {
+    ChunkedIter {
+      slots: self.slots.clone(),
+    }
+  }
+}
+
+/// Iterator over the occupied slots of a ChunkedStorage, yielding mutable
+/// references. A shared chunk with a value in it is copied when the iterator
+/// reaches it, so chunks that are never reached stay shared.
+pub struct ChunkedIterMut<'a, T, I> {
+  slots: ChunkedSlots<ChunkIterMut<'a, T, I>, slice::IterMut<'a, Slot<T, I>>>,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for ChunkedIterMut<'a, T, I> {
+  type Item = (usize, &'a mut T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while let Some((index, slot)) = self.slots.next_slot() {
+      if let Some(value) = slot.as_mut() {
+        return Some((index, value));
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for ChunkedIterMut<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    while let Some((index, slot)) = self.slots.next_slot_back() {
+      if let Some(value) = slot.as_mut() {
+        return Some((index, value));
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for ChunkedIterMut<'a, T, I> {}
+
+/// Iterator that consumes a ChunkedStorage, yielding the value of each
+/// occupied slot
+pub struct ChunkedIntoIter<T, I> {
+  slots: ChunkedSlots<ChunkIntoIter<T, I>, vec::IntoIter<Slot<T, I>>>,
+}
+
+impl<T, I: SlotIndex> Iterator for ChunkedIntoIter<T, I> {
+  type Item = (usize, T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while let Some((index, slot)) = self.slots.next_slot() {
+      if let Some(value) = slot.occupied() {
+        return Some((index, value));
+      }
+    }
+    None
+  }
+}
+
+impl<T, I: SlotIndex> DoubleEndedIterator for ChunkedIntoIter<T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    while let Some((index, slot)) = self.slots.next_slot_back() {
+      if let Some(value) = slot.occupied() {
+        return Some((index, value));
+      }
+    }
+    None
+  }
+}
+
+impl<T, I: SlotIndex> FusedIterator for ChunkedIntoIter<T, I> {}
+
+#[cfg(test)]
+mod tests {
+  use super::{Arc, ChunkedStorage, Vec, CHUNK_SIZE};
+  use crate::list::{Slot, SlotList};
+  use crate::policy::AllocationPolicy;
+  use crate::storage::Storage;
+
+  fn shared_chunks<T, I>(
+    first: &ChunkedStorage<T, I>,
+    second: &ChunkedStorage<T, I>,
+  ) -> Vec<bool> {
+    let pairs = first.chunks.iter().zip(second.chunks.iter());
+    pairs.map(|(a, b)| Arc::ptr_eq(a, b)).collect()
+  }
+
+  #[test]
+  fn copying_only_changed_chunks() {
+    let mut storage: ChunkedStorage<u32> = ChunkedStorage::new();
+    for value in 0..(CHUNK_SIZE * 3) as u32 {
+      storage.push(Slot::Occupied { generation: 0, value });
+    }
+    let mut copy = storage.clone();
+    assert_eq!(shared_chunks(&storage, &copy), [true, true, true]);
+    // Reading doesn't copy anything, and neither does a missing value
+    assert_eq!(copy.get_value(CHUNK_SIZE + 1), Some(&(CHUNK_SIZE as u32 + 1)));
+    assert_eq!(copy.get_mut(CHUNK_SIZE * 3), None);
+    assert_eq!(shared_chunks(&storage, &copy), [true, true, true]);
+
+    *copy.get_mut(CHUNK_SIZE + 1).unwrap() = 1000;
+    assert_eq!(shared_chunks(&storage, &copy), [true, false, true]);
+    copy.take(2);
+    assert_eq!(shared_chunks(&storage, &copy), [false, false, true]);
+    let value = CHUNK_SIZE as u32 + 1;
+    assert_eq!(storage.get_value(CHUNK_SIZE + 1), Some(&value));
+    assert_eq!(storage.get_value(2), Some(&2));
+    assert_eq!(copy.get_value(2), None);
+    assert!(!copy.is_marked_used(2));
+    assert!(storage.is_marked_used(2));
+  }
+
+  #[test]
+  fn iterating_mutably_copies_lazily() {
+    let mut storage: ChunkedStorage<u32> = ChunkedStorage::new();
+    for value in 0..(CHUNK_SIZE * 3) as u32 {
+      storage.push(Slot::Occupied { generation: 0, value });
+    }
+    let mut copy = storage.clone();
+    let last = {
+      let mut values = copy.iter_mut();
+      *values.next().unwrap().1 = 1000;
+      let (last, value) = values.next_back().unwrap();
+      *value = 2000;
+      last
+    };
+    assert_eq!(shared_chunks(&storage, &copy), [false, true, false]);
+    assert_eq!(copy.get_value(0), Some(&1000));
+    assert_eq!(copy.get_value(last), Some(&2000));
+    assert_eq!(storage.get_value(0), Some(&0));
+    assert_eq!(storage.get_value(last), Some(&(last as u32)));
+  }
+
+  #[test]
+  fn searching_across_chunks() {
+    let mut storage: ChunkedStorage<u32> = ChunkedStorage::new();
+    for index in 0..150 {
+      if index % 70 == 3 {
+        storage.push(Slot::Occupied { generation: 0, value: index });
+      } else {
+        storage.push(Slot::unlinked(0));
+      }
+    }
+    assert_eq!(storage.next_used(0, usize::MAX), Some(3));
+    assert_eq!(storage.next_used(4, usize::MAX), Some(73));
+    assert_eq!(storage.next_used(74, 143), None);
+    assert_eq!(storage.next_used(74, usize::MAX), Some(143));
+    assert_eq!(storage.next_used(144, usize::MAX), None);
+    assert_eq!(storage.next_vacant(3, usize::MAX), Some(4));
+    assert_eq!(storage.next_vacant(143, usize::MAX), Some(144));
+    for index in 144..150 {
+      storage.replace(index, Slot::Reserved { generation: 0 });
+    }
+    // Indices past the end of the storage are vacant
+    assert_eq!(storage.next_vacant(143, usize::MAX), Some(150));
+    assert_eq!(storage.next_vacant(143, 150), None);
+    assert_eq!(storage.next_vacant(1000, 1001), Some(1000));
+    assert!(storage.iter().map(|(index, _)| index).eq([3, 73, 143]));
+    assert!(storage.iter().rev().map(|(index, _)| index).eq([143, 73, 3]));
+    assert!(storage.iter_mut().map(|(index, _)| index).eq([3, 73, 143]));
+    assert!(storage.into_iter().rev().map(|(_, value)| value).eq([143, 73, 3]));
+  }
+
+  #[test]
+  fn reserving_whole_chunks() {
+    let mut storage: ChunkedStorage<u32> = ChunkedStorage::new();
+    for value in 0..3 {
+      storage.push(Slot::Occupied { generation: 0, value });
+    }
+    let copy = storage.clone();
+    // The shared chunk that new slots go into is copied while reserving
+    storage.reserve(CHUNK_SIZE * 2).unwrap();
+    assert_eq!(storage.capacity(), CHUNK_SIZE * 3);
+    assert_eq!(shared_chunks(&storage, &copy), [false]);
+    let chunks: Vec<_> = storage.chunks.iter().map(Arc::as_ptr).collect();
+    let slots = |storage: &ChunkedStorage<u32>| -> Vec<_> {
+      storage.chunks.iter().map(|chunk| chunk.slots.as_ptr()).collect()
+    };
+    let before = slots(&storage);
+    for value in 3..(CHUNK_SIZE * 2) as u32 {
+      storage.push(Slot::Occupied { generation: 0, value });
+    }
+    // Pushing used the reserved chunks instead of allocating new ones
+    assert!(storage.chunks.iter().map(Arc::as_ptr).eq(chunks));
+    assert_eq!(slots(&storage), before);
+    assert_eq!(storage.capacity(), CHUNK_SIZE * 3);
+    // Reserved chunks are left out of clones, and dropped when shrinking
+    assert_eq!(storage.clone().capacity(), CHUNK_SIZE * 2);
+    storage.shrink_to_fit();
+    assert_eq!(storage.capacity(), CHUNK_SIZE * 2);
+
+    // Popping the only slot of a shared chunk drops the chunk without
+    // copying it
+    storage.push(Slot::Occupied { generation: 0, value: 1000 });
+    let copy = storage.clone();
+    let popped = storage.pop();
+    assert!(matches!(popped, Some(Slot::Occupied { value: 1000, .. })));
+    assert_eq!(storage.capacity(), CHUNK_SIZE * 2);
+    assert_eq!(shared_chunks(&storage, &copy), [true, true]);
+    assert_eq!(copy.get_value(CHUNK_SIZE * 2), Some(&1000));
+  }
+
+  #[test]
+  fn cloning_a_list() {
+    let policies = [
+      AllocationPolicy::Fifo,
+      AllocationPolicy::Lifo,
+      AllocationPolicy::LowestIndex,
+      AllocationPolicy::Cyclic,
+    ];
+    for policy in policies {
+      let storage = ChunkedStorage::<u32>::new();
+      let mut list =
+        SlotList::with_storage_policy_and_limit(storage, policy, usize::MAX);
+      for value in 0..300 {
+        list.insert(value);
+      }
+      for index in (0..300).filter(|index| index % 7 == 2) {
+        list.remove(index);
+      }
+      let mut copy = list.clone();
+      assert_eq!(copy.check_invariants(), Ok(()));
+      // Both lists fill their empty slots in the same order, without affecting
+      // each other
+      for value in 1000..1050 {
+        assert_eq!(copy.insert(value), list.insert(value + 1), "{:?}", policy);
+      }
+      let mut pairs = copy.iter_indexed().zip(list.iter_indexed());
+      assert!(pairs.all(|((index, a), (other, b))| {
+        index == other && (a == b || *a + 1 == *b)
+      }));
+      copy.remove(0);
+      assert_eq!(list.get(0), Some(&0));
+      assert_eq!(copy.get(0), None);
+      assert_eq!(list.check_invariants(), Ok(()));
+      assert_eq!(copy.check_invariants(), Ok(()));
+    }
+  }
+}
@@ -0,0 +1,880 @@
+#[cfg(feature = "std")]
+use std::boxed::Box;
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::Deref;
+use core::ptr;
+
+#[cfg(loom)]
+use loom::cell::{ConstPtr, UnsafeCell};
+#[cfg(loom)]
+use loom::sync::atomic::{
+  AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering,
+};
+#[cfg(not(loom))]
+use core::sync::atomic::{
+  AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering,
+};
+#[cfg(not(loom))]
+use cell::{ConstPtr, UnsafeCell};
+
+use crate::error::{InsertError, SlotListError};
+use crate::list::Key;
+
+/// The parts of loom's `UnsafeCell` API used below, so that the same code can
+/// be model-checked under loom
+#[cfg(not(loom))]
+mod cell {
+  pub(super) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+  impl<T> UnsafeCell<T> {
+    pub fn new(data: T) -> UnsafeCell<T> {
+      UnsafeCell(core::cell::UnsafeCell::new(data))
+    }
+
+    pub fn get(&self) -> ConstPtr<T> {
+      ConstPtr(self.0.get())
+    }
+
+    pub fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+      f(self.0.get())
+    }
+  }
+
+  pub(super) struct ConstPtr<T>(*const T);
+
+  impl<T> ConstPtr<T> {
+    pub unsafe fn deref(&self) -> &T {
+      &*self.0
+    }
+  }
+}
+
+/// Slots are allocated in segments that never move, so that a reader never
+/// has to wait for the table to grow. The first segment holds `FIRST_SEGMENT`
+/// slots, and each segment after it is twice the size of the one before.
+#[cfg(not(loom))]
+const FIRST_SEGMENT_BITS: u32 = 5;
+#[cfg(loom)]
+const FIRST_SEGMENT_BITS: u32 = 1;
+const FIRST_SEGMENT: usize = 1 << FIRST_SEGMENT_BITS;
+const SEGMENTS: usize = 32 - FIRST_SEGMENT_BITS as usize;
+
+/// Indices are stored in the free stack as `u32`, with `u32::MAX` marking its
+/// end, and the segments stop just short of it
+const MAX_SLOTS: usize = u32::MAX as usize - FIRST_SEGMENT + 1;
+const NO_SLOT: u32 = u32::MAX;
+
+// The state of each slot is a single word: the generation in the upper half,
+// followed by a flag for a slot that holds a readable value, and a flag for a
+// slot whose value has been removed but not yet dropped.
+const GENERATION_SHIFT: u32 = 32;
+const OCCUPIED: u64 = 1 << 31;
+const REMOVED: u64 = 1 << 30;
+
+/// Marks a hazard record that isn't covering any slot
+const NO_HAZARD: usize = usize::MAX;
+
+// Whether a thread is dropping retired values, and whether another thread has
+// asked for that since it last looked at the retired stack
+const RECLAIMING: u32 = 1;
+const PENDING: u32 = 2;
+
+fn generation(state: u64) -> u32 {
+  (state >> GENERATION_SHIFT) as u32
+}
+
+/// Determine whether a slot in a given state holds the value a key refers to
+fn holds_key(state: u64, key: Key) -> bool {
+  state & OCCUPIED != 0 && generation(state) == key.generation()
+}
+
+/// Find the segment holding an index, and the index's offset within it
+fn locate(index: usize) -> (usize, usize) {
+  let biased = index + FIRST_SEGMENT;
+  let top = usize::BITS - 1 - biased.leading_zeros();
+  ((top - FIRST_SEGMENT_BITS) as usize, biased - (1 << top))
+}
+
+fn segment_length(segment: usize) -> usize {
+  FIRST_SEGMENT << segment
+}
+
+struct SharedSlot<T> {
+  state: AtomicU64,
+  /// The slot below this one in the free stack or the retired stack, while
+  /// this slot is in one of them
+  next: AtomicU32,
+  value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> SharedSlot<T> {
+  fn new() -> SharedSlot<T> {
+    SharedSlot {
+      state: AtomicU64::new(0),
+      next: AtomicU32::new(NO_SLOT),
+      value: UnsafeCell::new(MaybeUninit::uninit()),
+    }
+  }
+}
+
+/// A record through which a guard announces the slot it is reading, so that
+/// the slot's value isn't dropped under it
+struct Hazard {
+  /// Whether a guard is using this record
+  claimed: AtomicBool,
+  /// The index of the slot being read, or `NO_HAZARD`
+  index: AtomicUsize,
+  /// The record after this one, which never changes once this one is shared
+  next: *mut Hazard,
+}
+
+/// A slot list that can be shared between threads without a lock, for tables
+/// of handles that are read far more often than they change.
+/// Values are addressed by generational `Key`s. Reading a value never waits
+/// for other threads: `get` returns a `SlotRef` guard even while other threads
+/// insert and remove values, and it finds every value that hasn't been
+/// removed. Removing a value takes effect immediately, so no new guard can be
+/// created for it, but the value is only dropped once the last guard already
+/// reading it is released. The slot is re-used after that.
+/// Insertions take the most recently freed slot from a lock-free stack, or a
+/// never-used slot if the stack is empty. The table grows by allocating new
+/// segments of slots, and existing slots never move.
+/// Removed values are reclaimed with hazard pointers. Rather than keeping a
+/// record for each thread, which every thread would have to register by hand
+/// without the thread-locals of `std`, each guard claims an idle record from a
+/// list kept by the table, and a new record is only allocated when every one
+/// is in use. Removing a value retires its slot, and retired values are dropped
+/// once no record covers their slot, so a long-lived guard only holds back its
+/// own value. `get` writes only to its own record, so guards on the same value
+/// don't contend with each other, and keys for removed values are turned away
+/// without a write. The records are kept until the table is dropped.
+pub struct ConcurrentSlotList<T> {
+  segments: [AtomicPtr<SharedSlot<T>>; SEGMENTS],
+  /// The top of the free stack, tagged with a counter in the upper half that
+  /// changes on every push and pop, so that a thread can't mistake a stack
+  /// that was popped and pushed again for one that never changed
+  free: AtomicU64,
+  /// The top of the stack of slots whose values were removed but not yet
+  /// dropped. Slots are only ever taken off all at once, so it needs no tag.
+  retired: AtomicU32,
+  /// The most recently added hazard record
+  hazards: AtomicPtr<Hazard>,
+  reclaimer: AtomicU32,
+  /// The lowest index that has never been used
+  fresh: AtomicUsize,
+  len: AtomicUsize,
+  marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for ConcurrentSlotList<T> {}
+// Any thread may read a value through a guard, and any thread may be the one
+// to drop it
+unsafe impl<T: Send + Sync> Sync for ConcurrentSlotList<T> {}
+
+impl<T> ConcurrentSlotList<T> {
+  pub fn new() -> ConcurrentSlotList<T> {
+    ConcurrentSlotList {
+      segments: core::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+      free: AtomicU64::new(NO_SLOT as u64),
+      retired: AtomicU32::new(NO_SLOT),
+      hazards: AtomicPtr::new(ptr::null_mut()),
+      reclaimer: AtomicU32::new(0),
+      fresh: AtomicUsize::new(0),
+      len: AtomicUsize::new(0),
+      marker: PhantomData,
+    }
+  }
+
+  /// The number of values in the list. Other threads may have changed it by
+  /// the time it is returned.
+  pub fn len(&self) -> usize {
+    self.len.load(Ordering::Relaxed)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Insert a new value into the list, returning the key used to access it.
+  /// Panics if the list already has as many slots as it can address.
+  pub fn insert(&self, item: T) -> Key {
+    match self.try_insert(item) {
+      Ok(key) => key,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Insert a new value into the list, like `insert`. If every slot the list
+  /// can address is occupied, the value is handed back inside of the error
+  /// instead.
+  pub fn try_insert(&self, item: T) -> Result<Key, InsertError<T>> {
+    let (index, slot) = match self.pop_free() {
+      Some(found) => found,
+      None => match self.claim_fresh() {
+        Some(found) => found,
+        None => {
+          let error = SlotListError::CapacityExceeded;
+          return Err(InsertError::new(error, item));
+        },
+      },
+    };
+    // No other thread can reach the value until the slot is marked as occupied
+    slot.value.with_mut(|value| unsafe { (*value).write(item) });
+    let state = slot.state.fetch_or(OCCUPIED, Ordering::Release);
+    self.len.fetch_add(1, Ordering::Relaxed);
+    Ok(Key::new(index, generation(state)))
+  }
+
+  /// Get a guard for the value stored with a key. The value won't be dropped
+  /// while the guard is held, even if another thread removes it.
+  /// Returns `None` only if the value has been removed.
+  pub fn get(&self, key: Key) -> Option<SlotRef<'_, T>> {
+    let slot = self.slot(key.index())?;
+    if !holds_key(slot.state.load(Ordering::Relaxed), key) {
+      return None;
+    }
+    let hazard = self.claim_hazard();
+    // Announcing the read with a read-modify-write orders it against a thread
+    // reclaiming values, which reads the record the same way after retiring
+    // the slot. Either that thread sees the announcement, or the check below
+    // sees the value's removal.
+    hazard.index.swap(key.index(), Ordering::AcqRel);
+    if !holds_key(slot.state.load(Ordering::Acquire), key) {
+      self.release_hazard(hazard, slot);
+      return None;
+    }
+    Some(SlotRef {
+      list: self,
+      index: key.index(),
+      slot,
+      hazard,
+      value: ManuallyDrop::new(slot.value.get()),
+    })
+  }
+
+  pub fn contains_key(&self, key: Key) -> bool {
+    match self.slot(key.index()) {
+      Some(slot) => holds_key(slot.state.load(Ordering::Relaxed), key),
+      None => false,
+    }
+  }
+
+  /// Remove the value stored with a key, returning whether there was one.
+  /// Once this returns, `get` no longer finds the value. It is dropped as soon
+  /// as every guard for it has been released, possibly by another thread.
+  pub fn remove(&self, key: Key) -> bool {
+    let slot = match self.slot(key.index()) {
+      Some(slot) => slot,
+      None => return false,
+    };
+    let mut state = slot.state.load(Ordering::Relaxed);
+    loop {
+      if !holds_key(state, key) {
+        return false;
+      }
+      // The generation changes now, rather than on the next insertion, so that
+      // stale keys are turned away while the value is still being read
+      let next_generation = generation(state).wrapping_add(1) as u64;
+      let exchanged = slot.state.compare_exchange_weak(
+        state,
+        (next_generation << GENERATION_SHIFT) | REMOVED,
+        Ordering::AcqRel,
+        Ordering::Relaxed,
+      );
+      match exchanged {
+        Ok(_) => break,
+        Err(current) => state = current,
+      }
+    }
+    self.len.fetch_sub(1, Ordering::Relaxed);
+    self.retire(key.index(), slot);
+    self.reclaim();
+    true
+  }
+
+  fn slot(&self, index: usize) -> Option<&SharedSlot<T>> {
+    if index >= MAX_SLOTS {
+      return None;
+    }
+    let (segment, offset) = locate(index);
+    let slots = self.segments[segment].load(Ordering::Acquire);
+    if slots.is_null() {
+      None
+    } else {
+      Some(unsafe { &*slots.add(offset) })
+    }
+  }
+
+  /// Take the lowest index that has never been used, allocating its segment
+  /// if no other thread has yet
+  fn claim_fresh(&self) -> Option<(usize, &SharedSlot<T>)> {
+    let index = self.fresh
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |fresh| {
+        if fresh < MAX_SLOTS { Some(fresh + 1) } else { None }
+      })
+      .ok()?;
+    let (segment, offset) = locate(index);
+    let length = segment_length(segment);
+    let mut slots = self.segments[segment].load(Ordering::Acquire);
+    if slots.is_null() {
+      let allocated: Box<[SharedSlot<T>]> =
+        (0..length).map(|_| SharedSlot::new()).collect();
+      let allocated = Box::into_raw(allocated) as *mut SharedSlot<T>;
+      let exchanged = self.segments[segment].compare_exchange(
+        ptr::null_mut(),
+        allocated,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+      );
+      slots = match exchanged {
+        Ok(_) => allocated,
+        Err(existing) => {
+          let allocated = ptr::slice_from_raw_parts_mut(allocated, length);
+          drop(unsafe { Box::from_raw(allocated) });
+          existing
+        },
+      };
+    }
+    Some((index, unsafe { &*slots.add(offset) }))
+  }
+
+  fn pop_free(&self) -> Option<(usize, &SharedSlot<T>)> {
+    let mut head = self.free.load(Ordering::Acquire);
+    loop {
+      let index = head as u32;
+      if index == NO_SLOT {
+        return None;
+      }
+      // The slot may be popped by another thread before this one finishes, in
+      // which case `next` is stale, but the tag makes the exchange fail
+      let slot = self.slot(index as usize).unwrap();
+      let next = slot.next.load(Ordering::Relaxed);
+      let popped = (((head >> 32) + 1) << 32) | next as u64;
+      let exchanged = self.free.compare_exchange_weak(
+        head,
+        popped,
+        Ordering::Acquire,
+        Ordering::Acquire,
+      );
+      match exchanged {
+        Ok(_) => return Some((index as usize, slot)),
+        Err(current) => head = current,
+      }
+    }
+  }
+
+  fn push_free(&self, index: usize, slot: &SharedSlot<T>) {
+    let mut head = self.free.load(Ordering::Relaxed);
+    loop {
+      slot.next.store(head as u32, Ordering::Relaxed);
+      let pushed = (((head >> 32) + 1) << 32) | index as u64;
+      let exchanged = self.free.compare_exchange_weak(
+        head,
+        pushed,
+        Ordering::Release,
+        Ordering::Relaxed,
+      );
+      match exchanged {
+        Ok(_) => return,
+        Err(current) => head = current,
+      }
+    }
+  }
+
+  /// Claim a hazard record that no guard is using, adding a new one to the
+  /// list if every record is taken
+  fn claim_hazard(&self) -> &Hazard {
+    let mut hazard = self.hazards.load(Ordering::Acquire);
+    while let Some(record) = unsafe { hazard.as_ref() } {
+      let claimed = record.claimed.compare_exchange(
+        false,
+        true,
+        Ordering::Acquire,
+        Ordering::Relaxed,
+      );
+      if claimed.is_ok() {
+        return record;
+      }
+      hazard = record.next;
+    }
+    let record = Box::into_raw(Box::new(Hazard {
+      claimed: AtomicBool::new(true),
+      index: AtomicUsize::new(NO_HAZARD),
+      next: ptr::null_mut(),
+    }));
+    let mut head = self.hazards.load(Ordering::Relaxed);
+    loop {
+      // The record isn't shared until the exchange succeeds
+      unsafe { (*record).next = head };
+      let exchanged = self.hazards.compare_exchange_weak(
+        head,
+        record,
+        Ordering::AcqRel,
+        Ordering::Relaxed,
+      );
+      match exchanged {
+        Ok(_) => return unsafe { &*record },
+        Err(current) => head = current,
+      }
+    }
+  }
+
+  /// Withdraw a hazard record's announcement and give the record back. A
+  /// thread reclaiming values may have passed over the slot because of this
+  /// record, so if the slot's value was removed, it is looked at again.
+  fn release_hazard(&self, hazard: &Hazard, slot: &SharedSlot<T>) {
+    // A read-modify-write, so that if a reclaiming thread saw the record, the
+    // removal it was reclaiming is seen below
+    hazard.index.swap(NO_HAZARD, Ordering::AcqRel);
+    hazard.claimed.store(false, Ordering::Release);
+    if slot.state.load(Ordering::Acquire) & REMOVED != 0 {
+      self.reclaim();
+    }
+  }
+
+  /// Determine whether a guard has announced that it is reading a slot, given
+  /// the most recently added hazard record
+  fn is_covered(&self, index: usize, mut hazard: *mut Hazard) -> bool {
+    while let Some(record) = unsafe { hazard.as_ref() } {
+      // A read-modify-write, to pair with the one announcing the read
+      if record.index.fetch_add(0, Ordering::AcqRel) == index {
+        return true;
+      }
+      hazard = record.next;
+    }
+    false
+  }
+
+  /// Push a slot whose value was removed onto the retired stack
+  fn retire(&self, index: usize, slot: &SharedSlot<T>) {
+    let mut head = self.retired.load(Ordering::Relaxed);
+    loop {
+      slot.next.store(head, Ordering::Relaxed);
+      let exchanged = self.retired.compare_exchange_weak(
+        head,
+        index as u32,
+        Ordering::Release,
+        Ordering::Relaxed,
+      );
+      match exchanged {
+        Ok(_) => return,
+        Err(current) => head = current,
+      }
+    }
+  }
+
+  /// Drop every retired value that no guard is reading, and free its slot.
+  /// One thread does this at a time. A thread that asks while another is busy
+  /// leaves it to that thread to go round again, so no request is missed.
+  fn reclaim(&self) {
+    let asked = self.reclaimer.fetch_or(RECLAIMING | PENDING, Ordering::AcqRel);
+    if asked & RECLAIMING != 0 {
+      return;
+    }
+    loop {
+      self.reclaimer.fetch_and(!PENDING, Ordering::AcqRel);
+      let mut index = self.retired.swap(NO_SLOT, Ordering::Acquire);
+      // Records added after the retired slots were taken are read with a
+      // read-modify-write of the head, so that their announcements are either
+      // seen or come late enough to see the removals
+      let mut hazards = self.hazards.load(Ordering::Relaxed);
+      while let Err(current) = self.hazards.compare_exchange_weak(
+        hazards,
+        hazards,
+        Ordering::AcqRel,
+        Ordering::Relaxed,
+      ) {
+        hazards = current;
+      }
+      while index != NO_SLOT {
+        let slot = self.slot(index as usize).unwrap();
+        let next = slot.next.load(Ordering::Relaxed);
+        if self.is_covered(index as usize, hazards) {
+          self.retire(index as usize, slot);
+        } else {
+          slot.value.with_mut(|value| unsafe { (*value).assume_init_drop() });
+          slot.state.fetch_and(!REMOVED, Ordering::Relaxed);
+          self.push_free(index as usize, slot);
+        }
+        index = next;
+      }
+      let finished = self.reclaimer.compare_exchange(
+        RECLAIMING,
+        0,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+      );
+      if finished.is_ok() {
+        return;
+      }
+    }
+  }
+}
+
+impl<T> Default for ConcurrentSlotList<T> {
+  fn default() -> Self {
+    ConcurrentSlotList::new()
+  }
+}
+
+impl<T> Drop for ConcurrentSlotList<T> {
+  fn drop(&mut self) {
+    for (segment, slots) in self.segments.iter().enumerate() {
+      let slots = slots.load(Ordering::Acquire);
+      if slots.is_null() {
+        break;
+      }
+      let slots = ptr::slice_from_raw_parts_mut(slots, segment_length(segment));
+      let slots = unsafe { Box::from_raw(slots) };
+      for slot in slots.iter() {
+        if slot.state.load(Ordering::Relaxed) & (OCCUPIED | REMOVED) != 0 {
+          slot.value.with_mut(|value| unsafe { (*value).assume_init_drop() });
+        }
+      }
+    }
+    let mut hazard = self.hazards.load(Ordering::Acquire);
+    while !hazard.is_null() {
+      let record = unsafe { Box::from_raw(hazard) };
+      hazard = record.next;
+    }
+  }
+}
+
+/// A reference to a value in a `ConcurrentSlotList`. The value is kept alive
+/// until the guard is dropped, even if it is removed from the list.
+pub struct SlotRef<'a, T> {
+  list: &'a ConcurrentSlotList<T>,
+  index: usize,
+  slot: &'a SharedSlot<T>,
+  hazard: &'a Hazard,
+  value: ManuallyDrop<ConstPtr<MaybeUninit<T>>>,
+}
+
+impl<'a, T> SlotRef<'a, T> {
+  pub fn index(&self) -> usize {
+    self.index
+  }
+}
+
+impl<'a, T> Deref for SlotRef<'a, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    unsafe { ConstPtr::deref(&self.value).assume_init_ref() }
+  }
+}
+
+impl<'a, T: core::fmt::Debug> core::fmt::Debug for SlotRef<'a, T> {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    core::fmt::Debug::fmt(&**self, formatter)
+  }
+}
+
+impl<'a, T> Drop for SlotRef<'a, T> {
+  fn drop(&mut self) {
+    // The pointer has to go before the hazard record is released, since
+    // releasing it may drop the value
+    unsafe { ManuallyDrop::drop(&mut self.value) };
+    self.list.release_hazard(self.hazard, self.slot);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[cfg(not(feature = "std"))]
+  extern crate std;
+
+  use super::{locate, segment_length, ConcurrentSlotList, FIRST_SEGMENT};
+  #[cfg(loom)]
+  use crate::list::Key;
+  #[cfg(loom)]
+  use loom::sync::Arc;
+  #[cfg(loom)]
+  use loom::sync::atomic::{AtomicUsize, Ordering};
+  #[cfg(loom)]
+  use loom::thread;
+  #[cfg(not(loom))]
+  use std::sync::Arc;
+  #[cfg(not(loom))]
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  #[cfg(not(loom))]
+  use std::thread;
+  #[cfg(not(loom))]
+  use std::vec::Vec;
+
+  /// Counts how many times it has been dropped
+  struct Tracked(usize, Arc<AtomicUsize>);
+
+  impl Drop for Tracked {
+    fn drop(&mut self) {
+      self.1.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+
+  #[test]
+  fn locating_indices() {
+    assert_eq!(locate(0), (0, 0));
+    assert_eq!(locate(FIRST_SEGMENT - 1), (0, FIRST_SEGMENT - 1));
+    assert_eq!(locate(FIRST_SEGMENT), (1, 0));
+    assert_eq!(locate(FIRST_SEGMENT * 3), (2, 0));
+    let last = super::SEGMENTS - 1;
+    assert_eq!(locate(super::MAX_SLOTS - 1), (last, segment_length(last) - 1));
+  }
+
+  #[cfg(not(loom))]
+  #[test]
+  fn inserting_and_removing() {
+    let list = ConcurrentSlotList::new();
+    let keys: Vec<_> = (0..100).map(|value| list.insert(value)).collect();
+    assert_eq!(list.len(), 100);
+    assert_eq!(*list.get(keys[40]).unwrap(), 40);
+    assert!(list.remove(keys[40]));
+    assert!(!list.remove(keys[40]));
+    assert!(list.get(keys[40]).is_none());
+    assert!(!list.contains_key(keys[40]));
+    // The freed slot is re-used with a new generation
+    let key = list.insert(1000);
+    assert_eq!(key.index(), 40);
+    assert!(list.get(keys[40]).is_none());
+    assert_eq!(*list.get(key).unwrap(), 1000);
+    assert_eq!(list.len(), 100);
+  }
+
+  #[cfg(not(loom))]
+  #[test]
+  fn removing_while_reading() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let list = ConcurrentSlotList::new();
+    let key = list.insert(Tracked(7, drops.clone()));
+    let first = list.get(key).unwrap();
+    let second = list.get(key).unwrap();
+    assert!(list.remove(key));
+    assert!(list.get(key).is_none());
+    // The slot isn't re-used until the value is dropped
+    assert_ne!(list.insert(Tracked(8, drops.clone())).index(), key.index());
+    drop(first);
+    assert_eq!(second.0, 7);
+    assert_eq!(drops.load(Ordering::Relaxed), 0);
+    drop(second);
+    assert_eq!(drops.load(Ordering::Relaxed), 1);
+    assert_eq!(list.insert(Tracked(9, drops.clone())).index(), key.index());
+    drop(list);
+    assert_eq!(drops.load(Ordering::Relaxed), 3);
+  }
+
+  #[cfg(not(loom))]
+  #[test]
+  fn holding_many_guards() {
+    let hazard_records = |list: &ConcurrentSlotList<u32>| {
+      let mut hazard = list.hazards.load(Ordering::Relaxed);
+      let mut records = 0;
+      while let Some(record) = unsafe { hazard.as_ref() } {
+        records += 1;
+        hazard = record.next;
+      }
+      records
+    };
+    let list = ConcurrentSlotList::new();
+    let key = list.insert(3);
+    let other = list.insert(4);
+    // There is no limit on how many guards can be held on one value
+    let guards: Vec<_> = (0..1000).map(|_| list.get(key).unwrap()).collect();
+    assert!(guards.iter().all(|guard| **guard == 3));
+    assert_eq!(hazard_records(&list), 1000);
+    assert!(list.remove(key));
+    assert!(list.get(key).is_none());
+    assert!(list.insert(5).index() > other.index());
+    drop(guards);
+    // Released records are claimed again rather than adding more
+    let guards: Vec<_> = (0..10).map(|_| list.get(other).unwrap()).collect();
+    assert!(guards.iter().all(|guard| **guard == 4));
+    assert_eq!(hazard_records(&list), 1000);
+    assert_eq!(list.insert(6).index(), key.index());
+  }
+
+  #[cfg(not(loom))]
+  #[test]
+  fn sharing_between_threads() {
+    let threads = 4;
+    let steps = if cfg!(miri) { 50 } else { 5000 };
+    let drops = Arc::new(AtomicUsize::new(0));
+    let list = Arc::new(ConcurrentSlotList::new());
+    let handles: Vec<_> = (0..threads).map(|thread| {
+      let list = list.clone();
+      let drops = drops.clone();
+      thread::spawn(move || {
+        let mut keys = Vec::new();
+        for step in 0..steps {
+          let value = thread * steps + step;
+          keys.push((list.insert(Tracked(value, drops.clone())), value));
+          if step % 3 == 2 {
+            let (key, _) = keys.swap_remove(step % keys.len());
+            assert!(list.remove(key));
+          }
+          for (key, value) in keys.iter().rev().take(4) {
+            assert_eq!(list.get(*key).unwrap().0, *value);
+          }
+        }
+        keys
+      })
+    }).collect();
+    let mut keys: Vec<_> = handles
+      .into_iter()
+      .flat_map(|handle| handle.join().unwrap())
+      .collect();
+    assert_eq!(list.len(), keys.len());
+    keys.sort_by_key(|(key, _)| key.index());
+    keys.dedup_by_key(|(key, _)| key.index());
+    assert_eq!(list.len(), keys.len());
+    for (key, value) in keys {
+      assert_eq!(list.get(key).unwrap().0, value);
+    }
+    assert_eq!(drops.load(Ordering::Relaxed), threads * steps - list.len());
+  }
+
+  // Run these with `RUSTFLAGS="--cfg loom" cargo test --release concurrent`
+
+  #[cfg(loom)]
+  #[test]
+  fn loom_concurrent_inserts() {
+    loom::model(|| {
+      let list = Arc::new(ConcurrentSlotList::new());
+      // Fill the first segment, so that both threads race to allocate the next
+      let first = list.insert(0);
+      let second = list.insert(1);
+      let other = list.clone();
+      let handle = thread::spawn(move || other.insert(2));
+      let key = list.insert(3);
+      let other_key = handle.join().unwrap();
+      assert_ne!(key.index(), other_key.index());
+      assert_eq!(*list.get(key).unwrap(), 3);
+      assert_eq!(*list.get(other_key).unwrap(), 2);
+      assert_eq!(*list.get(first).unwrap(), 0);
+      assert_eq!(*list.get(second).unwrap(), 1);
+    });
+  }
+
+  #[cfg(loom)]
+  #[test]
+  fn loom_reading_new_values() {
+    loom::model(|| {
+      let list = Arc::new(ConcurrentSlotList::new());
+      let stale = list.insert(0);
+      list.remove(stale);
+      let other = list.clone();
+      let handle = thread::spawn(move || other.insert(7));
+      // The freed slot is re-used with the next generation, so its key is
+      // known before it is handed out
+      let key = Key::new(stale.index(), stale.generation() + 1);
+      if let Some(value) = list.get(key) {
+        assert_eq!(*value, 7);
+      }
+      assert_eq!(handle.join().unwrap(), key);
+    });
+  }
+
+  #[cfg(loom)]
+  #[test]
+  fn loom_removing_while_reading() {
+    loom::model(|| {
+      let drops = Arc::new(AtomicUsize::new(0));
+      let list = Arc::new(ConcurrentSlotList::new());
+      let key = list.insert(Tracked(5, drops.clone()));
+      let other = list.clone();
+      let handle = thread::spawn(move || {
+        if let Some(value) = other.get(key) {
+          assert_eq!(value.0, 5);
+        }
+      });
+      assert!(list.remove(key));
+      // Once the slot is free again, a stale key must not reach the new value
+      let new_key = list.insert(Tracked(6, drops.clone()));
+      handle.join().unwrap();
+      assert_eq!(drops.load(Ordering::Relaxed), 1);
+      assert!(list.get(key).is_none());
+      assert_eq!(list.get(new_key).unwrap().0, 6);
+    });
+  }
+
+  #[cfg(loom)]
+  #[test]
+  fn loom_reading_present_values() {
+    loom::model(|| {
+      let list = Arc::new(ConcurrentSlotList::new());
+      let key = list.insert(1);
+      let removed = list.insert(2);
+      let other = list.clone();
+      // A value that stays in the list is always found, however many guards
+      // are held on it, and whatever happens to other values meanwhile
+      let handle = thread::spawn(move || {
+        let guard = other.get(key).unwrap();
+        assert_eq!(*other.get(key).unwrap(), 1);
+        *guard
+      });
+      let guard = list.get(key).unwrap();
+      assert!(list.remove(removed));
+      assert_eq!(*guard, 1);
+      assert_eq!(handle.join().unwrap(), 1);
+    });
+  }
+
+  #[cfg(loom)]
+  #[test]
+  fn loom_releasing_last_guard() {
+    loom::model(|| {
+      let drops = Arc::new(AtomicUsize::new(0));
+      let list = Arc::new(ConcurrentSlotList::new());
+      let key = list.insert(Tracked(5, drops.clone()));
+      let guard = list.get(key).unwrap();
+      let other = list.clone();
+      // The value is dropped by whichever thread releases the last guard,
+      // even while another thread is reclaiming
+      let handle = thread::spawn(move || {
+        let guard = other.get(key);
+        assert!(other.remove(key));
+        guard.map(|guard| guard.0)
+      });
+      assert_eq!(guard.0, 5);
+      drop(guard);
+      assert!(matches!(handle.join().unwrap(), Some(5) | None));
+      assert_eq!(drops.load(Ordering::Relaxed), 1);
+      assert!(list.get(key).is_none());
+    });
+  }
+
+  #[cfg(loom)]
+  #[test]
+  fn loom_free_stack() {
+    loom::model(|| {
+      let list = Arc::new(ConcurrentSlotList::new());
+      let first = list.insert(0);
+      let second = list.insert(1);
+      list.remove(first);
+      list.remove(second);
+      let other = list.clone();
+      // While one thread pops from the free stack, the other pops both slots
+      // and pushes one of them back, leaving the same slot on top
+      let handle = thread::spawn(move || {
+        let popped = other.insert(2);
+        let kept = other.insert(3);
+        other.remove(popped);
+        kept
+      });
+      let key = list.insert(4);
+      let kept = handle.join().unwrap();
+      let last = list.insert(5);
+      assert_ne!(key.index(), kept.index());
+      assert_ne!(last.index(), kept.index());
+      assert_ne!(last.index(), key.index());
+      assert_eq!(*list.get(kept).unwrap(), 3);
+      assert_eq!(*list.get(key).unwrap(), 4);
+      assert_eq!(*list.get(last).unwrap(), 5);
+    });
+  }
+}
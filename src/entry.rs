@@ -0,0 +1,120 @@
+use crate::index::SlotIndex;
+use crate::list::{Key, SlotList};
+use crate::storage::{Storage, VecStorage};
+
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Hands out the identities that tie each Reservation to the list that made it
+static NEXT_LIST_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Identifies a list that has made a Reservation
+pub(crate) type ListId = NonZeroUsize;
+
+pub(crate) fn next_list_id() -> ListId {
+  loop {
+    #[cfg(target_has_atomic = "ptr")]
+    let id = NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed);
+    // Without atomic read-modify-write, lists that reserve a slot for the first
+    // time at the same moment on different cores might share an identity
+    #[cfg(not(target_has_atomic = "ptr"))]
+    let id = {
+      let id = NEXT_LIST_ID.load(Ordering::Relaxed);
+      NEXT_LIST_ID.store(id.wrapping_add(1), Ordering::Relaxed);
+      id
+    };
+    // Zero is skipped if the counter ever wraps around
+    if let Some(id) = NonZeroUsize::new(id) {
+      return id;
+    }
+  }
+}
+
+/// A handle to the empty slot that the next insertion into a SlotList will
+/// use. This allows the index of a value to be known before the value itself
+/// is constructed. Creating an entry only reserves memory, so dropping it
+/// without inserting leaves the contents of the list exactly as they were.
+pub struct VacantEntry<'a, T, I = usize, S = VecStorage<T, I>>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  list: &'a mut SlotList<T, I, S>,
+  index: usize,
+}
+
+impl<'a, T, I: SlotIndex, S: Storage<T, I>> VacantEntry<'a, T, I, S> {
+  pub(crate) fn new(
+    list: &'a mut SlotList<T, I, S>,
+    index: usize,
+  ) -> VacantEntry<'a, T, I, S> {
+    VacantEntry { list, index }
+  }
+
+  /// The index the value will be stored at
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  /// The generational Key the value will have once it is inserted
+  pub fn key(&self) -> Key {
+    self.list.next_key(self.index)
+  }
+
+  /// Store a value in the slot, returning a mutable reference to it
+  pub fn insert(self, value: T) -> &'a mut T {
+    self.list.fill_vacant_entry(self.index, value);
+    self.list.get_mut(self.index).unwrap()
+  }
+}
+
+impl<'a, T, I, S> core::fmt::Debug for VacantEntry<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    formatter.debug_struct("VacantEntry")
+      .field("index", &self.index)
+      .finish()
+  }
+}
+
+/// A slot that has been set aside by `SlotList::reserve`, for a value that
+/// will be stored there later. Unlike a VacantEntry, a Reservation doesn't
+/// borrow the list, so the list can be used normally while the value is being
+/// prepared. Every Reservation must be handed back to the list that created
+/// it, through either `commit` or `cancel`; a Reservation that is dropped
+/// instead leaves its slot reserved for as long as the list exists. A clone
+/// of the list counts as a different list, so the clone's copy of the slot
+/// stays reserved.
+#[must_use = "a reserved slot stays reserved until committed or cancelled"]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reservation {
+  list: ListId,
+  index: usize,
+  generation: u32,
+}
+
+impl Reservation {
+  pub(crate) fn new(
+    list: ListId,
+    index: usize,
+    generation: u32,
+  ) -> Reservation {
+    Reservation { list, index, generation }
+  }
+
+  /// The index that has been reserved
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  pub(crate) fn generation(&self) -> u32 {
+    self.generation
+  }
+
+  pub(crate) fn list(&self) -> ListId {
+    self.list
+  }
+}
@@ -0,0 +1,248 @@
+/// Describes why an operation on a SlotList could not be completed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlotListError {
+  /// The index is past the end of the list
+  OutOfBounds,
+  /// The operation required an empty slot, but the slot holds a value
+  Occupied,
+  /// The operation required a value, but the slot is empty
+  Vacant,
+  /// Every slot below the list's limit is already occupied
+  CapacityExceeded,
+  /// The list needed to grow, but memory could not be allocated
+  AllocFailed,
+  /// The list needed to grow, but the new index would not fit in the list's
+  /// index type
+  IndexOverflow,
+  /// The chain of empty slots no longer matches the contents of the list.
+  /// The list may have been partially modified by the failed operation.
+  ChainCorrupted,
+  /// The key refers to a value that has since been removed
+  StaleKey,
+  /// The slot is reserved, and can only be filled or released through its
+  /// Reservation
+  Reserved,
+  /// The Reservation doesn't match a reserved slot in this list
+  UnknownReservation,
+  /// The record of which slots are in use disagrees with itself or with the
+  /// list, and there is nothing to rebuild it from
+  OccupancyCorrupted,
+}
+
+impl core::fmt::Display for SlotListError {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let message = match self {
+      SlotListError::OutOfBounds => "index out of bounds",
+      SlotListError::Occupied => "slot is occupied",
+      SlotListError::Vacant => "slot is empty",
+      SlotListError::CapacityExceeded => {
+        "no empty slot is available below the list's limit"
+      },
+      SlotListError::AllocFailed => "memory allocation failed",
+      SlotListError::IndexOverflow => {
+        "index does not fit in the list's index type"
+      },
+      SlotListError::ChainCorrupted => "empty slot chain was broken",
+      SlotListError::StaleKey => "key refers to a value that was removed",
+      SlotListError::Reserved => "slot is reserved",
+      SlotListError::UnknownReservation => {
+        "reservation does not match a reserved slot"
+      },
+      SlotListError::OccupancyCorrupted => "occupancy bitmap was corrupted",
+    };
+    formatter.write_str(message)
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SlotListError {}
+
+/// Returned when a value could not be stored in a SlotList. The value is
+/// handed back to the caller, so that it isn't lost.
+#[derive(Clone, PartialEq, Eq)]
+pub struct InsertError<T> {
+  kind: SlotListError,
+  value: T,
+}
+
+impl<T> InsertError<T> {
+  pub(crate) fn new(kind: SlotListError, value: T) -> InsertError<T> {
+    InsertError { kind, value }
+  }
+
+  pub fn kind(&self) -> SlotListError {
+    self.kind
+  }
+
+  /// Recover the value that could not be stored
+  pub fn into_inner(self) -> T {
+    self.value
+  }
+}
+
+impl<T> core::fmt::Debug for InsertError<T> {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    formatter.debug_struct("InsertError")
+      .field("kind", &self.kind)
+      .finish_non_exhaustive()
+  }
+}
+
+impl<T> core::fmt::Display for InsertError<T> {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    core::fmt::Display::fmt(&self.kind, formatter)
+  }
+}
+
+#[cfg(feature = "std")]
+impl<T> std::error::Error for InsertError<T> {}
+
+/// Describes a specific way in which the internal bookkeeping of a SlotList
+/// has become inconsistent
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+  /// A link in the empty chain points past the end of the list
+  ChainOutOfBounds { index: usize },
+  /// A link in the empty chain points to a slot that is occupied or reserved
+  OccupiedInChain { index: usize },
+  /// Following the empty chain visits the same slot twice
+  ChainCycle { index: usize },
+  /// A slot's link to the previous slot in the chain is wrong
+  BrokenBackLink {
+    index: usize,
+    expected: Option<usize>,
+    found: Option<usize>,
+  },
+  /// The pointer to the last slot in the chain doesn't match the chain
+  WrongTail { expected: Option<usize>, found: Option<usize> },
+  /// An empty slot can't be reached by following the chain
+  UnreachableEmptySlot { index: usize },
+  /// The occupancy bitmap disagrees with the contents of a slot
+  OccupancyMismatch { index: usize },
+  /// The stored count of values doesn't match the number of occupied slots
+  WrongLength { expected: usize, found: usize },
+  /// The stored count of empty slots doesn't match the list
+  WrongVacantCount { expected: usize, found: usize },
+  /// The stored count of reserved slots doesn't match the list
+  WrongReservedCount { expected: usize, found: usize },
+}
+
+impl core::fmt::Display for InvariantViolation {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    match self {
+      InvariantViolation::ChainOutOfBounds { index } => {
+        write!(formatter, "empty chain links to out-of-bounds index {}", index)
+      },
+      InvariantViolation::OccupiedInChain { index } => {
+        write!(formatter, "empty chain links to non-empty slot {}", index)
+      },
+      InvariantViolation::ChainCycle { index } => {
+        write!(formatter, "empty chain visits slot {} more than once", index)
+      },
+      InvariantViolation::BrokenBackLink { index, expected, found } => write!(
+        formatter,
+        "slot {} links back to {:?}, expected {:?}",
+        index, found, expected,
+      ),
+      InvariantViolation::WrongTail { expected, found } => write!(
+        formatter,
+        "empty chain ends at {:?}, but the tail pointer is {:?}",
+        expected, found,
+      ),
+      InvariantViolation::UnreachableEmptySlot { index } => {
+        write!(formatter, "empty slot {} is not part of the empty chain", index)
+      },
+      InvariantViolation::OccupancyMismatch { index } => {
+        write!(formatter, "occupancy bitmap disagrees with slot {}", index)
+      },
+      InvariantViolation::WrongLength { expected, found } => write!(
+        formatter,
+        "list has {} values, but its length is {}",
+        expected, found,
+      ),
+      InvariantViolation::WrongVacantCount { expected, found } => write!(
+        formatter,
+        "list has {} empty slots, but its count is {}",
+        expected, found,
+      ),
+      InvariantViolation::WrongReservedCount { expected, found } => write!(
+        formatter,
+        "list has {} reserved slots, but its count is {}",
+        expected, found,
+      ),
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvariantViolation {}
+
+/// Describes why a snapshot of a SlotList could not be written or read
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+  /// The input doesn't begin with the snapshot magic number
+  NotASnapshot,
+  /// The snapshot was written in a version of the format that isn't supported
+  UnsupportedVersion { version: u8 },
+  /// The snapshot was written by a list with a different index type. Widths
+  /// are in bytes.
+  IndexWidthMismatch { expected: u8, found: u8 },
+  /// The input ends before the snapshot does
+  Truncated,
+  /// The checksum at the end of the snapshot doesn't match its contents
+  ChecksumMismatch,
+  /// The snapshot contains a field that can't be decoded
+  Malformed,
+  /// The snapshot has more slots than the list's limit, storage, or index
+  /// type allow
+  TooManySlots,
+  /// The value codec couldn't decode the value stored at this index
+  InvalidValue { index: usize },
+  /// The restored list would be inconsistent
+  InvalidList(InvariantViolation),
+  /// The sink ran out of room for the snapshot
+  SinkFull,
+  /// Memory could not be allocated
+  AllocFailed,
+}
+
+impl core::fmt::Display for SnapshotError {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    match self {
+      SnapshotError::NotASnapshot => {
+        formatter.write_str("input is not a snapshot")
+      },
+      SnapshotError::UnsupportedVersion { version } => write!(
+        formatter,
+        "snapshot format version {} is not supported",
+        version,
+      ),
+      SnapshotError::IndexWidthMismatch { expected, found } => write!(
+        formatter,
+        "snapshot has {}-byte indices, but the list has {}-byte indices",
+        found, expected,
+      ),
+      SnapshotError::Truncated => formatter.write_str("snapshot is truncated"),
+      SnapshotError::ChecksumMismatch => {
+        formatter.write_str("snapshot checksum does not match")
+      },
+      SnapshotError::Malformed => formatter.write_str("snapshot is malformed"),
+      SnapshotError::TooManySlots => {
+        formatter.write_str("snapshot has more slots than the list can hold")
+      },
+      SnapshotError::InvalidValue { index } => {
+        write!(formatter, "value at index {} could not be decoded", index)
+      },
+      SnapshotError::InvalidList(violation) => {
+        write!(formatter, "snapshot is inconsistent: {}", violation)
+      },
+      SnapshotError::SinkFull => formatter.write_str("sink is full"),
+      SnapshotError::AllocFailed => {
+        formatter.write_str("memory allocation failed")
+      },
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnapshotError {}
@@ -0,0 +1,113 @@
+mod sealed {
+  pub trait Sealed {}
+}
+
+/// An unsigned integer type that a SlotList can use to link its empty slots
+/// together. Narrower types make each empty slot smaller, at the cost of
+/// limiting how many slots the list can hold.
+/// An empty slot holds two links and a generation, alongside the tag that
+/// every slot carries, so a slot of a `SlotList<u32, u32>` takes 16 bytes,
+/// where one of a `SlotList<u32>` takes 24 on a 64-bit target.
+/// This trait is sealed, and is implemented for `u16`, `u32`, and `usize`.
+pub trait SlotIndex: Copy + Eq + sealed::Sealed {
+  /// The largest value of the type, which is reserved to mean "no slot"
+  const NONE: Self;
+
+  /// The number of slots a list can hold when linked with this type. Every
+  /// index below this fits in the type without colliding with `NONE`.
+  const MAX_SLOTS: usize;
+
+  /// Convert an index that is already known to be below `MAX_SLOTS`
+  fn from_usize(index: usize) -> Self;
+
+  fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_slot_index {
+  ($type:ty) => {
+    impl sealed::Sealed for $type {}
+
+    impl SlotIndex for $type {
+      const NONE: $type = <$type>::MAX;
+      const MAX_SLOTS: usize =
+        if (<$type>::MAX as u128) < (usize::MAX as u128) {
+          <$type>::MAX as usize
+        } else {
+          usize::MAX
+        };
+
+      fn from_usize(index: usize) -> $type {
+        debug_assert!(index < Self::MAX_SLOTS);
+        index as $type
+      }
+
+      fn to_usize(self) -> usize {
+        self as usize
+      }
+    }
+  };
+}
+
+impl_slot_index!(u16);
+impl_slot_index!(u32);
+impl_slot_index!(usize);
+
+/// A link from one empty slot to another, or to nothing. Rather than wrapping
+/// the index in an `Option`, which would double its size, the largest value of
+/// the index type stands in for `None`.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Link<I>(I);
+
+impl<I: SlotIndex> Link<I> {
+  pub const NONE: Link<I> = Link(I::NONE);
+
+  pub fn new(index: Option<usize>) -> Link<I> {
+    match index {
+      Some(index) => Link(I::from_usize(index)),
+      None => Link::NONE,
+    }
+  }
+
+  pub fn get(self) -> Option<usize> {
+    if self.0 == I::NONE {
+      None
+    } else {
+      Some(self.0.to_usize())
+    }
+  }
+}
+
+impl<I: SlotIndex> core::fmt::Debug for Link<I> {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    core::fmt::Debug::fmt(&self.get(), formatter)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Link, SlotIndex};
+  use crate::list::Slot;
+
+  #[test]
+  fn encoding_links() {
+    assert_eq!(Link::<u16>::new(None).get(), None);
+    assert_eq!(Link::<u16>::new(Some(0)).get(), Some(0));
+    assert_eq!(Link::<u16>::new(Some(65534)).get(), Some(65534));
+    assert_eq!(Link::<u32>::new(Some(7)).get(), Some(7));
+    let last = usize::MAX - 1;
+    assert_eq!(Link::<usize>::new(Some(last)).get(), Some(last));
+    assert_eq!(core::mem::size_of::<Link<u16>>(), 2);
+    // An empty slot holds its generation and two links, plus the enum's tag
+    assert_eq!(core::mem::size_of::<Slot<u32, u32>>(), 16);
+    assert_eq!(core::mem::size_of::<Slot<u32, u16>>(), 12);
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(core::mem::size_of::<Slot<u32, usize>>(), 24);
+  }
+
+  #[test]
+  fn slot_limits() {
+    assert_eq!(<u16 as SlotIndex>::MAX_SLOTS, 65535);
+    assert_eq!(<u32 as SlotIndex>::MAX_SLOTS, u32::MAX as usize);
+    assert_eq!(<usize as SlotIndex>::MAX_SLOTS, usize::MAX);
+  }
+}
@@ -0,0 +1,482 @@
+use core::iter::FusedIterator;
+
+use crate::index::SlotIndex;
+use crate::list::SlotList;
+use crate::storage::{Storage, VecStorage};
+
+/// Iterator over the occupied slots of a SlotList, yielding each index along
+/// with a reference to the value stored there
+pub struct Iter<'a, T, I = usize, S = VecStorage<T, I>>
+where
+  T: 'a,
+  I: SlotIndex,
+  S: Storage<T, I> + 'a,
+{
+  slots: S::Iter<'a>,
+  remaining: usize,
+}
+
+impl<'a, T: 'a, I: SlotIndex, S: Storage<T, I> + 'a> Iter<'a, T, I, S> {
+  pub(crate) fn new(slots: S::Iter<'a>, remaining: usize) -> Iter<'a, T, I, S> {
+    Iter { slots, remaining }
+  }
+}
+
+impl<'a, T, I, S> Iterator for Iter<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  type Item = (usize, &'a T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let entry = self.slots.next()?;
+    self.remaining -= 1;
+    Some(entry)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a, T, I, S> DoubleEndedIterator for Iter<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let entry = self.slots.next_back()?;
+    self.remaining -= 1;
+    Some(entry)
+  }
+}
+
+impl<'a, T, I, S> ExactSizeIterator for Iter<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> FusedIterator for Iter<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> Clone for Iter<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn clone(&self) -> Self {
+    Iter {
+      slots: self.slots.clone(),
+      remaining: self.remaining,
+    }
+  }
+}
+
+/// Iterator over the occupied slots of a SlotList, yielding each index along
+/// with a mutable reference to the value stored there
+pub struct IterMut<'a, T, I = usize, S = VecStorage<T, I>>
+where
+  T: 'a,
+  I: SlotIndex,
+  S: Storage<T, I> + 'a,
+{
+  slots: S::IterMut<'a>,
+  remaining: usize,
+}
+
+impl<'a, T: 'a, I: SlotIndex, S: Storage<T, I> + 'a> IterMut<'a, T, I, S> {
+  pub(crate) fn new(
+    slots: S::IterMut<'a>,
+    remaining: usize,
+  ) -> IterMut<'a, T, I, S> {
+    IterMut { slots, remaining }
+  }
+}
+
+impl<'a, T, I, S> Iterator for IterMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  type Item = (usize, &'a mut T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let entry = self.slots.next()?;
+    self.remaining -= 1;
+    Some(entry)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a, T, I, S> DoubleEndedIterator for IterMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let entry = self.slots.next_back()?;
+    self.remaining -= 1;
+    Some(entry)
+  }
+}
+
+impl<'a, T, I, S> ExactSizeIterator for IterMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> FusedIterator for IterMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+/// Iterator that consumes a SlotList, yielding each occupied index along with
+/// the value stored there
+pub struct IntoIter<T, I = usize, S = VecStorage<T, I>>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  slots: S::IntoIter,
+  remaining: usize,
+}
+
+impl<T, I: SlotIndex, S: Storage<T, I>> IntoIter<T, I, S> {
+  pub(crate) fn new(slots: S::IntoIter, remaining: usize) -> IntoIter<T, I, S> {
+    IntoIter { slots, remaining }
+  }
+}
+
+impl<T, I: SlotIndex, S: Storage<T, I>> Iterator for IntoIter<T, I, S> {
+  type Item = (usize, T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let entry = self.slots.next()?;
+    self.remaining -= 1;
+    Some(entry)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<T, I, S> DoubleEndedIterator for IntoIter<T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let entry = self.slots.next_back()?;
+    self.remaining -= 1;
+    Some(entry)
+  }
+}
+
+impl<T, I, S> ExactSizeIterator for IntoIter<T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<T, I: SlotIndex, S: Storage<T, I>> FusedIterator for IntoIter<T, I, S> {}
+
+/// Iterator over the indices of the occupied slots in a SlotList
+pub struct Keys<'a, T, I = usize, S = VecStorage<T, I>>
+where
+  T: 'a,
+  I: SlotIndex,
+  S: Storage<T, I> + 'a,
+{
+  inner: Iter<'a, T, I, S>,
+}
+
+impl<'a, T: 'a, I: SlotIndex, S: Storage<T, I> + 'a> Keys<'a, T, I, S> {
+  pub(crate) fn new(inner: Iter<'a, T, I, S>) -> Keys<'a, T, I, S> {
+    Keys { inner }
+  }
+}
+
+impl<'a, T, I, S> Iterator for Keys<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  type Item = usize;
+
+  fn next(&mut self) -> Option<usize> {
+    self.inner.next().map(|(index, _)| index)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<'a, T, I, S> DoubleEndedIterator for Keys<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn next_back(&mut self) -> Option<usize> {
+    self.inner.next_back().map(|(index, _)| index)
+  }
+}
+
+impl<'a, T, I, S> ExactSizeIterator for Keys<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> FusedIterator for Keys<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> Clone for Keys<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn clone(&self) -> Self {
+    Keys {
+      inner: self.inner.clone(),
+    }
+  }
+}
+
+/// Iterator over references to the values stored in a SlotList
+pub struct Values<'a, T, I = usize, S = VecStorage<T, I>>
+where
+  T: 'a,
+  I: SlotIndex,
+  S: Storage<T, I> + 'a,
+{
+  inner: Iter<'a, T, I, S>,
+}
+
+impl<'a, T: 'a, I: SlotIndex, S: Storage<T, I> + 'a> Values<'a, T, I, S> {
+  pub(crate) fn new(inner: Iter<'a, T, I, S>) -> Values<'a, T, I, S> {
+    Values { inner }
+  }
+}
+
+impl<'a, T, I, S> Iterator for Values<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  type Item = &'a T;
+
+  fn next(&mut self) -> Option<&'a T> {
+    self.inner.next().map(|(_, value)| value)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<'a, T, I, S> DoubleEndedIterator for Values<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn next_back(&mut self) -> Option<&'a T> {
+    self.inner.next_back().map(|(_, value)| value)
+  }
+}
+
+impl<'a, T, I, S> ExactSizeIterator for Values<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> FusedIterator for Values<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> Clone for Values<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn clone(&self) -> Self {
+    Values {
+      inner: self.inner.clone(),
+    }
+  }
+}
+
+/// Iterator over mutable references to the values stored in a SlotList
+pub struct ValuesMut<'a, T, I = usize, S = VecStorage<T, I>>
+where
+  T: 'a,
+  I: SlotIndex,
+  S: Storage<T, I> + 'a,
+{
+  inner: IterMut<'a, T, I, S>,
+}
+
+impl<'a, T: 'a, I: SlotIndex, S: Storage<T, I> + 'a> ValuesMut<'a, T, I, S> {
+  pub(crate) fn new(inner: IterMut<'a, T, I, S>) -> ValuesMut<'a, T, I, S> {
+    ValuesMut { inner }
+  }
+}
+
+impl<'a, T, I, S> Iterator for ValuesMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  type Item = &'a mut T;
+
+  fn next(&mut self) -> Option<&'a mut T> {
+    self.inner.next().map(|(_, value)| value)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<'a, T, I, S> DoubleEndedIterator for ValuesMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn next_back(&mut self) -> Option<&'a mut T> {
+    self.inner.next_back().map(|(_, value)| value)
+  }
+}
+
+impl<'a, T, I, S> ExactSizeIterator for ValuesMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> FusedIterator for ValuesMut<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+/// Iterator that removes every value from a SlotList, yielding each occupied
+/// index along with the value that was stored there. Each slot is emptied the
+/// same way `remove` would empty it, so any values not consumed before the
+/// iterator is dropped are removed anyway, and stale keys stop resolving.
+pub struct Drain<'a, T, I = usize, S = VecStorage<T, I>>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  list: &'a mut SlotList<T, I, S>,
+  front: usize,
+  back: usize,
+  remaining: usize,
+}
+
+impl<'a, T, I: SlotIndex, S: Storage<T, I>> Drain<'a, T, I, S> {
+  pub(crate) fn new(
+    list: &'a mut SlotList<T, I, S>,
+    slot_count: usize,
+    remaining: usize,
+  ) -> Drain<'a, T, I, S> {
+    Drain {
+      list,
+      front: 0,
+      back: slot_count,
+      remaining,
+    }
+  }
+}
+
+impl<'a, T, I: SlotIndex, S: Storage<T, I>> Iterator for Drain<'a, T, I, S> {
+  type Item = (usize, T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while self.front < self.back {
+      let index = self.front;
+      self.front += 1;
+      if let Some(value) = self.list.drain_slot(index) {
+        self.remaining -= 1;
+        return Some((index, value));
+      }
+    }
+    None
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a, T, I, S> DoubleEndedIterator for Drain<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    while self.front < self.back {
+      self.back -= 1;
+      let index = self.back;
+      if let Some(value) = self.list.drain_slot(index) {
+        self.remaining -= 1;
+        return Some((index, value));
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I, S> ExactSizeIterator for Drain<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I, S> FusedIterator for Drain<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+}
+
+impl<'a, T, I: SlotIndex, S: Storage<T, I>> Drop for Drain<'a, T, I, S> {
+  fn drop(&mut self) {
+    self.for_each(drop);
+    // Shrinking is held off until every value has been drained, so that it
+    // runs once instead of after every value
+    if let Err(err) = self.list.apply_shrink_policy() {
+      panic!("{}", err);
+    }
+  }
+}
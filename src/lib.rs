@@ -1,5 +1,45 @@
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+mod array;
+mod bitmap;
+#[cfg(target_has_atomic = "ptr")]
+mod chunked;
+#[cfg(target_has_atomic = "64")]
+mod concurrent;
+mod entry;
+mod error;
+mod index;
+mod iter;
 mod list;
+mod packed;
+mod policy;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "std")]
+mod sharded;
+mod skipfield;
+mod snapshot;
+mod storage;
 
-pub use list::SlotList;
+pub use array::ArraySlotList;
+#[cfg(target_has_atomic = "ptr")]
+pub use chunked::ChunkedStorage;
+#[cfg(target_has_atomic = "64")]
+pub use concurrent::{ConcurrentSlotList, SlotRef};
+pub use entry::{Reservation, VacantEntry};
+pub use error::{InsertError, InvariantViolation, SlotListError, SnapshotError};
+pub use index::SlotIndex;
+pub use iter::{Drain, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+pub use list::{Key, SlotList};
+pub use packed::PackedStorage;
+pub use policy::{AllocationPolicy, ShrinkPolicy};
+#[cfg(feature = "std")]
+pub use sharded::{
+  LockedShards, ShardRef, ShardRefMut, ShardedIter, ShardedSlotList,
+};
+pub use skipfield::SkipfieldStorage;
+pub use snapshot::{SnapshotSink, ValueCodec};
+pub use storage::{ArrayStorage, Storage, VecStorage};
@@ -1,391 +1,3209 @@
 #[cfg(feature = "std")]
-use std::vec::Vec;
+use std::{collections::TryReserveError, vec::Vec};
 #[cfg(not(feature = "std"))]
 extern crate alloc;
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{collections::TryReserveError, vec::Vec};
+
+use crate::entry::{next_list_id, ListId, Reservation, VacantEntry};
+use crate::error::{InsertError, InvariantViolation, SlotListError};
+use crate::index::{Link, SlotIndex};
+use crate::iter::{Drain, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+use crate::policy::{AllocationPolicy, ShrinkPolicy};
+use crate::storage::{Storage, VecStorage};
+
+use core::marker::PhantomData;
 
 /// Rather than store `Option` elements, a SlotList uses a custom maybe type
-/// that associates a number with each empty slot. These numbers allow the
-/// collection to create a linked list of empty slots, reducing an insertion
-/// complexity that would otherwise be `O(n)`.
-#[derive(Copy, Clone, Debug)]
-pub enum Slot<T: Sized> {
-  Occupied(T),
-  Empty(Option<usize>),
+/// that associates a pair of numbers with each empty slot. These numbers allow
+/// the collection to create a doubly linked list of empty slots, reducing an
+/// insertion complexity that would otherwise be `O(n)`, and allowing any empty
+/// slot to be claimed directly in `O(1)`.
+/// Each slot also carries a generation counter, which is bumped every time an
+/// occupied slot is emptied. Pairing an index with its generation produces a
+/// `Key` that can tell a live entry apart from a later re-use of the same slot.
+/// A slot may also be reserved, which sets it aside for a value that hasn't
+/// been constructed yet. A reserved slot is neither part of the empty chain nor
+/// visible as a value.
+/// The links are stored as the list's index type `I`, so that lists with a
+/// narrower index type have smaller empty slots.
+#[derive(Copy, Clone)]
+pub enum Slot<T: Sized, I = usize> {
+  Occupied { generation: u32, value: T },
+  Empty { generation: u32, prev: Link<I>, next: Link<I> },
+  Reserved { generation: u32 },
 }
 
-impl<T> Slot<T> {
-  pub fn replace(&mut self, value: T) -> Slot<T> {
-    core::mem::replace(self, Slot::Occupied(value))
+impl<T, I: SlotIndex> Slot<T, I> {
+  /// Construct an empty slot that isn't linked to any other
+  pub const fn unlinked(generation: u32) -> Slot<T, I> {
+    Slot::Empty { generation, prev: Link::NONE, next: Link::NONE }
+  }
+
+  /// Store a value in the slot, keeping its current generation. The previous
+  /// contents of the slot are returned.
+  pub fn replace(&mut self, value: T) -> Slot<T, I> {
+    let generation = self.generation();
+    core::mem::replace(self, Slot::Occupied { generation, value })
+  }
+
+  /// Empty the slot, returning its previous contents. If the slot was
+  /// occupied, its generation is advanced so that existing keys go stale.
+  pub fn take(&mut self) -> Slot<T, I> {
+    let generation = match self {
+      Slot::Occupied { generation, .. } => generation.wrapping_add(1),
+      Slot::Empty { generation, .. } | Slot::Reserved { generation } => {
+        *generation
+      },
+    };
+    core::mem::replace(self, Slot::unlinked(generation))
+  }
+
+  /// Link this slot to the next one in the empty chain. Fails if this slot
+  /// is occupied or reserved, since only empty slots can be part of the chain.
+  pub fn set_next_empty(
+    &mut self,
+    index: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    match self {
+      Slot::Occupied { .. } => Err(SlotListError::Occupied),
+      Slot::Reserved { .. } => Err(SlotListError::Reserved),
+      Slot::Empty { next, .. } => {
+        *next = Link::new(index);
+        Ok(())
+      },
+    }
+  }
+
+  /// Link this slot to the previous one in the empty chain. Fails if this
+  /// slot is occupied or reserved, since only empty slots can be part of the
+  /// chain.
+  pub fn set_prev_empty(
+    &mut self,
+    index: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    match self {
+      Slot::Occupied { .. } => Err(SlotListError::Occupied),
+      Slot::Reserved { .. } => Err(SlotListError::Reserved),
+      Slot::Empty { prev, .. } => {
+        *prev = Link::new(index);
+        Ok(())
+      },
+    }
   }
 
-  pub fn take(&mut self) -> Slot<T> {
-    core::mem::take(self)
+  /// View the slot with its value, if any, borrowed
+  pub fn as_ref(&self) -> Slot<&T, I> {
+    match *self {
+      Slot::Occupied { generation, ref value } => {
+        Slot::Occupied { generation, value }
+      },
+      Slot::Empty { generation, prev, next } => {
+        Slot::Empty { generation, prev, next }
+      },
+      Slot::Reserved { generation } => Slot::Reserved { generation },
+    }
   }
 
-  pub fn set_next_empty(&mut self, index: usize) {
+  pub fn generation(&self) -> u32 {
     match self {
-      Slot::Occupied(_) => panic!("Can't modify empty chain for an occupied slot"),
-      Slot::Empty(next) => *next = Some(index),
+      Slot::Occupied { generation, .. } => *generation,
+      Slot::Empty { generation, .. } => *generation,
+      Slot::Reserved { generation } => *generation,
     }
   }
 
   pub fn as_option_of_ref(&self) -> Option<&T> {
     match self {
-      Slot::Occupied(ref value) => Some(value),
-      Slot::Empty(_) => None,
+      Slot::Occupied { ref value, .. } => Some(value),
+      Slot::Empty { .. } | Slot::Reserved { .. } => None,
     }
   }
 
   pub fn as_mut(&mut self) -> Option<&mut T> {
     match *self {
-      Slot::Occupied(ref mut value) => Some(value),
-      Slot::Empty(_) => None,
+      Slot::Occupied { ref mut value, .. } => Some(value),
+      Slot::Empty { .. } | Slot::Reserved { .. } => None,
     }
   }
 
   pub fn is_occupied(&self) -> bool {
     match self {
-      Slot::Occupied(_) => true,
-      Slot::Empty(_) => false,
+      Slot::Occupied { .. } => true,
+      Slot::Empty { .. } | Slot::Reserved { .. } => false,
+    }
+  }
+
+  /// Whether the slot is available to store a new value. Reserved slots are
+  /// neither occupied nor vacant.
+  pub fn is_vacant(&self) -> bool {
+    match self {
+      Slot::Empty { .. } => true,
+      Slot::Occupied { .. } | Slot::Reserved { .. } => false,
     }
   }
 
   pub fn occupied(self) -> Option<T> {
     match self {
-      Slot::Occupied(value) => Some(value),
-      Slot::Empty(_) => None,
+      Slot::Occupied { value, .. } => Some(value),
+      Slot::Empty { .. } | Slot::Reserved { .. } => None,
+    }
+  }
+}
+
+impl<T: Clone, I: SlotIndex> Slot<&T, I> {
+  /// Produce an owned slot by cloning the borrowed value, if any
+  pub fn cloned(self) -> Slot<T, I> {
+    match self {
+      Slot::Occupied { generation, value } => {
+        Slot::Occupied { generation, value: value.clone() }
+      },
+      Slot::Empty { generation, prev, next } => {
+        Slot::Empty { generation, prev, next }
+      },
+      Slot::Reserved { generation } => Slot::Reserved { generation },
+    }
+  }
+}
+
+impl<T: core::fmt::Debug, I: SlotIndex> core::fmt::Debug for Slot<T, I> {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    match self {
+      Slot::Occupied { generation, value } => formatter.debug_struct("Occupied")
+        .field("generation", generation)
+        .field("value", value)
+        .finish(),
+      Slot::Empty { generation, prev, next } => formatter.debug_struct("Empty")
+        .field("generation", generation)
+        .field("prev", prev)
+        .field("next", next)
+        .finish(),
+      Slot::Reserved { generation } => formatter.debug_struct("Reserved")
+        .field("generation", generation)
+        .finish(),
     }
   }
 }
 
-impl<T> Default for Slot<T> {
-  fn default() -> Slot<T> {
-    Slot::Empty(None)
+impl<T, I: SlotIndex> Default for Slot<T, I> {
+  fn default() -> Slot<T, I> {
+    Slot::unlinked(0)
+  }
+}
+
+/// A Key pairs a slot index with the generation of the value stored there.
+/// Unlike a bare index, a Key stops resolving once its value is removed, even
+/// if the slot is later re-used by a new insertion.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key {
+  index: usize,
+  generation: u32,
+}
+
+impl Key {
+  pub(crate) fn new(index: usize, generation: u32) -> Key {
+    Key { index, generation }
+  }
+
+  pub fn index(&self) -> usize {
+    self.index
+  }
+
+  pub fn generation(&self) -> u32 {
+    self.generation
   }
 }
 
+/// The parts of a list that an insertion changes before it knows whether it
+/// will succeed, recorded so that a failed insertion can put them back
+#[derive(Copy, Clone)]
+struct Checkpoint {
+  slot_count: usize,
+  next_cyclic_slot: usize,
+}
+
 /// SlotList is a vector-like data structure where every entry, or "slot," is an
 /// `Option` that may contain a value. The added value of a SlotList over a
 /// `Vec<Option<T>>` is that inserting a new value tries to re-use any empty
 /// slots before allocating new space. This creates a data structure with two
 /// properties: the index of a given element will always remain static, and
 /// elements can be removed without wasting space.
-pub struct SlotList<T: Sized> {
-  first_empty_slot: Option<usize>,
-  last_empty_slot: Option<usize>,
-  slots: Vec<Slot<T>>,
+/// By default, slots are stored in a `Vec` that grows as needed. Other kinds of
+/// `Storage` can be used instead, such as the fixed-size array behind an
+/// `ArraySlotList`.
+/// Empty slots are linked together using the index type `I`. Lists that will
+/// never hold more than a few billion slots can use `u32`, or `u16` for fewer
+/// than 65535 slots, to make each slot smaller.
+pub struct SlotList<T: Sized, I = usize, S = VecStorage<T, I>> {
+  policy: AllocationPolicy,
+  shrink_policy: ShrinkPolicy,
+  limit: usize,
+  next_cyclic_slot: usize,
+  first_empty_slot: Link<I>,
+  last_empty_slot: Link<I>,
+  len: usize,
+  vacant: usize,
+  reserved: usize,
+  fresh_generation: u32,
+  /// Assigned the first time a slot is reserved, so that Reservations made by
+  /// other lists can be told apart
+  id: Option<ListId>,
+  storage: S,
+  marker: PhantomData<(T, I)>,
 }
 
 impl<T: Sized> SlotList<T> {
   /// Construct a new SlotList with no elements. A new, empty list will not
   /// allocate any memory, and can be a `const` value.
   pub const fn new() -> SlotList<T> {
-    SlotList {
-      first_empty_slot: None,
-      last_empty_slot: None,
-      slots: Vec::new(),
-    }
+    SlotList::with_policy(AllocationPolicy::Fifo)
+  }
+
+  /// Construct a new, empty SlotList that re-uses empty slots according to the
+  /// provided policy.
+  pub const fn with_policy(policy: AllocationPolicy) -> SlotList<T> {
+    SlotList::with_policy_and_limit(policy, usize::MAX)
+  }
+
+  /// Construct a new, empty SlotList that will never grow past the provided
+  /// number of slots. Once every slot under the limit is occupied, `insert`
+  /// will panic and `try_insert` will fail, the way a process runs out of file
+  /// descriptors when it reaches `RLIMIT_NOFILE`.
+  pub const fn with_limit(limit: usize) -> SlotList<T> {
+    SlotList::with_policy_and_limit(AllocationPolicy::Fifo, limit)
+  }
+
+  /// Construct a new, empty SlotList with both an allocation policy and a
+  /// limit on the number of slots.
+  pub const fn with_policy_and_limit(
+    policy: AllocationPolicy,
+    limit: usize,
+  ) -> SlotList<T> {
+    SlotList::with_index_policy_and_limit(policy, limit)
   }
 
   /// Preallocate a SlotList with enough memory to store the requested number of
   /// elements.
   pub fn with_capacity(capacity: usize) -> SlotList<T> {
+    SlotList::with_capacity_and_policy(capacity, AllocationPolicy::Fifo)
+  }
+
+  /// Preallocate a SlotList with enough memory to store the requested number of
+  /// elements, re-using empty slots according to the provided policy.
+  pub fn with_capacity_and_policy(
+    capacity: usize,
+    policy: AllocationPolicy,
+  ) -> SlotList<T> {
+    let storage = VecStorage::with_capacity(capacity);
+    SlotList::from_storage(storage, policy, usize::MAX)
+  }
+}
+
+impl<T: Sized, I: SlotIndex> SlotList<T, I> {
+  /// Construct a new, empty SlotList that links its empty slots with the index
+  /// type `I`, rather than `usize`.
+  pub const fn with_index() -> SlotList<T, I> {
+    SlotList::with_index_policy_and_limit(AllocationPolicy::Fifo, usize::MAX)
+  }
+
+  /// Construct a new, empty SlotList that links its empty slots with the index
+  /// type `I`, with both an allocation policy and a limit on the number of
+  /// slots.
+  pub const fn with_index_policy_and_limit(
+    policy: AllocationPolicy,
+    limit: usize,
+  ) -> SlotList<T, I> {
+    SlotList::from_storage(VecStorage::new(), policy, limit)
+  }
+
+  /// Preallocate a SlotList that links its empty slots with the index type
+  /// `I`, with enough memory to store the requested number of elements.
+  pub fn with_capacity_and_index(capacity: usize) -> SlotList<T, I> {
+    let storage = VecStorage::with_capacity(capacity);
+    SlotList::from_storage(storage, AllocationPolicy::Fifo, usize::MAX)
+  }
+
+  /// Try to reserve memory for at least `additional` more slots, reporting
+  /// allocation failure as an error instead of aborting. If this fails, the
+  /// contents of the list are left unchanged.
+  pub fn try_reserve(
+    &mut self,
+    additional: usize,
+  ) -> Result<(), TryReserveError> {
+    self.storage.slots.try_reserve(additional)?;
+    let slots = self.storage.slots.len().saturating_add(additional);
+    self.storage.occupancy.try_reserve(slots)
+  }
+}
+
+impl<T: Sized, I: SlotIndex, S: Storage<T, I>> SlotList<T, I, S> {
+  /// Construct a new, empty SlotList on top of the provided storage, which
+  /// must not hold any slots yet. This is how a kind of storage other than the
+  /// default is chosen.
+  pub const fn with_storage(storage: S) -> SlotList<T, I, S> {
+    let policy = AllocationPolicy::Fifo;
+    SlotList::with_storage_policy_and_limit(storage, policy, usize::MAX)
+  }
+
+  /// Construct a new, empty SlotList on top of the provided storage, with both
+  /// an allocation policy and a limit on the number of slots. The limit is
+  /// lowered to what the storage can hold, if necessary.
+  pub const fn with_storage_policy_and_limit(
+    storage: S,
+    policy: AllocationPolicy,
+    limit: usize,
+  ) -> SlotList<T, I, S> {
+    let limit = if limit < S::MAX_SLOTS { limit } else { S::MAX_SLOTS };
+    SlotList::from_storage(storage, policy, limit)
+  }
+
+  /// Construct an empty list on top of empty storage
+  pub(crate) const fn from_storage(
+    storage: S,
+    policy: AllocationPolicy,
+    limit: usize,
+  ) -> SlotList<T, I, S> {
     SlotList {
-      first_empty_slot: None,
-      last_empty_slot: None,
-      slots: Vec::with_capacity(capacity),
+      policy,
+      shrink_policy: ShrinkPolicy::Never,
+      limit,
+      next_cyclic_slot: 0,
+      first_empty_slot: Link::NONE,
+      last_empty_slot: Link::NONE,
+      len: 0,
+      vacant: 0,
+      reserved: 0,
+      fresh_generation: 0,
+      id: None,
+      storage,
+      marker: PhantomData,
     }
   }
 
   pub fn capacity(&self) -> usize {
-    self.slots.capacity()
-  }
-
-  /// Locate the first empty slot that can be used to store a value, returning
-  /// its numeric index. If none is found, the list will push an empty slot onto
-  /// the end and return the index of that slot.
-  fn find_empty_slot(&mut self) -> usize {
-    let mut index = self.slots.len();
-
-    if let Some(first_index) = self.first_empty_slot {
-      // An empty slot exists, so re-use it
-      index = first_index;
-      let empty = self.slots.get(first_index).unwrap();
-      let next_first = match empty {
-        Slot::Occupied(_) => panic!("Empty slot chain was broken"),
-        Slot::Empty(next) => *next,
-      };
-      self.first_empty_slot = next_first;
-    }
+    self.storage.capacity()
+  }
 
-    if self.first_empty_slot.is_none() {
-      // This implies that there are no more empty slots.
-      // After the first element has been placed on the list, it maintains at
-      // least one empty entry at all times that can be used for the next insert
-      // operation.
-      // If there was no first empty slot (should only be true for a newly
-      // initialized list), this also guarantees that the initial index value
-      // set at dthe top of the function will point to an empty entry.
-      let mut last_entry = self.slots.len();
-      self.slots.push(Slot::Empty(None));
-      if last_entry == 0 {
-        self.slots.push(Slot::Empty(None));
-        last_entry += 1;
-      }
-      self.first_empty_slot = Some(last_entry);
-      self.last_empty_slot = Some(last_entry);
-    }
+  /// The number of values stored in the list
+  pub fn len(&self) -> usize {
+    self.len
+  }
 
-    index
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
   }
 
-  /// Insert a new value into the list. This will attempt to use an empty slot,
-  /// before allocating a new one at the end
-  pub fn insert(&mut self, item: T) -> usize {
-    let index = self.find_empty_slot();
-    self.slots[index] = Slot::Occupied(item);
-    index
+  /// The total number of slots in the list, whether or not they are occupied.
+  /// This includes the spare empty slot kept at the end of the list.
+  pub fn slot_count(&self) -> usize {
+    self.storage.slot_count()
   }
 
-  /// Retrieve a reference to the value at the specified index
-  pub fn get(&self, index: usize) -> Option<&T> {
-    let slot = self.slots.get(index)?;
-    match slot {
-      Slot::Occupied(item) => Some(item),
-      Slot::Empty(_) => None,
+  /// The number of empty slots available for re-use
+  pub fn vacant_count(&self) -> usize {
+    self.vacant
+  }
+
+  /// The number of slots held by outstanding Reservations. These count as
+  /// neither occupied nor vacant.
+  pub fn reserved_count(&self) -> usize {
+    self.reserved
+  }
+
+  pub fn policy(&self) -> AllocationPolicy {
+    self.policy
+  }
+
+  pub fn shrink_policy(&self) -> ShrinkPolicy {
+    self.shrink_policy
+  }
+
+  /// Choose whether the list releases memory automatically as values are
+  /// removed. The policy is first applied on the next removal.
+  pub fn set_shrink_policy(&mut self, shrink_policy: ShrinkPolicy) {
+    self.shrink_policy = shrink_policy;
+  }
+
+  /// The maximum number of slots the list may grow to, if it has a limit
+  pub fn limit(&self) -> Option<usize> {
+    if self.limit == usize::MAX {
+      None
+    } else {
+      Some(self.limit)
     }
   }
 
-  /// Retrieve a mutable reference to the value at the specified index
-  pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
-    let slot = self.slots.get_mut(index)?;
-    slot.as_mut()
+  /// Locate an empty slot that can be used to store a value, according to the
+  /// list's allocation policy. If none is found, the list will push an empty
+  /// slot onto the end and return the index of that slot, unless the list has
+  /// already reached its limit.
+  fn find_empty_slot(&mut self) -> Result<Option<usize>, SlotListError> {
+    let found = match self.policy {
+      AllocationPolicy::Fifo | AllocationPolicy::Lifo => {
+        self.pop_empty_chain()?
+      },
+      AllocationPolicy::LowestIndex => {
+        self.storage.first_vacant(self.slot_count())
+      },
+      AllocationPolicy::Cyclic => {
+        let len = self.slot_count();
+        self.storage
+          .next_vacant(self.next_cyclic_slot, len)
+          .or_else(|| self.storage.first_vacant(len))
+      },
+    };
+    if found.is_some() || self.slot_count() >= self.limit {
+      return Ok(found);
+    }
+    if self.slot_count() >= I::MAX_SLOTS {
+      return Err(SlotListError::IndexOverflow);
+    }
+    // The new slot will be filled immediately, so it never needs to join the
+    // empty chain
+    self.storage.push(self.fresh_slot());
+    self.vacant += 1;
+    Ok(Some(self.slot_count() - 1))
   }
 
-  /// Remove the value at the specified index, returning the value that was
-  /// stored there
-  pub fn remove(&mut self, index: usize) -> Option<T> {
-    let slot = self.slots.get_mut(index)?;
-    let prev = slot.take();
+  /// Determine which slot `find_empty_slot` would use, without modifying the
+  /// list. An index equal to the slot count means the list would grow.
+  fn peek_empty_slot(&self) -> Result<usize, SlotListError> {
+    let len = self.slot_count();
+    let found = match self.policy {
+      AllocationPolicy::Fifo | AllocationPolicy::Lifo => {
+        self.first_empty_slot.get()
+      },
+      AllocationPolicy::LowestIndex => self.storage.first_vacant(len),
+      AllocationPolicy::Cyclic => self.storage
+        .next_vacant(self.next_cyclic_slot, len)
+        .or_else(|| self.storage.first_vacant(len)),
+    };
+    match found {
+      Some(index) => Ok(index),
+      None if len >= self.limit => Err(SlotListError::CapacityExceeded),
+      None if len >= I::MAX_SLOTS => Err(SlotListError::IndexOverflow),
+      None => Ok(len),
+    }
+  }
 
-    if prev.is_occupied() {
-      // `index` now represents the latest in the chain of empty slots
-      if let Some(last_slot_index) = self.last_empty_slot {
-        self.slots
-          .get_mut(last_slot_index)
-          .unwrap()
-          .set_next_empty(index);
-      }
-      self.last_empty_slot = Some(index);
+  /// Remove the first slot from the empty chain, returning its index
+  fn pop_empty_chain(&mut self) -> Result<Option<usize>, SlotListError> {
+    let first_index = match self.first_empty_slot.get() {
+      Some(index) => index,
+      None => return Ok(None),
+    };
+    let next = match self.storage.get(first_index) {
+      Some(Slot::Empty { next, .. }) => next.get(),
+      _ => return Err(SlotListError::ChainCorrupted),
+    };
+    self.unlink_empty_chain(first_index, None, next)?;
+    Ok(Some(first_index))
+  }
+
+  /// Thread a newly emptied slot onto the empty chain. FIFO lists add it to the
+  /// end of the chain, and LIFO lists add it to the front.
+  fn push_empty_chain(&mut self, index: usize) -> Result<(), SlotListError> {
+    let first = self.first_empty_slot.get();
+    let last = self.last_empty_slot.get();
+    match (self.policy, first, last) {
+      (AllocationPolicy::Lifo, Some(first_index), _) => {
+        self.set_chain_prev(first_index, Some(index))?;
+        self.storage.set_next_empty(index, Some(first_index))?;
+        self.first_empty_slot = Link::new(Some(index));
+      },
+      (_, Some(_), Some(last_index)) => {
+        self.set_chain_next(last_index, Some(index))?;
+        self.storage.set_prev_empty(index, Some(last_index))?;
+        self.last_empty_slot = Link::new(Some(index));
+      },
+      _ => {
+        self.first_empty_slot = Link::new(Some(index));
+        self.last_empty_slot = Link::new(Some(index));
+      },
+    }
+    Ok(())
+  }
+
+  /// Remove an arbitrary slot from the empty chain, given the links that were
+  /// stored in it. Because the chain is doubly linked, only the neighboring
+  /// slots need to be fixed up.
+  fn unlink_empty_chain(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    // Make sure both neighbors agree that they are linked to this slot before
+    // changing anything
+    if !self.neighbors_agree(index, prev, next) {
+      return Err(SlotListError::ChainCorrupted);
     }
 
-    prev.occupied()
+    match prev {
+      Some(prev_index) => self.storage.set_next_empty(prev_index, next)?,
+      None => self.first_empty_slot = Link::new(next),
+    }
+    match next {
+      Some(next_index) => self.storage.set_prev_empty(next_index, prev)?,
+      None => self.last_empty_slot = Link::new(prev),
+    }
+    Ok(())
   }
 
-  /// Set a specific slot to the provided value, returning the value that was
-  /// previously stored there.
-  /// This may require fixing up the empty slot chain, and in a worst-case
-  /// scenario the complexity of this method becomes O(n).
-  pub fn replace(&mut self, index: usize, item: T) -> Option<T> {
-    if index >= self.slots.len() {
-      panic!("Index out of bounds");
-    }
-    let slot = self.slots.get_mut(index).unwrap();
-    let prev = slot.replace(item);
-
-    if let Slot::Empty(next) = prev {
-      // `index` represented an element in the empty chain
-      // To fix up the chain, we need to replace pointers to it
-      let mut current = self.first_empty_slot;
-      while let Some(current_index) = current {
-        let current_slot = self.slots.get_mut(current_index).unwrap();
-        current = match current_slot {
-          Slot::Occupied(_) => panic!("Empty slot chain was broken"),
-          Slot::Empty(next_slot) => *next_slot,
-        };
-        if current == Some(index) {
-          *current_slot = Slot::Empty(next);
-          // If the removed empty slot was the last in the chain, update the
-          // pointer to the new last item
-          if self.last_empty_slot == Some(index) {
-            self.last_empty_slot = Some(current_index);
-          }
-          current = None;
-        }
-      }
+  /// Put a slot back into the empty chain between the neighbors it was
+  /// unlinked from, undoing `unlink_empty_chain`
+  fn relink_empty_chain(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+  ) {
+    // Both neighbors were checked when the slot was unlinked, and haven't been
+    // touched since, so linking them back to it cannot fail
+    match prev {
+      Some(prev_index) => {
+        self.storage.set_next_empty(prev_index, Some(index)).unwrap()
+      },
+      None => self.first_empty_slot = Link::new(Some(index)),
+    }
+    match next {
+      Some(next_index) => {
+        self.storage.set_prev_empty(next_index, Some(index)).unwrap()
+      },
+      None => self.last_empty_slot = Link::new(Some(index)),
     }
-    
-    prev.occupied()
   }
 
-  /// Construct an iterator that will visit all of the occupied slots in
-  /// increasing index order
-  pub fn iter(&self) -> impl Iterator<Item = &T> {
-    self.slots.iter().filter_map(|i| i.as_option_of_ref())
+  /// Determine whether the slots on either side of a slot in the empty chain
+  /// link back to it
+  fn neighbors_agree(
+    &self,
+    index: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
+  ) -> bool {
+    let prev_is_linked = match prev {
+      Some(prev_index) => match self.storage.get(prev_index) {
+        Some(Slot::Empty { next, .. }) => next.get() == Some(index),
+        _ => false,
+      },
+      None => self.first_empty_slot.get() == Some(index),
+    };
+    let next_is_linked = match next {
+      Some(next_index) => match self.storage.get(next_index) {
+        Some(Slot::Empty { prev, .. }) => prev.get() == Some(index),
+        _ => false,
+      },
+      None => self.last_empty_slot.get() == Some(index),
+    };
+    prev_is_linked && next_is_linked
   }
 
-  /// Helper for testing chain consistency, only available in test mode
-  #[cfg(test)]
-  pub fn get_first_empty_slot(&self) -> Option<usize> {
-    self.first_empty_slot
+  /// Link a slot that the chain claims to be part of it to the next one
+  fn set_chain_next(
+    &mut self,
+    index: usize,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    match self.storage.set_next_empty(index, next) {
+      Err(SlotListError::OutOfBounds) => Err(SlotListError::ChainCorrupted),
+      result => result,
+    }
   }
 
-  /// Helper for testing chain consistency, only available in test mode
-  #[cfg(test)]
-  pub fn get_last_empty_slot(&self) -> Option<usize> {
-    self.last_empty_slot
+  /// Link a slot that the chain claims to be part of it to the previous one
+  fn set_chain_prev(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    match self.storage.set_prev_empty(index, prev) {
+      Err(SlotListError::OutOfBounds) => Err(SlotListError::ChainCorrupted),
+      result => result,
+    }
   }
 
-  /// Helper for testing chain consistency, only available in test mode
-  #[cfg(test)]
-  pub fn get_raw_slot(&self, index: usize) -> Option<&Slot<T>> {
-    self.slots.get(index)
+  /// Construct an empty slot to be added to the end of the list. Its
+  /// generation is newer than that of any slot that was previously trimmed off
+  /// of the end, so that keys to trimmed slots can't resolve to new values.
+  fn fresh_slot(&self) -> Slot<T, I> {
+    Slot::unlinked(self.fresh_generation)
   }
-}
 
-impl<T: Clone> Clone for SlotList<T> {
-  fn clone(&self) -> Self {
-    Self {
-      first_empty_slot: self.first_empty_slot,
-      last_empty_slot: self.last_empty_slot,
-      slots: self.slots.clone(),
+  /// Add a new empty slot to the end of the list
+  fn push_empty_slot(&mut self) -> Result<(), SlotListError> {
+    let index = self.slot_count();
+    self.storage.push(self.fresh_slot());
+    self.vacant += 1;
+    if self.policy.uses_empty_chain() {
+      self.push_empty_chain(index)?;
     }
+    Ok(())
   }
-}
 
-impl<T: core::fmt::Debug> core::fmt::Debug for SlotList<T> {
-  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
-    formatter.debug_list()
-      .entries(self.slots.iter())
-      .finish()
+  /// After the first element has been placed on the list, it maintains at
+  /// least one empty slot at all times that can be used for the next insert
+  /// operation. This is called after a slot is filled, to add a new empty slot
+  /// if the last one was just used up.
+  fn ensure_spare_slot(&mut self) -> Result<(), SlotListError> {
+    let slot_count = self.slot_count();
+    if self.vacant == 0
+      && slot_count < self.limit
+      && slot_count < I::MAX_SLOTS
+    {
+      self.push_empty_slot()?;
+    }
+    Ok(())
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::{Slot, SlotList};
+  /// Reserve enough memory to grow the list by `additional` slots, so that
+  /// nothing can fail once an operation has started changing the list
+  fn reserve_for_growth(
+    &mut self,
+    additional: usize,
+  ) -> Result<(), SlotListError> {
+    self.storage.reserve(additional)
+  }
 
-  #[test]
-  fn initialization() {
-    let mut list: SlotList<u32> = SlotList::new();
-    assert_eq!(list.insert(5), 0);
+  /// Reserve enough memory for the spare slot that `ensure_spare_slot` may
+  /// add, so that it can't fail once the list has been changed
+  fn reserve_for_spare_slot(&mut self) -> Result<(), SlotListError> {
+    if self.slot_count() > 0 && self.slot_count() < self.limit {
+      self.reserve_for_growth(1)?;
+    }
+    Ok(())
   }
 
-  #[test]
-  fn inserting_items() {
-    let mut list: SlotList<u32> = SlotList::with_capacity(3);
-    assert_eq!(list.get(1), None);
-    assert_eq!(list.insert(20), 0);
-    assert_eq!(list.insert(30), 1);
-    assert_eq!(list.insert(40), 2);
-    assert_eq!(list.get(0), Some(&20));
-    assert_eq!(list.get(1), Some(&30));
-    assert_eq!(list.get(2), Some(&40));
-    assert_eq!(list.get(3), None);
+  /// Insert a new value into the list. This will attempt to use an empty slot,
+  /// before allocating a new one at the end.
+  /// Panics if the list has a limit, and every slot under it is occupied.
+  pub fn insert(&mut self, item: T) -> usize {
+    match self.try_insert(item) {
+      Ok(index) => index,
+      Err(err) => panic!("{}", err),
+    }
   }
 
-  #[test]
-  fn grow_to_fit() {
-    let mut list: SlotList<u32> = SlotList::new();
-    assert_eq!(list.get(1), None);
-    assert_eq!(list.insert(20), 0);
-    assert_eq!(list.insert(30), 1);
-    assert_eq!(list.insert(40), 2);
-    assert_eq!(list.get(0), Some(&20));
-    assert_eq!(list.get(1), Some(&30));
-    assert_eq!(list.get(2), Some(&40));
-    assert_eq!(list.get(3), None);
+  /// Insert a new value into the list, like `insert`. If the list has a limit
+  /// and every slot under it is occupied, if the list needs to grow past what
+  /// its index type can address, or if the list needs to grow and memory can't
+  /// be allocated, the value is handed back inside of the error instead. On
+  /// failure, the list is left unchanged.
+  pub fn try_insert(&mut self, item: T) -> Result<usize, InsertError<T>> {
+    // An insertion grows the list by at most two slots: one for the value, and
+    // a spare empty slot.
+    if self.slot_count() < self.limit {
+      if let Err(err) = self.reserve_for_growth(2) {
+        return Err(InsertError::new(err, item));
+      }
+    }
+    let checkpoint = self.checkpoint();
+    let index = match self.find_empty_slot() {
+      Ok(Some(index)) => index,
+      Ok(None) => {
+        let err = SlotListError::CapacityExceeded;
+        return Err(InsertError::new(err, item));
+      },
+      Err(err) => return Err(InsertError::new(err, item)),
+    };
+    let prev = self.fill_slot(index, item);
+    self.next_cyclic_slot = index + 1;
+    match self.ensure_spare_slot() {
+      Ok(()) => Ok(index),
+      Err(err) => {
+        let item = self.restore_slot(index, prev, checkpoint);
+        Err(InsertError::new(err, item))
+      },
+    }
   }
 
-  #[test]
-  fn removing_items() {
-    let mut list: SlotList<u32> = SlotList::new();
-    list.insert(55);
-    list.insert(40);
-    list.insert(60);
-    assert_eq!(list.remove(1), Some(40));
-    assert_eq!(list.get(1), None);
+  /// Store a value in a slot, keeping the counters in sync. If the slot was
+  /// empty, it must already have been removed from the empty chain. The
+  /// previous contents of the slot are returned.
+  fn fill_slot(&mut self, index: usize, item: T) -> Slot<T, I> {
+    let prev = self.storage.fill(index, item);
+    if !prev.is_occupied() {
+      self.len += 1;
+      self.vacant -= 1;
+    }
+    prev
   }
 
-  #[test]
-  fn replacing_emptied_items() {
-    let mut list: SlotList<u32> = SlotList::new();
-    list.insert(11);
-    list.insert(22);
-    list.insert(33);
-    list.remove(0);
-    list.remove(1);
-    // First it will fill the empty slot at the end of the list
-    assert_eq!(list.insert(44), 3);
-    // Another empty slot has been added to index 4, but that is at the end of
-    // the empty chain. 
-    // Next it will fill the previously freed slots at 0 and 1
-    assert_eq!(list.insert(55), 0);
-    assert_eq!(list.insert(66), 1);
-    // Once those have been filled, the chain returns to point to slot 4
-    assert_eq!(list.insert(77), 4);
+  /// Record the parts of the list that an insertion may change before it
+  /// knows whether it will succeed
+  fn checkpoint(&self) -> Checkpoint {
+    Checkpoint {
+      slot_count: self.slot_count(),
+      next_cyclic_slot: self.next_cyclic_slot,
+    }
   }
 
-  #[test]
-  fn replacing_empty_slot() {
-    let mut list: SlotList<u32> = SlotList::new();
-    list.insert(0);
-    assert_eq!(list.get_first_empty_slot(), Some(1));
-    assert_eq!(list.get_last_empty_slot(), Some(1));
-    list.remove(0);
-    assert_eq!(list.get_first_empty_slot(), Some(1));
-    assert_eq!(list.get_last_empty_slot(), Some(0));
-    // Replacing the last element in the "empty chain" should fix up the chain
-    // and its pointers.
-    assert_eq!(list.replace(0, 5), None);
-    assert_eq!(list.get_first_empty_slot(), Some(1));
-    assert_eq!(list.get_last_empty_slot(), Some(1));
-    if let Slot::Empty(next) = list.get_raw_slot(1).unwrap() {
-      assert!(next.is_none());
-    } else {
-      panic!("First slot was not empty");
+  /// Undo `fill_slot` when an operation fails partway through, returning the
+  /// value that was stored. A slot that was taken from the empty chain is put
+  /// back where it was, and the rest of the list is rewound to the checkpoint.
+  fn restore_slot(
+    &mut self,
+    index: usize,
+    prev: Slot<T, I>,
+    checkpoint: Checkpoint,
+  ) -> T {
+    if !prev.is_occupied() {
+      self.len -= 1;
+      self.vacant += 1;
     }
+    self.put_back(index, prev, checkpoint).occupied().unwrap()
   }
 
-  #[test]
-  fn replacing_existing_entries() {
-    let mut list: SlotList<u32> = SlotList::new();
-    list.insert(1);
-    list.insert(3);
-    list.insert(5);
-    list.remove(1);
-    assert_eq!(list.replace(0, 10), Some(1));
-    assert_eq!(list.replace(1, 12), None);
+  /// Store the previous contents of a slot that a failed operation took, and
+  /// rewind the list to the checkpoint taken before the operation started,
+  /// returning what the operation had stored in the slot
+  fn put_back(
+    &mut self,
+    index: usize,
+    prev: Slot<T, I>,
+    checkpoint: Checkpoint,
+  ) -> Slot<T, I> {
+    let links = match prev {
+      Slot::Empty { prev, next, .. } => Some((prev.get(), next.get())),
+      _ => None,
+    };
+    let taken = self.storage.replace(index, prev);
+    // The slot kept its own links while it was out of the chain. Slots added
+    // to the end of the list were never part of it.
+    if let Some((prev, next)) = links {
+      if index < checkpoint.slot_count && self.policy.uses_empty_chain() {
+        self.relink_empty_chain(index, prev, next);
+      }
+    }
+    self.rewind(checkpoint);
+    taken
   }
 
-  #[test]
-  fn iterator() {
-    let mut list: SlotList<u32> = SlotList::new();
-    list.insert(1);
-    list.insert(2);
-    list.insert(1);
-    list.insert(3);
-    list.insert(1);
+  /// Remove any slots added to the end of the list since the checkpoint was
+  /// taken, and restore the cyclic cursor
+  fn rewind(&mut self, checkpoint: Checkpoint) {
+    while self.slot_count() > checkpoint.slot_count {
+      let index = self.slot_count() - 1;
+      if self.policy.uses_empty_chain() {
+        if let Some(Slot::Empty { prev, next, .. }) = self.storage.get(index) {
+          let (prev, next) = (prev.get(), next.get());
+          // A slot whose neighbors don't link back to it was never added to
+          // the chain
+          if self.neighbors_agree(index, prev, next) {
+            self.unlink_empty_chain(index, prev, next).unwrap();
+          }
+        }
+      }
+      self.storage.pop();
+      self.vacant -= 1;
+    }
+    self.next_cyclic_slot = checkpoint.next_cyclic_slot;
+  }
 
-    list.remove(1);
-    list.remove(3);
-    let mut count = 0;
-    for x in list.iter() {
-      count += 1;
-      assert_eq!(*x, 1);
+  /// Insert a new value into the list, returning a generational Key for it.
+  /// The Key can be used with the `*_by_key` methods, which will refuse to
+  /// act on the slot once the value has been removed.
+  pub fn insert_keyed(&mut self, item: T) -> Key {
+    match self.try_insert_keyed(item) {
+      Ok(key) => key,
+      Err(err) => panic!("{}", err),
     }
-    assert_eq!(count, 3);
   }
 
-  #[test]
-  fn maintain_size() {
-    let mut list: SlotList<u32> = SlotList::with_capacity(4);
-    for _ in 0..100 {
-      let index = list.insert(10);
-      list.remove(index);
+  /// Insert a new value into the list, returning a generational Key for it.
+  /// Fails under the same conditions as `try_insert`.
+  pub fn try_insert_keyed(&mut self, item: T) -> Result<Key, InsertError<T>> {
+    let index = self.try_insert(item)?;
+    Ok(Key {
+      index,
+      generation: self.storage.get(index).unwrap().generation(),
+    })
+  }
+
+  /// Get a handle to the empty slot that the next insertion will use. This
+  /// allows a value to be built with knowledge of its own index, before it is
+  /// inserted.
+  /// Panics if the list has a limit, and every slot under it is occupied, or if
+  /// the empty slot chain is found to be corrupted.
+  pub fn vacant_entry(&mut self) -> VacantEntry<'_, T, I, S> {
+    match self.try_vacant_entry() {
+      Ok(entry) => entry,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Get a handle to the empty slot that the next insertion will use. Fails
+  /// under the same conditions as `try_insert`, or if the empty slot chain is
+  /// corrupted. Everything the insertion needs is checked and allocated here,
+  /// so inserting into the entry can't fail.
+  pub fn try_vacant_entry(
+    &mut self,
+  ) -> Result<VacantEntry<'_, T, I, S>, SlotListError> {
+    if self.slot_count() < self.limit {
+      self.reserve_for_growth(2)?;
+    }
+    let index = self.peek_empty_slot()?;
+    if self.policy.uses_empty_chain() && index < self.slot_count() {
+      let linked = match self.storage.get(index) {
+        Some(Slot::Empty { prev, next, .. }) => {
+          self.neighbors_agree(index, prev.get(), next.get())
+        },
+        _ => false,
+      };
+      if !linked {
+        return Err(SlotListError::ChainCorrupted);
+      }
+    }
+    Ok(VacantEntry::new(self, index))
+  }
+
+  /// Store a value in the empty slot that a VacantEntry was created for.
+  /// `try_vacant_entry` already reserved memory and checked the slot's links,
+  /// so nothing here can fail.
+  pub(crate) fn fill_vacant_entry(&mut self, index: usize, item: T) {
+    if index == self.slot_count() {
+      // The new slot will be filled immediately, so it never needs to join the
+      // empty chain
+      self.storage.push(self.fresh_slot());
+      self.vacant += 1;
+    } else if self.policy.uses_empty_chain() {
+      if let Some(Slot::Empty { prev, next, .. }) = self.storage.get(index) {
+        self.unlink_empty_chain(index, prev.get(), next.get()).unwrap();
+      }
+    }
+    self.fill_slot(index, item);
+    self.next_cyclic_slot = index + 1;
+    // A spare slot is only added once the chain is empty, so linking it into
+    // the chain cannot fail
+    self.ensure_spare_slot().unwrap();
+  }
+
+  /// Insert a value produced by a closure, which is passed the index the
+  /// value will be stored at. The index is returned.
+  /// Panics if the list has a limit, and every slot under it is occupied.
+  pub fn insert_with<F: FnOnce(usize) -> T>(&mut self, f: F) -> usize {
+    let entry = self.vacant_entry();
+    let index = entry.index();
+    entry.insert(f(index));
+    index
+  }
+
+  /// Set aside an empty slot for a value that will be constructed later. The
+  /// slot is chosen the same way `insert` would choose it, but until the
+  /// Reservation is passed to `commit` or `cancel`, the slot holds no value:
+  /// `get` returns nothing for it, iterators skip it, and no other insertion
+  /// will use it.
+  /// Panics if the list has a limit, and every slot under it is occupied.
+  pub fn reserve(&mut self) -> Reservation {
+    match self.try_reserve_slot() {
+      Ok(reservation) => reservation,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Set aside an empty slot for a value that will be constructed later, like
+  /// `reserve`. Fails under the same conditions as `try_insert`, in which case
+  /// the list is left unchanged.
+  pub fn try_reserve_slot(&mut self) -> Result<Reservation, SlotListError> {
+    if self.slot_count() < self.limit {
+      self.reserve_for_growth(2)?;
+    }
+    let checkpoint = self.checkpoint();
+    let index = self.find_empty_slot()?.ok_or(SlotListError::CapacityExceeded)?;
+    let generation = self.storage.get(index).unwrap().generation();
+    let prev = self.storage.replace(index, Slot::Reserved { generation });
+    self.vacant -= 1;
+    self.reserved += 1;
+    self.next_cyclic_slot = index + 1;
+    if let Err(err) = self.ensure_spare_slot() {
+      self.vacant += 1;
+      self.reserved -= 1;
+      self.put_back(index, prev, checkpoint);
+      return Err(err);
+    }
+    let id = *self.id.get_or_insert_with(next_list_id);
+    Ok(Reservation::new(id, index, generation))
+  }
+
+  /// Store a value in a reserved slot, returning its index.
+  /// Panics if the Reservation was created by a different list.
+  pub fn commit(&mut self, reservation: Reservation, item: T) -> usize {
+    match self.try_commit(reservation, item) {
+      Ok(index) => index,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Store a value in a reserved slot, returning its index. Fails if the
+  /// Reservation doesn't match a reserved slot in this list, in which case the
+  /// value is handed back inside of the error.
+  pub fn try_commit(
+    &mut self,
+    reservation: Reservation,
+    item: T,
+  ) -> Result<usize, InsertError<T>> {
+    let index = reservation.index();
+    if !self.holds_reservation(&reservation) {
+      return Err(InsertError::new(SlotListError::UnknownReservation, item));
+    }
+    self.storage.fill(index, item);
+    self.reserved -= 1;
+    self.len += 1;
+    Ok(index)
+  }
+
+  /// Give a reserved slot back to the list, so that it can be used by a later
+  /// insertion. The slot is returned to the empty chain the same way `remove`
+  /// would return it.
+  /// Panics if the Reservation was created by a different list, or if the
+  /// empty slot chain is found to be corrupted.
+  pub fn cancel(&mut self, reservation: Reservation) {
+    if let Err(err) = self.try_cancel(reservation) {
+      panic!("{}", err);
+    }
+  }
+
+  /// Give a reserved slot back to the list. Fails if the Reservation doesn't
+  /// match a reserved slot in this list, or if the empty slot chain is
+  /// corrupted. If the corruption is only found while applying the shrink
+  /// policy, the slot has already been given back.
+  pub fn try_cancel(
+    &mut self,
+    reservation: Reservation,
+  ) -> Result<(), SlotListError> {
+    let index = reservation.index();
+    if !self.holds_reservation(&reservation) {
+      return Err(SlotListError::UnknownReservation);
+    }
+    let prev = self.storage.take(index);
+    if self.policy.uses_empty_chain() {
+      if let Err(err) = self.push_empty_chain(index) {
+        self.storage.replace(index, prev);
+        return Err(err);
+      }
+    }
+    self.reserved -= 1;
+    self.vacant += 1;
+    // The Reservation has been used up, so the slot stays empty even if the
+    // list can't be shrunk
+    self.apply_shrink_policy()
+  }
+
+  /// Determine whether a Reservation was made by this list, and refers to a
+  /// slot that is still reserved
+  fn holds_reservation(&self, reservation: &Reservation) -> bool {
+    if self.id != Some(reservation.list()) {
+      return false;
+    }
+    match self.storage.get(reservation.index()) {
+      Some(Slot::Reserved { generation }) => {
+        generation == reservation.generation()
+      },
+      _ => false,
+    }
+  }
+
+  /// Construct the Key that a value will have once it is stored in the empty
+  /// slot at the specified index
+  pub(crate) fn next_key(&self, index: usize) -> Key {
+    let generation = self.storage
+      .get(index)
+      .map_or(self.fresh_generation, |slot| slot.generation());
+    Key { index, generation }
+  }
+
+  /// Construct the Key for the value currently stored at the specified index
+  pub fn key_of(&self, index: usize) -> Option<Key> {
+    let slot = self.storage.get(index)?;
+    if slot.is_occupied() {
+      Some(Key {
+        index,
+        generation: slot.generation(),
+      })
+    } else {
+      None
+    }
+  }
+
+  /// Determine whether a key still refers to the value it was created for
+  pub fn contains_key(&self, key: Key) -> bool {
+    self.key_of(key.index) == Some(key)
+  }
+
+  /// Retrieve a reference to the value at the specified index
+  pub fn get(&self, index: usize) -> Option<&T> {
+    self.storage.get_value(index)
+  }
+
+  /// Retrieve a reference to the value a key refers to, if it has not been
+  /// removed
+  pub fn get_by_key(&self, key: Key) -> Option<&T> {
+    if self.contains_key(key) {
+      self.get(key.index)
+    } else {
+      None
+    }
+  }
+
+  /// Retrieve a mutable reference to the value at the specified index
+  pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    self.storage.get_mut(index)
+  }
+
+  /// Retrieve a mutable reference to the value a key refers to, if it has not
+  /// been removed
+  pub fn get_mut_by_key(&mut self, key: Key) -> Option<&mut T> {
+    if self.contains_key(key) {
+      self.get_mut(key.index)
+    } else {
+      None
+    }
+  }
+
+  /// Remove the value at the specified index, returning the value that was
+  /// stored there.
+  /// Panics if the empty slot chain is found to be corrupted.
+  pub fn remove(&mut self, index: usize) -> Option<T> {
+    match self.try_remove(index) {
+      Ok(value) => Some(value),
+      Err(SlotListError::OutOfBounds)
+      | Err(SlotListError::Vacant)
+      | Err(SlotListError::Reserved) => None,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Remove the value at the specified index, returning the value that was
+  /// stored there. Fails if the index is out of bounds, empty, or reserved, or
+  /// if the empty slot chain is corrupted.
+  pub fn try_remove(&mut self, index: usize) -> Result<T, SlotListError> {
+    let prev = self.take_slot(index)?;
+    if let Err(err) = self.apply_shrink_policy() {
+      // A failed shrink leaves the list as it was, so the value can be put
+      // back exactly as it was
+      self.untake_slot(index, prev);
+      return Err(err);
+    }
+    Ok(prev.occupied().unwrap())
+  }
+
+  /// Remove the value at the specified index on behalf of `Drain`, which
+  /// applies the shrink policy once when it is dropped rather than after every
+  /// value
+  pub(crate) fn drain_slot(&mut self, index: usize) -> Option<T> {
+    match self.take_slot(index) {
+      Ok(prev) => prev.occupied(),
+      Err(SlotListError::OutOfBounds)
+      | Err(SlotListError::Vacant)
+      | Err(SlotListError::Reserved) => None,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Empty an occupied slot and add it to the empty chain, keeping the counters
+  /// in sync, and return the slot's previous contents. Nothing is changed if
+  /// this fails.
+  fn take_slot(&mut self, index: usize) -> Result<Slot<T, I>, SlotListError> {
+    match self.storage.get(index) {
+      None => return Err(SlotListError::OutOfBounds),
+      Some(Slot::Occupied { .. }) => (),
+      Some(Slot::Empty { .. }) => return Err(SlotListError::Vacant),
+      Some(Slot::Reserved { .. }) => return Err(SlotListError::Reserved),
+    }
+    let prev = self.storage.take(index);
+
+    // Under bitmap-based policies, the bitmap is the only record of empty
+    // slots. Otherwise, the slot needs to be threaded onto the empty chain.
+    if self.policy.uses_empty_chain() {
+      if let Err(err) = self.push_empty_chain(index) {
+        // Put the value back exactly as it was
+        self.storage.replace(index, prev);
+        return Err(err);
+      }
+    }
+    self.len -= 1;
+    self.vacant += 1;
+    Ok(prev)
+  }
+
+  /// Undo `take_slot` when a later step of a removal fails, putting the
+  /// previous contents of the slot back
+  fn untake_slot(&mut self, index: usize, slot: Slot<T, I>) {
+    if self.policy.uses_empty_chain() {
+      if let Some(Slot::Empty { prev, next, .. }) = self.storage.get(index) {
+        // The slot was just added to the chain, so this cannot fail
+        self.unlink_empty_chain(index, prev.get(), next.get()).unwrap();
+      }
+    }
+    self.storage.replace(index, slot);
+    self.len += 1;
+    self.vacant -= 1;
+  }
+
+  /// Remove the value a key refers to. If the key is stale, the list is left
+  /// untouched.
+  pub fn remove_by_key(&mut self, key: Key) -> Option<T> {
+    match self.try_remove_by_key(key) {
+      Ok(value) => Some(value),
+      Err(SlotListError::StaleKey) => None,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Remove the value a key refers to. Fails if the key is stale, or if the
+  /// empty slot chain is corrupted.
+  pub fn try_remove_by_key(&mut self, key: Key) -> Result<T, SlotListError> {
+    if !self.contains_key(key) {
+      return Err(SlotListError::StaleKey);
+    }
+    self.try_remove(key.index)
+  }
+
+  /// Set a specific slot to the provided value, returning the value that was
+  /// previously stored there.
+  /// If the slot was empty, it is unlinked from the empty slot chain in O(1).
+  /// Panics if the index is out of bounds or reserved.
+  pub fn replace(&mut self, index: usize, item: T) -> Option<T> {
+    match self.try_replace(index, item) {
+      Ok(prev) => prev,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Set a specific slot to the provided value, returning the value that was
+  /// previously stored there. Fails if the index is out of bounds or
+  /// reserved, if a new spare slot can't be allocated, or if the empty slot
+  /// chain is corrupted; in each case, the value is handed back inside of the
+  /// error.
+  pub fn try_replace(
+    &mut self,
+    index: usize,
+    item: T,
+  ) -> Result<Option<T>, InsertError<T>> {
+    let checkpoint = self.checkpoint();
+    let links = match self.storage.get(index) {
+      None => return Err(InsertError::new(SlotListError::OutOfBounds, item)),
+      Some(Slot::Reserved { .. }) => {
+        return Err(InsertError::new(SlotListError::Reserved, item));
+      },
+      Some(Slot::Occupied { .. }) => None,
+      Some(Slot::Empty { prev, next, .. }) => Some((prev, next)),
+    };
+    if let Some((prev, next)) = links {
+      // Filling an empty slot may require pushing a new spare slot
+      if let Err(err) = self.reserve_for_growth(1) {
+        return Err(InsertError::new(err, item));
+      }
+      // `index` represents an element in the empty chain
+      if self.policy.uses_empty_chain() {
+        let unlinked = self.unlink_empty_chain(index, prev.get(), next.get());
+        if let Err(err) = unlinked {
+          return Err(InsertError::new(err, item));
+        }
+      }
+    }
+    let prev = self.fill_slot(index, item);
+    if !prev.is_occupied() {
+      if let Err(err) = self.ensure_spare_slot() {
+        let item = self.restore_slot(index, prev, checkpoint);
+        return Err(InsertError::new(err, item));
+      }
+    }
+
+    Ok(prev.occupied())
+  }
+
+  /// Store a value at a caller-chosen index, returning the value it displaced.
+  /// Unlike `replace`, the index may be past the end of the list; the list is
+  /// grown to fit, and any slots skipped over become empty slots available to
+  /// later insertions. This is the behavior needed to implement `dup2`.
+  /// Panics if the index is not below the list's limit.
+  pub fn insert_at(&mut self, index: usize, item: T) -> Option<T> {
+    match self.try_insert_at(index, item) {
+      Ok(prev) => prev,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Store a value at a caller-chosen index, returning the value it displaced.
+  /// Fails if the index is not below the list's limit, if memory for new
+  /// slots can't be allocated, or if the empty slot chain is corrupted; in
+  /// each case, the value is handed back inside of the error.
+  pub fn try_insert_at(
+    &mut self,
+    index: usize,
+    item: T,
+  ) -> Result<Option<T>, InsertError<T>> {
+    if index < self.slot_count() {
+      return self.try_replace(index, item);
+    }
+    if index >= self.limit {
+      return Err(InsertError::new(SlotListError::CapacityExceeded, item));
+    }
+    if index >= I::MAX_SLOTS {
+      return Err(InsertError::new(SlotListError::IndexOverflow, item));
+    }
+    if let Err(err) = self.reserve_for_growth(index - self.slot_count() + 2) {
+      return Err(InsertError::new(err, item));
+    }
+    let checkpoint = self.checkpoint();
+    while self.slot_count() < index {
+      if let Err(err) = self.push_empty_slot() {
+        self.rewind(checkpoint);
+        return Err(InsertError::new(err, item));
+      }
+    }
+    self.storage.push(self.fresh_slot());
+    self.vacant += 1;
+    let prev = self.fill_slot(index, item);
+    match self.ensure_spare_slot() {
+      Ok(()) => Ok(None),
+      Err(err) => {
+        let item = self.restore_slot(index, prev, checkpoint);
+        Err(InsertError::new(err, item))
+      },
+    }
+  }
+
+  /// Replace the value a key refers to, returning the previous value. If the
+  /// key is stale, the new value is handed back as an error.
+  pub fn replace_by_key(&mut self, key: Key, item: T) -> Result<T, T> {
+    self.try_replace_by_key(key, item).map_err(|err| err.into_inner())
+  }
+
+  /// Replace the value a key refers to, returning the previous value. If the
+  /// key is stale, the new value is handed back inside of the error.
+  pub fn try_replace_by_key(
+    &mut self,
+    key: Key,
+    item: T,
+  ) -> Result<T, InsertError<T>> {
+    if self.contains_key(key) {
+      Ok(self.storage.fill(key.index, item).occupied().unwrap())
+    } else {
+      Err(InsertError::new(SlotListError::StaleKey, item))
+    }
+  }
+
+  /// Find the lowest occupied slot at or after `from`. Runs of vacant slots
+  /// are skipped a word at a time, so this is cheap even when the list is
+  /// sparse.
+  pub fn find_next_occupied(&self, from: usize) -> Option<usize> {
+    let mut from = from;
+    loop {
+      let index = self.storage.next_used(from, self.slot_count())?;
+      // Reserved slots are used, but hold no value
+      if self.storage.get(index)?.is_occupied() {
+        return Some(index);
+      }
+      from = index + 1;
+    }
+  }
+
+  /// Find the lowest vacant slot at or after `from`, among the slots the list
+  /// already has. Reserved slots don't count as vacant.
+  pub fn find_next_vacant(&self, from: usize) -> Option<usize> {
+    self.storage.next_vacant(from, self.slot_count())
+  }
+
+  /// Construct an iterator that will visit all of the occupied slots in
+  /// increasing index order
+  pub fn iter(&self) -> Values<'_, T, I, S> {
+    self.values()
+  }
+
+  /// Construct an iterator that will visit all of the occupied slots in
+  /// increasing index order, yielding mutable references to their values
+  pub fn iter_mut(&mut self) -> ValuesMut<'_, T, I, S> {
+    self.values_mut()
+  }
+
+  /// Construct an iterator that will visit all of the occupied slots in
+  /// increasing index order, yielding each index along with its value
+  pub fn iter_indexed(&self) -> Iter<'_, T, I, S> {
+    Iter::new(self.storage.iter(), self.len)
+  }
+
+  /// Construct an iterator that will visit all of the occupied slots in
+  /// increasing index order, yielding each index along with a mutable
+  /// reference to its value
+  pub fn iter_indexed_mut(&mut self) -> IterMut<'_, T, I, S> {
+    IterMut::new(self.storage.iter_mut(), self.len)
+  }
+
+  /// Construct an iterator over the indices of all occupied slots
+  pub fn keys(&self) -> Keys<'_, T, I, S> {
+    Keys::new(self.iter_indexed())
+  }
+
+  /// Construct an iterator over the values stored in the list
+  pub fn values(&self) -> Values<'_, T, I, S> {
+    Values::new(self.iter_indexed())
+  }
+
+  /// Construct an iterator over mutable references to the values stored in
+  /// the list
+  pub fn values_mut(&mut self) -> ValuesMut<'_, T, I, S> {
+    ValuesMut::new(self.iter_indexed_mut())
+  }
+
+  /// Remove every value from the list, yielding each index along with the
+  /// value that was stored there. The slots themselves are kept, and are
+  /// threaded onto the empty chain in the order they are drained, and the
+  /// shrink policy is applied once the Drain is dropped.
+  /// Panics if the empty slot chain is found to be corrupted.
+  pub fn drain(&mut self) -> Drain<'_, T, I, S> {
+    let slot_count = self.slot_count();
+    let remaining = self.len;
+    Drain::new(self, slot_count, remaining)
+  }
+
+  /// Move values into the lowest empty slots, so that every value sits below
+  /// every empty slot, then trim the empty slots off of the end and release
+  /// the unused memory. Reserved slots are never moved.
+  /// Moving a value changes its index, so a remap table is returned with an
+  /// `(old_index, new_index)` pair for each value that moved, in increasing
+  /// order of old index. Keys to moved values go stale.
+  /// Panics if memory can't be allocated for the remap table or the spare slot,
+  /// or if the bitmaps of a `PackedStorage` can't be trusted.
+  pub fn compact(&mut self) -> Vec<(usize, usize)> {
+    match self.try_compact() {
+      Ok(remap) => remap,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Compact the list, like `compact`. If memory can't be allocated for the
+  /// remap table or the spare slot, or if the bitmaps can't be trusted, an
+  /// error is returned instead, and the list is left unchanged.
+  pub fn try_compact(&mut self) -> Result<Vec<(usize, usize)>, SlotListError> {
+    // Every move fills an empty slot with a value, so there are at most as many
+    // moves as there are values
+    let mut remap = Vec::new();
+    remap
+      .try_reserve(self.len)
+      .map_err(|_| SlotListError::AllocFailed)?;
+    self.reserve_for_spare_slot()?;
+    // Values are found through the bitmaps, so they must be sound before any
+    // value is moved
+    self.rebuild_occupancy()?;
+    let mut front = 0;
+    let mut back = self.slot_count();
+    loop {
+      while front < back && !self.storage.get(front).unwrap().is_vacant() {
+        front += 1;
+      }
+      while front < back && !self.storage.get(back - 1).unwrap().is_occupied() {
+        back -= 1;
+      }
+      if front >= back {
+        break;
+      }
+      back -= 1;
+      let value = self.storage.take(back).occupied().unwrap();
+      self.storage.fill(front, value);
+      remap.push((back, front));
+    }
+    remap.reverse();
+    // Every empty slot is moving, so the chain is re-linked in index order.
+    // Memory for the spare slot was reserved above, so this cannot fail.
+    self.try_rebuild_free_chain().unwrap();
+    // The chain was just rebuilt, so this cannot fail either
+    self.try_shrink_to_fit().unwrap();
+    Ok(remap)
+  }
+
+  /// Trim the empty slots off of the end of the list, keeping one spare, and
+  /// release the unused memory. No value is ever moved.
+  /// Panics if the empty slot chain is found to be corrupted.
+  pub fn shrink_to_fit(&mut self) {
+    if let Err(err) = self.try_shrink_to_fit() {
+      panic!("{}", err);
+    }
+  }
+
+  /// Trim the empty slots off of the end of the list, keeping one spare, and
+  /// release the unused memory. Fails if the empty slot chain is corrupted, in
+  /// which case the list is left unchanged.
+  pub fn try_shrink_to_fit(&mut self) -> Result<(), SlotListError> {
+    let last_used = (0..self.slot_count())
+      .rev()
+      .find(|&index| !self.storage.get(index).unwrap().is_vacant());
+    let keep = match last_used {
+      Some(index) => index + 2,
+      None => 0,
+    };
+    // Check every slot that will be trimmed before unlinking any of them.
+    // Unlinking a slot keeps its neighbors in agreement with each other, so
+    // the checks still hold as the slots are trimmed.
+    if self.policy.uses_empty_chain() {
+      for index in keep..self.slot_count() {
+        if let Some(Slot::Empty { prev, next, .. }) = self.storage.get(index) {
+          if !self.neighbors_agree(index, prev.get(), next.get()) {
+            return Err(SlotListError::ChainCorrupted);
+          }
+        }
+      }
+    }
+    while self.slot_count() > keep {
+      let index = self.slot_count() - 1;
+      let slot = self.storage.get(index);
+      if let Some(Slot::Empty { prev, next, generation }) = slot {
+        if self.policy.uses_empty_chain() {
+          self.unlink_empty_chain(index, prev.get(), next.get())?;
+        }
+        self.fresh_generation =
+          core::cmp::max(self.fresh_generation, generation);
+      }
+      self.storage.pop();
+      self.vacant -= 1;
+    }
+    self.storage.shrink_to_fit();
+    Ok(())
+  }
+
+  /// Called after slots are emptied, to release memory if the shrink policy
+  /// calls for it. Fails without changing anything if the empty slot chain is
+  /// corrupted.
+  pub(crate) fn apply_shrink_policy(&mut self) -> Result<(), SlotListError> {
+    let used = self.len + self.reserved;
+    if self.shrink_policy.should_shrink(used, self.storage.capacity()) {
+      self.try_shrink_to_fit()?;
+    }
+    Ok(())
+  }
+
+  /// Verify that the list's internal bookkeeping is consistent: the empty
+  /// chain must link every empty slot exactly once in both directions, and the
+  /// occupancy bitmap and counters must agree with the contents of the slots.
+  /// The first problem found is reported.
+  /// This is `O(n)`, and is intended for debugging and for recovering from
+  /// suspected corruption with `rebuild_free_chain`.
+  pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+    // Slots are read through the bitmaps of storage that has no other record
+    // of what they hold, so those are checked first
+    self.storage.check_occupancy(self.len, self.reserved)?;
+    let mut occupied = 0;
+    let mut reserved = 0;
+    for index in 0..self.slot_count() {
+      let slot = self.storage.get(index).unwrap();
+      if slot.is_vacant() == self.storage.is_marked_used(index) {
+        return Err(InvariantViolation::OccupancyMismatch { index });
+      }
+      match slot {
+        Slot::Occupied { .. } => occupied += 1,
+        Slot::Reserved { .. } => reserved += 1,
+        Slot::Empty { .. } => (),
+      }
+    }
+    if self.len != occupied {
+      return Err(InvariantViolation::WrongLength {
+        expected: occupied,
+        found: self.len,
+      });
+    }
+    if self.reserved != reserved {
+      return Err(InvariantViolation::WrongReservedCount {
+        expected: reserved,
+        found: self.reserved,
+      });
+    }
+    let vacant = self.slot_count() - occupied - reserved;
+    if self.vacant != vacant {
+      return Err(InvariantViolation::WrongVacantCount {
+        expected: vacant,
+        found: self.vacant,
+      });
+    }
+    if !self.policy.uses_empty_chain() {
+      return Ok(());
+    }
+
+    // Nothing is allocated here, so that lists without an allocator can be
+    // checked too. Finding which slot is at fault may require walking the
+    // chain again, but only once a problem has already been detected.
+    let mut visited = 0;
+    let mut previous = None;
+    let mut current = self.first_empty_slot.get();
+    while let Some(index) = current {
+      let (prev, next) = match self.storage.get(index) {
+        None => return Err(InvariantViolation::ChainOutOfBounds { index }),
+        Some(Slot::Occupied { .. }) | Some(Slot::Reserved { .. }) => {
+          return Err(InvariantViolation::OccupiedInChain { index });
+        },
+        Some(Slot::Empty { prev, next, .. }) => (prev.get(), next.get()),
+      };
+      if prev != previous {
+        // Returning to a slot that was already visited always breaks a back
+        // link, since the slot still links back to its first predecessor
+        if self.chain_prefix_contains(visited, index) {
+          return Err(InvariantViolation::ChainCycle { index });
+        }
+        return Err(InvariantViolation::BrokenBackLink {
+          index,
+          expected: previous,
+          found: prev,
+        });
+      }
+      visited += 1;
+      previous = current;
+      current = next;
+    }
+    if self.last_empty_slot.get() != previous {
+      let found = self.last_empty_slot.get();
+      return Err(InvariantViolation::WrongTail { expected: previous, found });
+    }
+    if visited != vacant {
+      for index in 0..self.slot_count() {
+        let vacant = self.storage.get(index).unwrap().is_vacant();
+        if vacant && !self.chain_prefix_contains(visited, index) {
+          return Err(InvariantViolation::UnreachableEmptySlot { index });
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Determine whether a slot is one of the first `length` slots of the empty
+  /// chain, which must already be known to be intact
+  fn chain_prefix_contains(&self, length: usize, index: usize) -> bool {
+    let mut current = self.first_empty_slot.get();
+    for _ in 0..length {
+      match current {
+        Some(current_index) if current_index == index => return true,
+        Some(current_index) => match self.storage.get(current_index) {
+          Some(Slot::Empty { next, .. }) => current = next.get(),
+          _ => return false,
+        },
+        None => return false,
+      }
+    }
+    false
+  }
+
+  /// Reconstruct the empty chain, the occupancy bitmap, and the counters from
+  /// the contents of the slots, discarding whatever state they were previously
+  /// in. This can be used to recover from a corrupted chain, or after bulk
+  /// edits.
+  /// Empty slots are re-linked by visiting them in increasing index order, and
+  /// each one is added to the chain the way its policy adds an emptied slot.
+  /// A `Fifo` chain ends up in increasing order, so the lowest slot is re-used
+  /// first, while a `Lifo` chain ends up in decreasing order, so the highest
+  /// slot is re-used first. `LowestIndex` and `Cyclic` lists don't keep a
+  /// chain, so only their bitmap and counters are rebuilt.
+  /// `PackedStorage` keeps no record of what each slot holds other than its
+  /// bitmaps, so they are checked against the counters instead of rebuilt.
+  /// Panics if the bitmaps can't be trusted, or if a spare slot needs to be
+  /// added and memory can't be allocated.
+  pub fn rebuild_free_chain(&mut self) {
+    if let Err(err) = self.try_rebuild_free_chain() {
+      panic!("{}", err);
+    }
+  }
+
+  /// Rebuild the empty chain, like `rebuild_free_chain`. If the bitmaps can't
+  /// be trusted, or if a spare slot needs to be added and memory can't be
+  /// allocated, an error is returned instead, and the chain and counters are
+  /// left unchanged.
+  pub fn try_rebuild_free_chain(&mut self) -> Result<(), SlotListError> {
+    self.reserve_for_spare_slot()?;
+    self.rebuild_occupancy()?;
+    self.first_empty_slot = Link::NONE;
+    self.last_empty_slot = Link::NONE;
+    self.len = 0;
+    self.vacant = 0;
+    self.reserved = 0;
+    for index in 0..self.slot_count() {
+      match self.storage.get(index).unwrap() {
+        Slot::Occupied { .. } => self.len += 1,
+        Slot::Reserved { .. } => self.reserved += 1,
+        Slot::Empty { .. } => {
+          self.vacant += 1;
+          // The slot is known to be empty, so these cannot fail
+          self.storage.set_prev_empty(index, None).unwrap();
+          self.storage.set_next_empty(index, None).unwrap();
+          if self.policy.uses_empty_chain() {
+            // The slot was just unlinked, so this cannot fail
+            self.push_empty_chain(index).unwrap();
+          }
+        },
+      }
+    }
+    if self.slot_count() > 0 {
+      self.ensure_spare_slot()?;
+    }
+    Ok(())
+  }
+
+  /// Rebuild the storage's record of which slots are in use. Storage that keeps
+  /// no other record can only repair part of it, and fails if the rest
+  /// disagrees with itself or with the counters.
+  fn rebuild_occupancy(&mut self) -> Result<(), SlotListError> {
+    self.storage.rebuild_occupancy();
+    self.storage
+      .check_occupancy(self.len, self.reserved)
+      .map_err(|_| SlotListError::OccupancyCorrupted)
+  }
+
+  /// Record the parts of the list that aren't held in its slots or its empty
+  /// chain, so that the list can be restored later
+  pub(crate) fn saved_state(&self) -> SavedState {
+    SavedState {
+      policy: self.policy,
+      shrink_policy: self.shrink_policy,
+      limit: self.limit,
+      next_cyclic_slot: self.next_cyclic_slot,
+      fresh_generation: self.fresh_generation,
+    }
+  }
+
+  /// The order in which empty slots should be re-linked when the list is
+  /// restored: the empty chain from front to back, followed by any reserved
+  /// slots. A reservation can't outlive its list, so reserved slots are saved
+  /// as empty slots that will be the last to be re-used.
+  /// Lists that don't use the empty chain save no order at all.
+  pub(crate) fn saved_chain(&self) -> impl Iterator<Item = usize> + '_ {
+    let (first, slot_count) = if self.policy.uses_empty_chain() {
+      (self.first_empty_slot.get(), self.slot_count())
+    } else {
+      (None, 0)
+    };
+    // A corrupted chain is cut short, rather than followed forever
+    let chain = core::iter::successors(first, move |index| {
+      match self.storage.get(*index) {
+        Some(Slot::Empty { next, .. }) => next.get(),
+        _ => None,
+      }
+    });
+    let reserved = (0..slot_count).filter(move |index| {
+      matches!(self.storage.get(*index), Some(Slot::Reserved { .. }))
+    });
+    chain.take(self.vacant).chain(reserved)
+  }
+
+  /// Rebuild a saved list around storage that already holds its slots, with
+  /// every empty slot unlinked. The empty slots are linked in the order given
+  /// by `chain`, whatever the list's policy.
+  /// The restored list is checked with `check_invariants`, so that a saved
+  /// list from an untrusted source can't produce an inconsistent list.
+  pub(crate) fn restore(
+    state: SavedState,
+    storage: S,
+    chain: impl IntoIterator<Item = usize>,
+  ) -> Result<SlotList<T, I, S>, InvariantViolation> {
+    let limit = core::cmp::min(state.limit, S::MAX_SLOTS);
+    let mut list = SlotList::from_storage(storage, state.policy, limit);
+    list.shrink_policy = state.shrink_policy;
+    list.next_cyclic_slot = state.next_cyclic_slot;
+    list.fresh_generation = state.fresh_generation;
+    for index in 0..list.slot_count() {
+      match list.storage.get(index).unwrap() {
+        Slot::Occupied { .. } => list.len += 1,
+        Slot::Reserved { .. } => list.reserved += 1,
+        Slot::Empty { .. } => list.vacant += 1,
+      }
+    }
+    for index in chain {
+      let last = list.last_empty_slot.get();
+      list.storage.set_prev_empty(index, last).map_err(|err| match err {
+        SlotListError::OutOfBounds => {
+          InvariantViolation::ChainOutOfBounds { index }
+        },
+        _ => InvariantViolation::OccupiedInChain { index },
+      })?;
+      match last {
+        // The last slot was linked successfully, so it is known to be empty
+        Some(last) => list.storage.set_next_empty(last, Some(index)).unwrap(),
+        None => list.first_empty_slot = Link::new(Some(index)),
+      }
+      list.last_empty_slot = Link::new(Some(index));
+    }
+    list.check_invariants()?;
+    Ok(list)
+  }
+
+  /// Helper for testing chain consistency, only available in test mode
+  #[cfg(test)]
+  pub fn get_first_empty_slot(&self) -> Option<usize> {
+    self.first_empty_slot.get()
+  }
+
+  /// Helper for testing chain consistency, only available in test mode
+  #[cfg(test)]
+  pub fn get_last_empty_slot(&self) -> Option<usize> {
+    self.last_empty_slot.get()
+  }
+
+  /// Helper for corrupting the storage, only available in test mode
+  #[cfg(test)]
+  pub(crate) fn storage_mut(&mut self) -> &mut S {
+    &mut self.storage
+  }
+
+  /// Look at the raw contents of a slot, for testing chain consistency and for
+  /// saving the list
+  pub(crate) fn get_raw_slot(&self, index: usize) -> Option<Slot<&T, I>> {
+    self.storage.get(index)
+  }
+}
+
+/// The parts of a list that aren't held in its slots or its empty chain, as
+/// recorded when the list is saved
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SavedState {
+  pub policy: AllocationPolicy,
+  pub shrink_policy: ShrinkPolicy,
+  pub limit: usize,
+  pub next_cyclic_slot: usize,
+  pub fresh_generation: u32,
+}
+
+impl<T, I: SlotIndex, S: Storage<T, I>> IntoIterator for SlotList<T, I, S> {
+  type Item = (usize, T);
+  type IntoIter = IntoIter<T, I, S>;
+
+  fn into_iter(self) -> IntoIter<T, I, S> {
+    IntoIter::new(self.storage.into_iter(), self.len)
+  }
+}
+
+impl<'a, T, I, S> IntoIterator for &'a SlotList<T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  type Item = (usize, &'a T);
+  type IntoIter = Iter<'a, T, I, S>;
+
+  fn into_iter(self) -> Iter<'a, T, I, S> {
+    self.iter_indexed()
+  }
+}
+
+impl<'a, T, I, S> IntoIterator for &'a mut SlotList<T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  type Item = (usize, &'a mut T);
+  type IntoIter = IterMut<'a, T, I, S>;
+
+  fn into_iter(self) -> IterMut<'a, T, I, S> {
+    self.iter_indexed_mut()
+  }
+}
+
+impl<T: Sized, I: SlotIndex> Default for SlotList<T, I> {
+  fn default() -> SlotList<T, I> {
+    SlotList::with_index()
+  }
+}
+
+impl<T: Clone, I: Copy, S: Clone> Clone for SlotList<T, I, S> {
+  fn clone(&self) -> Self {
+    Self {
+      policy: self.policy,
+      shrink_policy: self.shrink_policy,
+      limit: self.limit,
+      next_cyclic_slot: self.next_cyclic_slot,
+      first_empty_slot: self.first_empty_slot,
+      last_empty_slot: self.last_empty_slot,
+      len: self.len,
+      vacant: self.vacant,
+      reserved: self.reserved,
+      fresh_generation: self.fresh_generation,
+      // Reservations belong to the list they were made by, not its clones
+      id: None,
+      storage: self.storage.clone(),
+      marker: PhantomData,
+    }
+  }
+}
+
+impl<T, I, S> core::fmt::Debug for SlotList<T, I, S>
+where
+  T: core::fmt::Debug,
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    formatter.debug_list()
+      .entries((0..self.slot_count()).map(|index| {
+        self.storage.get(index).unwrap()
+      }))
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    AllocationPolicy, Key, Link, ShrinkPolicy, Slot, SlotIndex, SlotList,
+    Storage, Vec,
+  };
+  use crate::chunked::ChunkedStorage;
+  use crate::packed::PackedStorage;
+  use crate::skipfield::SkipfieldStorage;
+  use crate::error::{InvariantViolation, SlotListError};
+
+  const POLICIES: [AllocationPolicy; 4] = [
+    AllocationPolicy::Fifo,
+    AllocationPolicy::Lifo,
+    AllocationPolicy::LowestIndex,
+    AllocationPolicy::Cyclic,
+  ];
+
+  /// The indices in the empty chain, from front to back
+  fn chain_order<I: SlotIndex, S: Storage<u32, I>>(
+    list: &SlotList<u32, I, S>,
+  ) -> Vec<usize> {
+    let mut forward = Vec::new();
+    let mut current = list.get_first_empty_slot();
+    while let Some(index) = current {
+      forward.push(index);
+      match list.get_raw_slot(index) {
+        Some(Slot::Empty { next, .. }) => current = next.get(),
+        _ => panic!("Chain contains a non-empty slot"),
+      }
+    }
+    forward
+  }
+
+  /// Walk the empty chain in both directions, ensuring that the links agree
+  /// with each other and that every empty slot is part of the chain
+  fn assert_chain_consistent<I: SlotIndex, S: Storage<u32, I>>(
+    list: &SlotList<u32, I, S>,
+  ) {
+    let forward = chain_order(list);
+    let mut backward = Vec::new();
+    let mut current = list.get_last_empty_slot();
+    while let Some(index) = current {
+      backward.push(index);
+      match list.get_raw_slot(index) {
+        Some(Slot::Empty { prev, .. }) => current = prev.get(),
+        _ => panic!("Chain contains a non-empty slot"),
+      }
+    }
+    backward.reverse();
+    assert_eq!(forward, backward);
+    let empty_count = (0..list.slot_count())
+      .filter(|&index| list.get_raw_slot(index).unwrap().is_vacant())
+      .count();
+    assert_eq!(forward.len(), empty_count);
+  }
+
+  /// Behavior shared by every allocation policy: run a list alongside a plain
+  /// `Vec<Option<_>>` through the same pseudo-random sequence of operations,
+  /// and ensure the two always agree.
+  fn exercise_policy<I: SlotIndex>(policy: AllocationPolicy) {
+    exercise_list(SlotList::<u32, I>::with_index_policy_and_limit(
+      policy,
+      usize::MAX,
+    ));
+  }
+
+  fn exercise_list<I: SlotIndex, S: Storage<u32, I>>(
+    mut list: SlotList<u32, I, S>,
+  ) {
+    let policy = list.policy();
+    let mut model: Vec<Option<u32>> = Vec::new();
+    let mut seed: u32 = 0x2545_f491;
+    let steps = if cfg!(miri) { 100 } else { 2000 };
+    for step in 0..steps {
+      seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+      let roll = (seed >> 16) % 10;
+      let target = (seed >> 4) as usize % (model.len() + 1);
+      if roll < 5 || model.is_empty() {
+        let index = list.insert(step);
+        if index >= model.len() {
+          model.resize(index + 1, None);
+        }
+        assert_eq!(model[index], None, "{:?} re-used an occupied slot", policy);
+        model[index] = Some(step);
+      } else if roll < 8 {
+        let expected = model.get_mut(target).and_then(|entry| entry.take());
+        assert_eq!(list.remove(target), expected);
+      } else if roll < 9 && target < model.len() {
+        let expected = model[target].replace(step);
+        assert_eq!(list.replace(target, step), expected);
+      } else if roll == 9 {
+        // Occasionally reach past the end of the list
+        let index = target + (seed as usize % 3);
+        if index >= model.len() {
+          model.resize(index + 1, None);
+        }
+        let expected = model[index].replace(step);
+        assert_eq!(list.insert_at(index, step), expected);
+      }
+      for (index, entry) in model.iter().enumerate() {
+        assert_eq!(list.get(index), entry.as_ref());
+      }
+      let keys = model
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.is_some())
+        .map(|(index, _)| index);
+      assert!(list.keys().eq(keys.clone()));
+      assert!(list.keys().rev().eq(keys.rev()));
+      if policy.uses_empty_chain() {
+        assert_chain_consistent(&list);
+      }
+      assert_eq!(list.check_invariants(), Ok(()));
+    }
+    let values: Vec<u32> = list.values().copied().collect();
+    let expected: Vec<u32> = model.iter().filter_map(|entry| *entry).collect();
+    assert_eq!(values, expected);
+  }
+
+  #[test]
+  fn initialization() {
+    let mut list: SlotList<u32> = SlotList::new();
+    assert_eq!(list.insert(5), 0);
+  }
+
+  #[test]
+  fn inserting_items() {
+    let mut list: SlotList<u32> = SlotList::with_capacity(3);
+    assert_eq!(list.get(1), None);
+    assert_eq!(list.insert(20), 0);
+    assert_eq!(list.insert(30), 1);
+    assert_eq!(list.insert(40), 2);
+    assert_eq!(list.get(0), Some(&20));
+    assert_eq!(list.get(1), Some(&30));
+    assert_eq!(list.get(2), Some(&40));
+    assert_eq!(list.get(3), None);
+  }
+
+  #[test]
+  fn grow_to_fit() {
+    let mut list: SlotList<u32> = SlotList::new();
+    assert_eq!(list.get(1), None);
+    assert_eq!(list.insert(20), 0);
+    assert_eq!(list.insert(30), 1);
+    assert_eq!(list.insert(40), 2);
+    assert_eq!(list.get(0), Some(&20));
+    assert_eq!(list.get(1), Some(&30));
+    assert_eq!(list.get(2), Some(&40));
+    assert_eq!(list.get(3), None);
+  }
+
+  #[test]
+  fn removing_items() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(55);
+    list.insert(40);
+    list.insert(60);
+    assert_eq!(list.remove(1), Some(40));
+    assert_eq!(list.get(1), None);
+  }
+
+  #[test]
+  fn replacing_emptied_items() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(11);
+    list.insert(22);
+    list.insert(33);
+    list.remove(0);
+    list.remove(1);
+    // First it will fill the empty slot at the end of the list
+    assert_eq!(list.insert(44), 3);
+    // Another empty slot has been added to index 4, but that is at the end of
+    // the empty chain. 
+    // Next it will fill the previously freed slots at 0 and 1
+    assert_eq!(list.insert(55), 0);
+    assert_eq!(list.insert(66), 1);
+    // Once those have been filled, the chain returns to point to slot 4
+    assert_eq!(list.insert(77), 4);
+  }
+
+  #[test]
+  fn replacing_empty_slot() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(0);
+    assert_eq!(list.get_first_empty_slot(), Some(1));
+    assert_eq!(list.get_last_empty_slot(), Some(1));
+    list.remove(0);
+    assert_eq!(list.get_first_empty_slot(), Some(1));
+    assert_eq!(list.get_last_empty_slot(), Some(0));
+    // Replacing the last element in the "empty chain" should fix up the chain
+    // and its pointers.
+    assert_eq!(list.replace(0, 5), None);
+    assert_eq!(list.get_first_empty_slot(), Some(1));
+    assert_eq!(list.get_last_empty_slot(), Some(1));
+    if let Slot::Empty { next, .. } = list.get_raw_slot(1).unwrap() {
+      assert!(next.get().is_none());
+    } else {
+      panic!("First slot was not empty");
+    }
+  }
+
+  #[test]
+  fn replacing_existing_entries() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    list.insert(3);
+    list.insert(5);
+    list.remove(1);
+    assert_eq!(list.replace(0, 10), Some(1));
+    assert_eq!(list.replace(1, 12), None);
+  }
+
+  #[test]
+  fn iterator() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    list.insert(2);
+    list.insert(1);
+    list.insert(3);
+    list.insert(1);
+
+    list.remove(1);
+    list.remove(3);
+    let mut count = 0;
+    for x in list.iter() {
+      count += 1;
+      assert_eq!(*x, 1);
+    }
+    assert_eq!(count, 3);
+  }
+
+  #[test]
+  fn maintain_size() {
+    let mut list: SlotList<u32> = SlotList::with_capacity(4);
+    for _ in 0..100 {
+      let index = list.insert(10);
+      list.remove(index);
     }
     assert_eq!(list.capacity(), 4);
   }
+
+  #[test]
+  fn stale_keys() {
+    let mut list: SlotList<u32> = SlotList::new();
+    let first = list.insert_keyed(10);
+    assert_eq!(list.get_by_key(first), Some(&10));
+    assert_eq!(list.remove_by_key(first), Some(10));
+    assert_eq!(list.remove_by_key(first), None);
+    // Fill the sentinel slot, then re-use the slot that `first` pointed to
+    list.insert(20);
+    let second = list.insert_keyed(30);
+    assert_eq!(second.index(), first.index());
+    assert_ne!(second.generation(), first.generation());
+    assert_eq!(list.get_by_key(first), None);
+    assert_eq!(list.get_mut_by_key(first), None);
+    assert_eq!(list.replace_by_key(first, 40), Err(40));
+    assert_eq!(list.get_by_key(second), Some(&30));
+    assert_eq!(list.replace_by_key(second, 50), Ok(30));
+    assert_eq!(list.get_by_key(second), Some(&50));
+  }
+
+  #[test]
+  fn key_of_index() {
+    let mut list: SlotList<u32> = SlotList::new();
+    let index = list.insert(1);
+    let key = list.key_of(index).unwrap();
+    assert_eq!(key.index(), index);
+    assert!(list.contains_key(key));
+    list.remove(index);
+    assert_eq!(list.key_of(index), None);
+    assert!(!list.contains_key(key));
+    assert!(!list.contains_key(Key { index: 50, generation: 0 }));
+  }
+
+  #[test]
+  fn lowest_index_policy() {
+    let mut list: SlotList<u32> =
+      SlotList::with_policy(AllocationPolicy::LowestIndex);
+    assert_eq!(list.insert(11), 0);
+    assert_eq!(list.insert(22), 1);
+    assert_eq!(list.insert(33), 2);
+    list.remove(1);
+    list.remove(0);
+    // Unlike the default policy, the freed slots are used before the spare
+    // slot at the end of the list, lowest index first
+    assert_eq!(list.insert(44), 0);
+    assert_eq!(list.insert(55), 1);
+    assert_eq!(list.insert(66), 3);
+    assert_eq!(list.insert(77), 4);
+    list.remove(2);
+    assert_eq!(list.insert(88), 2);
+  }
+
+  #[test]
+  fn lowest_index_replace() {
+    let mut list: SlotList<u32> =
+      SlotList::with_policy(AllocationPolicy::LowestIndex);
+    list.insert(1);
+    list.insert(2);
+    // Filling the spare slot directly still leaves room at the end
+    assert_eq!(list.replace(2, 3), None);
+    assert_eq!(list.insert(4), 3);
+    list.remove(0);
+    assert_eq!(list.replace(0, 5), None);
+    assert_eq!(list.insert(6), 4);
+  }
+
+  #[test]
+  fn all_policies_behave_like_a_vec() {
+    for policy in POLICIES.iter() {
+      exercise_policy::<usize>(*policy);
+    }
+  }
+
+  #[test]
+  fn all_policies_with_packed_storage() {
+    for policy in POLICIES.iter() {
+      let storage = PackedStorage::<u32, u16>::new();
+      exercise_list(SlotList::with_storage_policy_and_limit(
+        storage,
+        *policy,
+        usize::MAX,
+      ));
+    }
+  }
+
+  #[test]
+  fn all_policies_with_skipfield() {
+    for policy in POLICIES.iter() {
+      let storage = SkipfieldStorage::<u32, u16>::new();
+      exercise_list(SlotList::with_storage_policy_and_limit(
+        storage,
+        *policy,
+        usize::MAX,
+      ));
+    }
+  }
+
+  #[test]
+  fn all_policies_with_chunked_storage() {
+    for policy in POLICIES.iter() {
+      let storage = ChunkedStorage::<u32, u16>::new();
+      exercise_list(SlotList::with_storage_policy_and_limit(
+        storage,
+        *policy,
+        usize::MAX,
+      ));
+    }
+  }
+
+  #[test]
+  fn all_policies_with_narrow_indices() {
+    for policy in POLICIES.iter() {
+      exercise_policy::<u32>(*policy);
+      exercise_policy::<u16>(*policy);
+    }
+  }
+
+  #[test]
+  fn all_policies_replace_spare_slot() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      list.insert(1);
+      // Claiming every empty slot directly still leaves room at the end
+      assert_eq!(list.replace(1, 2), None);
+      assert_eq!(list.insert(3), 2);
+      assert_eq!(list.get(1), Some(&2));
+    }
+  }
+
+  #[test]
+  fn lifo_policy() {
+    let mut list: SlotList<u32> = SlotList::with_policy(AllocationPolicy::Lifo);
+    list.insert(11);
+    list.insert(22);
+    list.insert(33);
+    list.remove(0);
+    list.remove(1);
+    // The most recently freed slot comes back first
+    assert_eq!(list.insert(44), 1);
+    assert_eq!(list.insert(55), 0);
+    assert_eq!(list.insert(66), 3);
+  }
+
+  #[test]
+  fn cyclic_policy() {
+    let mut list: SlotList<u32> =
+      SlotList::with_policy(AllocationPolicy::Cyclic);
+    list.insert(11);
+    list.insert(22);
+    list.insert(33);
+    list.remove(0);
+    // The search continues past the most recent allocation before wrapping
+    assert_eq!(list.insert(44), 3);
+    list.remove(1);
+    assert_eq!(list.insert(55), 0);
+    assert_eq!(list.insert(66), 1);
+    assert_eq!(list.insert(77), 4);
+  }
+
+  #[test]
+  fn insert_at_index() {
+    let mut list: SlotList<u32> = SlotList::new();
+    // Restoring fixed handles on an empty list
+    assert_eq!(list.insert_at(2, 12), None);
+    assert_eq!(list.insert_at(0, 10), None);
+    assert_eq!(list.get(0), Some(&10));
+    assert_eq!(list.get(1), None);
+    assert_eq!(list.get(2), Some(&12));
+    // The remaining gap is handed out first, followed by the spare slot
+    assert_eq!(list.insert(11), 1);
+    assert_eq!(list.insert(13), 3);
+    // Displacing an existing value
+    assert_eq!(list.insert_at(1, 21), Some(11));
+    assert_eq!(list.get(1), Some(&21));
+  }
+
+  #[test]
+  fn insert_at_threads_gaps() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      list.insert(0);
+      assert_eq!(list.insert_at(5, 5), None);
+      let mut indices: Vec<usize> = (0..5).map(|n| list.insert(n)).collect();
+      indices.sort_unstable();
+      assert_eq!(indices, [1, 2, 3, 4, 6]);
+      assert_eq!(list.get(5), Some(&5));
+    }
+  }
+
+  #[test]
+  fn limited_list() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy_and_limit(*policy, 3);
+      assert_eq!(list.limit(), Some(3));
+      assert!(list.try_insert(10).is_ok());
+      assert!(list.try_insert(11).is_ok());
+      assert!(list.try_insert(12).is_ok());
+      let err = list.try_insert(13).unwrap_err();
+      assert_eq!(err.kind(), SlotListError::CapacityExceeded);
+      assert_eq!(err.into_inner(), 13);
+      assert!(list.get_raw_slot(3).is_none());
+      // Freeing a slot makes room again
+      list.remove(1);
+      assert_eq!(list.try_insert(14).unwrap(), 1);
+      assert!(list.try_insert(15).is_err());
+    }
+  }
+
+  #[test]
+  fn limited_list_insert_at() {
+    let mut list: SlotList<u32> = SlotList::with_limit(4);
+    assert_eq!(list.insert_at(3, 3), None);
+    assert_eq!(list.insert(0), 0);
+    assert_eq!(list.insert(1), 1);
+    assert_eq!(list.insert(2), 2);
+    assert!(list.try_insert(4).is_err());
+  }
+
+  #[test]
+  #[should_panic]
+  fn insert_at_past_limit() {
+    let mut list: SlotList<u32> = SlotList::with_limit(4);
+    list.insert_at(4, 4);
+  }
+
+  #[test]
+  fn unlimited_list() {
+    let mut list: SlotList<u32> = SlotList::new();
+    assert_eq!(list.limit(), None);
+    for i in 0..100 {
+      assert_eq!(list.try_insert(i).unwrap(), i as usize);
+    }
+  }
+
+  #[test]
+  fn reserving_capacity() {
+    let mut list: SlotList<u32> = SlotList::new();
+    assert!(list.try_reserve(10).is_ok());
+    assert!(list.capacity() >= 10);
+    assert!(list.try_reserve(usize::MAX).is_err());
+    assert_eq!(list.try_insert(5).unwrap(), 0);
+  }
+
+  #[test]
+  fn fallible_operations() {
+    let mut list: SlotList<u32> = SlotList::new();
+    let key = list.try_insert_keyed(1).unwrap();
+    list.insert(2);
+    assert_eq!(list.try_remove(10), Err(SlotListError::OutOfBounds));
+    assert_eq!(list.try_remove(2), Err(SlotListError::Vacant));
+    let err = list.try_replace(10, 3).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::OutOfBounds);
+    assert_eq!(list.try_replace(1, 3).unwrap(), Some(2));
+    assert_eq!(list.try_remove_by_key(key), Ok(1));
+    assert_eq!(list.try_remove_by_key(key), Err(SlotListError::StaleKey));
+    let err = list.try_replace_by_key(key, 4).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::StaleKey);
+    assert_eq!(err.into_inner(), 4);
+    assert_eq!(list.try_insert_at(5, 5).unwrap(), None);
+    assert_eq!(list.get(5), Some(&5));
+  }
+
+  #[test]
+  fn corrupted_chain() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    list.insert(2);
+    // Point the chain at an occupied slot
+    list.first_empty_slot = Link::new(Some(0));
+    let err = list.try_insert(3).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::ChainCorrupted);
+    assert_eq!(err.into_inner(), 3);
+    list.last_empty_slot = Link::new(Some(1));
+    // The value is left in place when it can't be added to the chain
+    assert_eq!(list.try_remove(0), Err(SlotListError::Occupied));
+    assert_eq!(list.get(0), Some(&1));
+  }
+
+  #[test]
+  fn failed_insertions_leave_chain_unchanged() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy_and_limit(*policy, 6);
+      for i in 0..6 {
+        list.insert(i);
+      }
+      list.remove(4);
+      list.remove(1);
+      let order = chain_order(&list);
+      let err = list.try_insert_at(6, 6).unwrap_err();
+      assert_eq!(err.kind(), SlotListError::CapacityExceeded);
+      assert_eq!(list.check_invariants(), Ok(()));
+      assert_eq!(chain_order(&list), order);
+      list.insert(7);
+      list.insert(8);
+      let err = list.try_insert(9).unwrap_err();
+      assert_eq!(err.kind(), SlotListError::CapacityExceeded);
+      assert_eq!(list.check_invariants(), Ok(()));
+      assert_eq!(list.slot_count(), 6);
+    }
+    let mut list: SlotList<u32, u16> = SlotList::with_index();
+    for i in 0..u16::MAX {
+      list.insert(i as u32);
+    }
+    list.remove(10);
+    list.remove(5);
+    let order = chain_order(&list);
+    let err = list.try_insert_at(65535, 1).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::IndexOverflow);
+    assert_eq!(list.check_invariants(), Ok(()));
+    assert_eq!(chain_order(&list), order);
+  }
+
+  #[test]
+  fn failed_spare_slot_restores_chain() {
+    // Make the list believe the chain is about to run out, while the tail of
+    // the chain points at an occupied slot, so that the spare slot pushed
+    // after storing a value can't be linked in
+    fn corrupt(list: &mut SlotList<u32>) {
+      list.vacant = 1;
+      list.last_empty_slot = Link::new(Some(3));
+    }
+    fn repair(list: &mut SlotList<u32>) {
+      list.vacant = 3;
+      list.last_empty_slot = Link::new(Some(2));
+    }
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..4 {
+      list.insert(i);
+    }
+    list.remove(1);
+    list.remove(2);
+    assert_eq!(chain_order(&list), [4, 1, 2]);
+    let cursor = list.next_cyclic_slot;
+
+    corrupt(&mut list);
+    let err = list.try_insert(10).unwrap_err();
+    assert_eq!(err.into_inner(), 10);
+    let err = list.try_replace(1, 11).unwrap_err();
+    assert_eq!(err.into_inner(), 11);
+    let err = list.try_insert_at(7, 12).unwrap_err();
+    assert_eq!(err.into_inner(), 12);
+    assert!(list.try_reserve_slot().is_err());
+    repair(&mut list);
+
+    assert_eq!(list.check_invariants(), Ok(()));
+    assert_eq!(chain_order(&list), [4, 1, 2]);
+    assert_eq!(list.slot_count(), 5);
+    assert_eq!(list.next_cyclic_slot, cursor);
+    assert_eq!(list.reserved_count(), 0);
+    assert_eq!(list.iter().count(), 2);
+  }
+
+  #[test]
+  fn failed_shrink_keeps_value() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..8 {
+      list.insert(i);
+    }
+    for i in (5..8).rev() {
+      list.remove(i);
+    }
+    assert_eq!(chain_order(&list), [8, 7, 6, 5]);
+    // Break a link among the slots that shrinking would trim
+    list.storage.set_prev_empty(6, Some(0)).unwrap();
+    list.set_shrink_policy(ShrinkPolicy::BelowPercent(90));
+    assert_eq!(list.try_remove(4), Err(SlotListError::ChainCorrupted));
+    list.storage.set_prev_empty(6, Some(7)).unwrap();
+
+    assert_eq!(list.check_invariants(), Ok(()));
+    assert_eq!(chain_order(&list), [8, 7, 6, 5]);
+    assert_eq!(list.slot_count(), 9);
+    assert_eq!(list.get(4), Some(&4));
+    assert_eq!(list.remove(4), Some(4));
+    assert_eq!(list.slot_count(), 5);
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  #[should_panic(expected = "empty slot chain was broken")]
+  fn corrupted_chain_panics() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    list.first_empty_slot = Link::new(Some(0));
+    list.insert(2);
+  }
+
+  #[test]
+  fn replacing_anywhere_in_chain() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..6 {
+      list.insert(i);
+    }
+    for i in 0..5 {
+      list.remove(i);
+    }
+    // The chain is now 6, 0, 1, 2, 3, 4
+    assert_eq!(list.get_first_empty_slot(), Some(6));
+    assert_eq!(list.get_last_empty_slot(), Some(4));
+    // Claim slots from the middle, the head, and the tail of the chain
+    list.replace(2, 20);
+    assert_chain_consistent(&list);
+    list.replace(6, 60);
+    assert_eq!(list.get_first_empty_slot(), Some(0));
+    assert_chain_consistent(&list);
+    list.replace(4, 40);
+    assert_eq!(list.get_last_empty_slot(), Some(3));
+    assert_chain_consistent(&list);
+    if let Slot::Empty { prev, next, .. } = list.get_raw_slot(1).unwrap() {
+      assert_eq!(prev.get(), Some(0));
+      assert_eq!(next.get(), Some(3));
+    } else {
+      panic!("Slot 1 was not empty");
+    }
+    assert_eq!(list.insert(7), 0);
+    assert_eq!(list.insert(8), 1);
+    assert_eq!(list.insert(9), 3);
+    assert_eq!(list.insert(10), 7);
+  }
+
+  #[test]
+  fn detecting_corruption() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..6 {
+      list.insert(i);
+    }
+    list.remove(1);
+    list.remove(3);
+    // The chain is now 6, 1, 3
+    assert_eq!(list.check_invariants(), Ok(()));
+
+    let mut broken = list.clone();
+    broken.storage.set_next_empty(1, Some(2)).unwrap();
+    assert_eq!(
+      broken.check_invariants(),
+      Err(InvariantViolation::OccupiedInChain { index: 2 }),
+    );
+
+    let mut broken = list.clone();
+    broken.storage.set_next_empty(3, Some(6)).unwrap();
+    assert_eq!(
+      broken.check_invariants(),
+      Err(InvariantViolation::ChainCycle { index: 6 }),
+    );
+
+    let mut broken = list.clone();
+    broken.storage.set_next_empty(3, Some(40)).unwrap();
+    assert_eq!(
+      broken.check_invariants(),
+      Err(InvariantViolation::ChainOutOfBounds { index: 40 }),
+    );
+
+    let mut broken = list.clone();
+    broken.last_empty_slot = Link::new(Some(1));
+    assert_eq!(
+      broken.check_invariants(),
+      Err(InvariantViolation::WrongTail { expected: Some(3), found: Some(1) }),
+    );
+
+    let mut broken = list.clone();
+    broken.storage.set_next_empty(1, None).unwrap();
+    broken.last_empty_slot = Link::new(Some(1));
+    assert_eq!(
+      broken.check_invariants(),
+      Err(InvariantViolation::UnreachableEmptySlot { index: 3 }),
+    );
+
+    let mut broken = list.clone();
+    broken.storage.set_prev_empty(3, Some(6)).unwrap();
+    assert_eq!(
+      broken.check_invariants(),
+      Err(InvariantViolation::BrokenBackLink {
+        index: 3,
+        expected: Some(1),
+        found: Some(6),
+      }),
+    );
+
+    let mut broken = list.clone();
+    broken.storage.occupancy.set(1);
+    assert_eq!(
+      broken.check_invariants(),
+      Err(InvariantViolation::OccupancyMismatch { index: 1 }),
+    );
+  }
+
+  #[test]
+  fn rebuilding_chain() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..6 {
+      list.insert(i);
+    }
+    list.remove(3);
+    list.remove(1);
+    list.storage.set_next_empty(3, Some(6)).unwrap();
+    list.storage.occupancy.set(1);
+    assert!(list.check_invariants().is_err());
+    list.rebuild_free_chain();
+    assert_eq!(list.check_invariants(), Ok(()));
+    // Empty slots are handed out in increasing order after a rebuild
+    assert_eq!(list.insert(10), 1);
+    assert_eq!(list.insert(11), 3);
+    assert_eq!(list.insert(12), 6);
+    assert_eq!(list.insert(13), 7);
+
+    // A LIFO chain is rebuilt in decreasing order
+    let mut list: SlotList<u32> =
+      SlotList::with_capacity_and_policy(8, AllocationPolicy::Lifo);
+    for i in 0..6 {
+      list.insert(i);
+    }
+    list.remove(1);
+    list.remove(3);
+    list.rebuild_free_chain();
+    assert_eq!(list.check_invariants(), Ok(()));
+    assert_eq!(list.get_first_empty_slot(), Some(6));
+    assert_eq!(list.get_last_empty_slot(), Some(1));
+    assert_eq!(list.remove(4), Some(4));
+    assert_eq!(list.insert(14), 4);
+  }
+
+  #[test]
+  fn iterators_with_indices() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..5 {
+      list.insert(i * 10);
+    }
+    list.remove(1);
+    list.remove(3);
+    let entries: Vec<(usize, &u32)> = list.iter_indexed().collect();
+    assert_eq!(entries, [(0, &0), (2, &20), (4, &40)]);
+    let reversed: Vec<usize> = list.keys().rev().collect();
+    assert_eq!(reversed, [4, 2, 0]);
+    assert_eq!(list.iter().len(), 3);
+    let mut iter = list.values();
+    assert_eq!(iter.next(), Some(&0));
+    assert_eq!(iter.next_back(), Some(&40));
+    assert_eq!(iter.len(), 1);
+    assert_eq!(iter.next(), Some(&20));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+
+    for (index, value) in list.iter_indexed_mut() {
+      *value += index as u32;
+    }
+    for value in list.values_mut() {
+      *value += 1;
+    }
+    for (index, value) in &list {
+      assert_eq!(*value, index as u32 * 11 + 1);
+    }
+    for (_, value) in &mut list {
+      *value = 0;
+    }
+    let owned: Vec<(usize, u32)> = list.into_iter().rev().collect();
+    assert_eq!(owned, [(4, 0), (2, 0), (0, 0)]);
+  }
+
+  #[test]
+  fn draining() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..5 {
+      list.insert(i);
+    }
+    let key = list.key_of(2).unwrap();
+    list.remove(1);
+    let mut drain = list.drain();
+    assert_eq!(drain.len(), 4);
+    assert_eq!(drain.next(), Some((0, 0)));
+    assert_eq!(drain.next_back(), Some((4, 4)));
+    drop(drain);
+    assert_eq!(list.iter().len(), 0);
+    assert_eq!(list.get(3), None);
+    assert!(!list.contains_key(key));
+    assert_eq!(list.check_invariants(), Ok(()));
+    // The spare slot was already at the front of the chain, followed by the
+    // slots in the order they were emptied
+    assert_eq!(list.insert(10), 5);
+    assert_eq!(list.insert(11), 1);
+    assert_eq!(list.insert(12), 0);
+    assert_eq!(list.insert(13), 4);
+  }
+
+  #[test]
+  fn draining_shrinks_once_finished() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      list.set_shrink_policy(ShrinkPolicy::BelowPercent(50));
+      for i in 0..100 {
+        list.insert(i);
+      }
+      let mut drain = list.drain();
+      assert_eq!(drain.next_back(), Some((99, 99)));
+      assert_eq!(drain.next(), Some((0, 0)));
+      assert_eq!(drain.len(), 98);
+      drop(drain);
+      assert_eq!(list.len(), 0);
+      assert_eq!(list.slot_count(), 0);
+      assert_eq!(list.capacity(), 0);
+      assert_eq!(list.check_invariants(), Ok(()));
+    }
+  }
+
+  #[test]
+  fn counting_slots() {
+    let mut list: SlotList<u32> = SlotList::new();
+    assert!(list.is_empty());
+    assert_eq!(list.slot_count(), 0);
+    assert_eq!(list.vacant_count(), 0);
+    list.insert(1);
+    list.insert(2);
+    assert_eq!(list.len(), 2);
+    assert!(!list.is_empty());
+    // Two occupied slots, plus the spare
+    assert_eq!(list.slot_count(), 3);
+    assert_eq!(list.vacant_count(), 1);
+    list.remove(0);
+    assert_eq!(list.len(), 1);
+    assert_eq!(list.vacant_count(), 2);
+    list.replace(0, 3);
+    list.replace(0, 4);
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.vacant_count(), 1);
+    list.insert_at(5, 5);
+    assert_eq!(list.len(), 3);
+    assert_eq!(list.slot_count(), 6);
+    assert_eq!(list.vacant_count(), 3);
+    list.drain();
+    assert_eq!(list.len(), 0);
+    assert_eq!(list.vacant_count(), 6);
+  }
+
+  #[derive(Debug, PartialEq)]
+  struct Node {
+    id: usize,
+    name: &'static str,
+  }
+
+  #[test]
+  fn vacant_entries() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<Node> = SlotList::with_policy(*policy);
+      let entry = list.vacant_entry();
+      let id = entry.index();
+      let key = entry.key();
+      entry.insert(Node { id, name: "first" });
+      assert_eq!(list.get(id).unwrap().id, id);
+      assert_eq!(list.get_by_key(key).unwrap().name, "first");
+
+      let id = list.insert_with(|id| Node { id, name: "second" });
+      assert_eq!(list.get(id), Some(&Node { id, name: "second" }));
+
+      list.remove(0);
+      let entry = list.vacant_entry();
+      let key = entry.key();
+      let value = entry.insert(Node { id: key.index(), name: "third" });
+      value.name = "renamed";
+      assert_eq!(list.get_by_key(key).unwrap().name, "renamed");
+      assert_eq!(list.check_invariants(), Ok(()));
+    }
+  }
+
+  #[test]
+  fn dropping_vacant_entry() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      for i in 0..5 {
+        list.insert(i);
+      }
+      list.remove(3);
+      list.remove(1);
+      let mut untouched = list.clone();
+      let index = list.vacant_entry().index();
+      assert_eq!(list.check_invariants(), Ok(()));
+      assert_eq!(list.get_first_empty_slot(), untouched.get_first_empty_slot());
+      assert_eq!(list.get_last_empty_slot(), untouched.get_last_empty_slot());
+      // Every later insertion matches a list that never made an entry
+      assert_eq!(list.insert(10), index);
+      assert_eq!(untouched.insert(10), index);
+      for i in 0..5 {
+        assert_eq!(list.insert(i), untouched.insert(i));
+      }
+    }
+  }
+
+  #[test]
+  fn vacant_entry_at_limit() {
+    let mut list: SlotList<u32> = SlotList::with_limit(1);
+    list.insert(1);
+    let err = list.try_vacant_entry().unwrap_err();
+    assert_eq!(err, SlotListError::CapacityExceeded);
+  }
+
+  #[test]
+  fn vacant_entry_with_broken_chain() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..4 {
+      list.insert(i);
+    }
+    list.remove(1);
+    list.remove(2);
+    list.storage.set_prev_empty(1, Some(0)).unwrap();
+    let err = list.try_vacant_entry().unwrap_err();
+    assert_eq!(err, SlotListError::ChainCorrupted);
+    // Once the chain is repaired, the entry fills the slot it was created for
+    list.rebuild_free_chain();
+    let entry = list.vacant_entry();
+    assert_eq!(entry.index(), 1);
+    entry.insert(10);
+    assert_eq!(list.get(1), Some(&10));
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn reserving_slots() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      list.insert(1);
+      let reservation = list.reserve();
+      let index = reservation.index();
+      assert_eq!(index, 1);
+      assert_eq!(list.get(index), None);
+      assert_eq!(list.key_of(index), None);
+      assert_eq!(list.len(), 1);
+      assert_eq!(list.reserved_count(), 1);
+      assert_eq!(list.vacant_count(), 1);
+      assert_eq!(list.check_invariants(), Ok(()));
+
+      // Other insertions carry on around the reserved slot
+      assert_eq!(list.insert(3), 2);
+      assert_eq!(list.iter_indexed().collect::<Vec<_>>(), [(0, &1), (2, &3)]);
+
+      assert_eq!(list.commit(reservation, 2), index);
+      assert_eq!(list.get(index), Some(&2));
+      assert_eq!(list.len(), 3);
+      assert_eq!(list.reserved_count(), 0);
+      assert_eq!(list.check_invariants(), Ok(()));
+    }
+  }
+
+  #[test]
+  fn cancelling_reservations() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      list.insert(1);
+      list.insert(2);
+      let reservation = list.reserve();
+      assert_eq!(reservation.index(), 2);
+      list.cancel(reservation);
+      assert_eq!(list.reserved_count(), 0);
+      assert_eq!(list.vacant_count(), 2);
+      assert_eq!(list.check_invariants(), Ok(()));
+      // The cancelled slot is available again, in whichever order the policy
+      // would have re-used a removed slot
+      let expected = match policy {
+        AllocationPolicy::Lifo | AllocationPolicy::LowestIndex => 2,
+        AllocationPolicy::Fifo | AllocationPolicy::Cyclic => 3,
+      };
+      assert_eq!(list.insert(3), expected);
+    }
+
+    // Under FIFO, a cancelled slot goes to the back of the chain, like a
+    // removed one
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..4 {
+      list.insert(i);
+    }
+    list.remove(1);
+    let reservation = list.reserve();
+    assert_eq!(reservation.index(), 4);
+    list.cancel(reservation);
+    assert_chain_consistent(&list);
+    assert_eq!(list.get_first_empty_slot(), Some(1));
+    assert_eq!(list.get_last_empty_slot(), Some(4));
+  }
+
+  #[test]
+  fn reserved_slots_reject_values() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    let reservation = list.reserve();
+    let index = reservation.index();
+    assert_eq!(list.remove(index), None);
+    assert_eq!(list.try_remove(index), Err(SlotListError::Reserved));
+    let err = list.try_replace(index, 5).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::Reserved);
+    let err = list.try_insert_at(index, 5).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::Reserved);
+    assert_eq!(list.drain().count(), 1);
+    assert_eq!(list.reserved_count(), 1);
+    assert_eq!(list.check_invariants(), Ok(()));
+    list.commit(reservation, 2);
+    assert_eq!(list.values().collect::<Vec<_>>(), [&2]);
+  }
+
+  #[test]
+  fn unknown_reservations() {
+    let mut list: SlotList<u32> = SlotList::new();
+    let mut other: SlotList<u32> = SlotList::new();
+    other.insert(1);
+    let reservation = other.reserve();
+    let err = list.try_commit(reservation, 5).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::UnknownReservation);
+    assert_eq!(err.into_inner(), 5);
+    let reservation = other.reserve();
+    let result = list.try_cancel(reservation);
+    assert_eq!(result, Err(SlotListError::UnknownReservation));
+    assert_eq!(other.reserved_count(), 2);
+  }
+
+  #[test]
+  fn reservations_from_other_lists() {
+    // Both lists reserve slot 0 with the same generation
+    let mut a: SlotList<u32> = SlotList::new();
+    let mut b: SlotList<u32> = SlotList::new();
+    let from_a = a.reserve();
+    let from_b = b.reserve();
+    assert_eq!(from_a.index(), from_b.index());
+    let err = b.try_commit(from_a, 7).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::UnknownReservation);
+    let from_a = a.reserve();
+    assert_eq!(b.try_cancel(from_a), Err(SlotListError::UnknownReservation));
+    assert_eq!(b.reserved_count(), 1);
+    assert_eq!(b.commit(from_b, 8), 0);
+    assert_eq!(b.get(0), Some(&8));
+    assert_eq!(a.reserved_count(), 2);
+    assert_eq!(a.get(0), None);
+
+    // A clone's copy of a reserved slot doesn't belong to the Reservation
+    let from_a = a.reserve();
+    let mut clone = a.clone();
+    let err = clone.try_commit(from_a, 9).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::UnknownReservation);
+    assert_eq!(clone.reserved_count(), 3);
+  }
+
+  #[test]
+  fn reservations_at_limit() {
+    let mut list: SlotList<u32> = SlotList::with_limit(2);
+    let first = list.reserve();
+    let second = list.reserve();
+    assert_eq!(list.try_reserve_slot(), Err(SlotListError::CapacityExceeded));
+    let err = list.try_insert(1).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::CapacityExceeded);
+    list.cancel(first);
+    assert_eq!(list.insert(1), 0);
+    list.commit(second, 2);
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn rebuilding_chain_keeps_reservations() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    let reservation = list.reserve();
+    list.remove(0);
+    list.rebuild_free_chain();
+    assert_eq!(list.reserved_count(), 1);
+    assert_eq!(list.check_invariants(), Ok(()));
+    list.commit(reservation, 2);
+    assert_eq!(list.get(1), Some(&2));
+  }
+
+  #[test]
+  fn compacting() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      for i in 0..100 {
+        list.insert(i);
+      }
+      for i in 0..95 {
+        if i % 10 != 0 {
+          list.remove(i);
+        }
+      }
+      let key = list.key_of(97).unwrap();
+      let remap = list.compact();
+      assert_eq!(remap, [
+        (20, 14), (30, 13), (40, 12), (50, 11), (60, 9), (70, 8), (80, 7),
+        (90, 6), (95, 5), (96, 4), (97, 3), (98, 2), (99, 1),
+      ]);
+      assert_eq!(list.len(), 15);
+      // The values, plus the spare
+      assert_eq!(list.slot_count(), 16);
+      assert!(list.capacity() < 100);
+      for (old, new) in remap {
+        assert_eq!(list.get(new), Some(&(old as u32)));
+      }
+      assert!(!list.contains_key(key));
+      assert_eq!(list.check_invariants(), Ok(()));
+      if policy.uses_empty_chain() {
+        assert_chain_consistent(&list);
+      }
+      assert_eq!(list.insert(100), 15);
+    }
+  }
+
+  #[test]
+  fn compacting_around_reservations() {
+    let mut list: SlotList<u32> = SlotList::new();
+    for i in 0..4 {
+      list.insert(i);
+    }
+    let reservation = list.reserve();
+    list.insert(5);
+    list.remove(0);
+    list.remove(1);
+    // The reserved slot stays where it is, and values move past it
+    assert_eq!(list.compact(), [(3, 1), (5, 0)]);
+    assert_eq!(reservation.index(), 4);
+    assert_eq!(list.check_invariants(), Ok(()));
+    list.commit(reservation, 4);
+    let values: Vec<_> = list.iter_indexed().collect();
+    assert_eq!(values, [(0, &5), (1, &3), (2, &2), (4, &4)]);
+    assert_eq!(list.try_compact(), Ok(Vec::from([(4, 3)])));
+    assert_eq!(list.try_compact(), Ok(Vec::new()));
+  }
+
+  #[test]
+  fn shrinking_to_fit() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      for i in 0..100 {
+        list.insert(i);
+      }
+      for i in 3..100 {
+        list.remove(i);
+      }
+      list.remove(1);
+      list.shrink_to_fit();
+      assert_eq!(list.slot_count(), 4);
+      assert_eq!(list.vacant_count(), 2);
+      assert!(list.capacity() < 100);
+      assert_eq!(list.get(2), Some(&2));
+      assert_eq!(list.check_invariants(), Ok(()));
+      if policy.uses_empty_chain() {
+        assert_chain_consistent(&list);
+      }
+
+      for i in 0..2 {
+        list.remove(i * 2);
+      }
+      list.shrink_to_fit();
+      assert_eq!(list.slot_count(), 0);
+      assert_eq!(list.check_invariants(), Ok(()));
+      assert_eq!(list.insert(5), 0);
+    }
+  }
+
+  #[test]
+  fn trimmed_slots_keep_keys_stale() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    let key = list.insert_keyed(2);
+    list.remove_by_key(key);
+    list.shrink_to_fit();
+    assert_eq!(list.slot_count(), 2);
+    list.remove(0);
+    list.shrink_to_fit();
+    assert_eq!(list.slot_count(), 0);
+    // Slot 1 is re-created, but it must not match the old key
+    list.insert(3);
+    let new_key = list.insert_keyed(4);
+    assert_eq!(new_key.index(), key.index());
+    assert!(!list.contains_key(key));
+    assert_eq!(list.get_by_key(new_key), Some(&4));
+  }
+
+  #[test]
+  fn shrinking_automatically() {
+    for policy in POLICIES.iter() {
+      let mut list: SlotList<u32> = SlotList::with_policy(*policy);
+      list.set_shrink_policy(ShrinkPolicy::BelowPercent(25));
+      for i in 0..1000 {
+        list.insert(i);
+      }
+      for i in (10..1000).rev() {
+        list.remove(i);
+      }
+      // Ten values must occupy at least a quarter of the allocation
+      assert!(list.capacity() <= 40);
+      assert_eq!(list.check_invariants(), Ok(()));
+      if policy.uses_empty_chain() {
+        assert_chain_consistent(&list);
+      }
+      for i in 0..10 {
+        assert_eq!(list.get(i), Some(&(i as u32)));
+      }
+    }
+  }
+
+  #[test]
+  fn shrinking_never_moves_values() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.set_shrink_policy(ShrinkPolicy::BelowPercent(50));
+    for i in 0..100 {
+      list.insert(i);
+    }
+    for i in 0..99 {
+      list.remove(i);
+    }
+    // The last value holds every slot below it in place
+    assert_eq!(list.slot_count(), 101);
+    assert_eq!(list.get(99), Some(&99));
+    list.remove(99);
+    assert_eq!(list.slot_count(), 0);
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn shrinking_is_opt_in() {
+    let mut list: SlotList<u32> = SlotList::new();
+    assert_eq!(list.shrink_policy(), ShrinkPolicy::Never);
+    for i in 0..100 {
+      list.insert(i);
+    }
+    for i in 0..100 {
+      list.remove(i);
+    }
+    assert_eq!(list.slot_count(), 101);
+  }
+
+  #[test]
+  fn narrow_index_types() {
+    let mut list: SlotList<u32, u16> = SlotList::with_index();
+    for _ in 0..u16::MAX {
+      list.insert(1);
+    }
+    // Every index below u16::MAX is in use, and u16::MAX marks the end of the
+    // empty chain
+    assert_eq!(list.slot_count(), 65535);
+    let err = list.try_insert(2).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::IndexOverflow);
+    let err = list.try_insert_at(65535, 2).unwrap_err();
+    assert_eq!(err.kind(), SlotListError::IndexOverflow);
+    let err = list.try_vacant_entry().unwrap_err();
+    assert_eq!(err, SlotListError::IndexOverflow);
+    assert_eq!(list.check_invariants(), Ok(()));
+
+    list.remove(65534);
+    list.remove(3);
+    assert_eq!(list.insert(2), 65534);
+    assert_eq!(list.insert(3), 3);
+    assert_eq!(list.check_invariants(), Ok(()));
+    assert_chain_consistent(&list);
+  }
+
+  #[test]
+  fn narrow_slots() {
+    use core::mem::size_of;
+    // A generation plus two links, and the enum tag. `PackedStorage` without
+    // generations brings a slot down to 8 bytes.
+    assert!(size_of::<Slot<u32, u32>>() <= 16);
+    assert!(size_of::<Slot<u32, u16>>() <= 12);
+    assert!(size_of::<Slot<u32, u32>>() < size_of::<Slot<u32>>());
+    assert_eq!(size_of::<Link<u32>>(), 4);
+  }
+
+  #[test]
+  fn finding_occupied_and_vacant_slots() {
+    let mut list: SlotList<u32> =
+      SlotList::with_policy(AllocationPolicy::LowestIndex);
+    for i in 0..10_000 {
+      list.insert(i);
+    }
+    for i in 0..10_000 {
+      if i != 3 && i != 5000 && i != 9999 {
+        list.remove(i as usize);
+      }
+    }
+    let reservation = list.reserve();
+    assert_eq!(reservation.index(), 0);
+    assert_eq!(list.find_next_occupied(0), Some(3));
+    assert_eq!(list.find_next_occupied(3), Some(3));
+    assert_eq!(list.find_next_occupied(4), Some(5000));
+    assert_eq!(list.find_next_occupied(5001), Some(9999));
+    assert_eq!(list.find_next_occupied(10_000), None);
+    // The reserved slot is neither occupied nor vacant
+    assert_eq!(list.find_next_vacant(0), Some(1));
+    assert_eq!(list.find_next_vacant(3), Some(4));
+    assert_eq!(list.find_next_vacant(9999), Some(10_000));
+    assert_eq!(list.find_next_vacant(list.slot_count()), None);
+    list.commit(reservation, 0);
+    assert_eq!(list.find_next_occupied(0), Some(0));
+  }
+
+  /// Iterate over a list where only a few scattered slots are in use, and
+  /// compare the results against a plain scan of every slot
+  fn iterate_sparse_list<S: Storage<u32>>(mut list: SlotList<u32, usize, S>) {
+    let slots = if cfg!(miri) { 300 } else { 20_000 };
+    for i in 0..slots {
+      list.insert(i as u32);
+    }
+    for i in 0..slots {
+      if i % 97 != 0 && i % 1000 != 999 {
+        list.remove(i);
+      }
+    }
+    let reservation = list.reserve();
+    let expected: Vec<usize> = (0..list.slot_count())
+      .filter(|i| list.get(*i).is_some())
+      .collect();
+    let forward: Vec<usize> = list.keys().collect();
+    assert_eq!(forward, expected);
+    let mut backward: Vec<usize> = list.keys().rev().collect();
+    backward.reverse();
+    assert_eq!(backward, expected);
+
+    let mut iter = list.iter_indexed_mut();
+    let (first, value) = iter.next().unwrap();
+    *value += 1;
+    let (last, value) = iter.next_back().unwrap();
+    *value += 1;
+    assert_eq!((first, last), (expected[0], expected[expected.len() - 1]));
+    for (index, value) in iter {
+      *value = index as u32 + 1;
+    }
+    let expected = |(index, value): (usize, &u32)| *value == index as u32 + 1;
+    assert!(list.iter_indexed().all(expected));
+    list.cancel(reservation);
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+
+  #[test]
+  fn iterating_sparse_lists() {
+    iterate_sparse_list(SlotList::<u32>::new());
+    iterate_sparse_list(SlotList::with_storage(PackedStorage::<u32>::new()));
+  }
 }
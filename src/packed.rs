@@ -0,0 +1,726 @@
+#[cfg(feature = "std")]
+use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use core::iter::FusedIterator;
+use core::mem::ManuallyDrop;
+
+use crate::bitmap::{OccupancyBitmap, SetBits};
+use crate::error::{InvariantViolation, SlotListError};
+use crate::index::{Link, SlotIndex};
+use crate::list::Slot;
+use crate::storage::{sealed, SliceCursor, Storage};
+
+/// The links of an empty slot in the chain
+#[derive(Copy, Clone)]
+struct Links<I> {
+  prev: Link<I>,
+  next: Link<I>,
+}
+
+impl<I: SlotIndex> Links<I> {
+  const NONE: Links<I> = Links { prev: Link::NONE, next: Link::NONE };
+}
+
+/// The contents of a packed slot: its value if it is occupied, or its links
+/// if it is not. Nothing in the union says which, so every read depends on the
+/// occupancy bitmaps kept alongside it.
+union Contents<T, I: SlotIndex> {
+  value: ManuallyDrop<T>,
+  links: Links<I>,
+}
+
+/// Heap-allocated storage without a per-slot discriminant. A `Slot` enum needs
+/// a tag, and padding to align the value after it; here, each value shares its
+/// space with the links used while the slot is empty, generations are kept in
+/// a separate array, and the state of each slot is recorded in a pair of
+/// bitmaps. Looking up a value is a bit test followed by a load.
+/// This suits large tables of small values, where the tag and padding would
+/// be a significant part of every slot.
+/// Setting `GENERATIONS` to false drops the generation array as well, so that
+/// a slot takes only the size of its value or its links: 8 bytes for `u32`
+/// values linked by `u32` indices, plus two bits in the bitmaps. Every slot
+/// then stays at generation 0, so a Key can't tell a re-used slot from the one
+/// it was made for.
+pub struct PackedStorage<
+  T,
+  I: SlotIndex = usize,
+  const GENERATIONS: bool = true,
+> {
+  contents: Vec<Contents<T, I>>,
+  /// Empty unless `GENERATIONS` is set
+  generations: Vec<u32>,
+  /// Slots that are occupied or reserved
+  used: OccupancyBitmap,
+  /// The subset of used slots that are reserved, which hold links rather than
+  /// a value
+  reserved: OccupancyBitmap,
+}
+
+impl<T, I: SlotIndex> PackedStorage<T, I> {
+  pub const fn new() -> PackedStorage<T, I> {
+    PackedStorage::empty()
+  }
+
+  pub fn with_capacity(capacity: usize) -> PackedStorage<T, I> {
+    PackedStorage::allocate(capacity)
+  }
+}
+
+impl<T, I: SlotIndex> PackedStorage<T, I, false> {
+  /// Create storage that doesn't keep a generation for each slot
+  pub const fn without_generations() -> PackedStorage<T, I, false> {
+    PackedStorage::empty()
+  }
+}
+
+impl<T, I: SlotIndex, const G: bool> PackedStorage<T, I, G> {
+  const fn empty() -> PackedStorage<T, I, G> {
+    PackedStorage {
+      contents: Vec::new(),
+      generations: Vec::new(),
+      used: OccupancyBitmap::new(),
+      reserved: OccupancyBitmap::new(),
+    }
+  }
+
+  fn allocate(capacity: usize) -> PackedStorage<T, I, G> {
+    let generations = if G { capacity } else { 0 };
+    PackedStorage {
+      contents: Vec::with_capacity(capacity),
+      generations: Vec::with_capacity(generations),
+      used: OccupancyBitmap::with_capacity(capacity),
+      reserved: OccupancyBitmap::with_capacity(capacity),
+    }
+  }
+
+  /// The generation of a slot, which is always 0 if generations aren't kept
+  fn generation(&self, index: usize) -> u32 {
+    if G {
+      self.generations[index]
+    } else {
+      0
+    }
+  }
+
+  fn is_occupied(&self, index: usize) -> bool {
+    index < self.contents.len()
+      && self.used.is_set(index)
+      && !self.reserved.is_set(index)
+  }
+
+  /// Move the contents of a slot out, leaving it to be overwritten by `write`
+  fn read(&mut self, index: usize, generation: u32) -> Slot<T, I> {
+    if !self.used.is_set(index) {
+      // SAFETY: a vacant slot holds its links
+      let links = unsafe { self.contents[index].links };
+      Slot::Empty { generation, prev: links.prev, next: links.next }
+    } else if self.reserved.is_set(index) {
+      Slot::Reserved { generation }
+    } else {
+      // SAFETY: an occupied slot holds its value, which is overwritten by the
+      // caller before it can be read again
+      let contents = &mut self.contents[index];
+      let value = unsafe { ManuallyDrop::take(&mut contents.value) };
+      Slot::Occupied { generation, value }
+    }
+  }
+
+  /// Store new contents in a slot whose previous contents have already been
+  /// moved out, and record its state
+  fn write(&mut self, index: usize, slot: Slot<T, I>) {
+    match slot {
+      Slot::Occupied { value, .. } => {
+        self.contents[index] = Contents { value: ManuallyDrop::new(value) };
+        self.used.set(index);
+        self.reserved.clear(index);
+      },
+      Slot::Empty { prev, next, .. } => {
+        self.contents[index] = Contents { links: Links { prev, next } };
+        self.used.clear(index);
+        self.reserved.clear(index);
+      },
+      Slot::Reserved { .. } => {
+        self.contents[index] = Contents { links: Links::NONE };
+        self.used.set(index);
+        self.reserved.set(index);
+      },
+    }
+  }
+
+  /// Look up the links of an empty slot, so that they can be changed
+  fn links_mut(
+    &mut self,
+    index: usize,
+  ) -> Result<&mut Links<I>, SlotListError> {
+    if index >= self.contents.len() {
+      Err(SlotListError::OutOfBounds)
+    } else if self.reserved.is_set(index) {
+      Err(SlotListError::Reserved)
+    } else if self.used.is_set(index) {
+      Err(SlotListError::Occupied)
+    } else {
+      // SAFETY: a vacant slot holds its links
+      Ok(unsafe { &mut self.contents[index].links })
+    }
+  }
+}
+
+impl<T, I: SlotIndex, const G: bool> Default for PackedStorage<T, I, G> {
+  fn default() -> PackedStorage<T, I, G> {
+    PackedStorage::empty()
+  }
+}
+
+impl<T, I: SlotIndex, const G: bool> Drop for PackedStorage<T, I, G> {
+  fn drop(&mut self) {
+    for index in 0..self.contents.len() {
+      if self.is_occupied(index) {
+        // SAFETY: an occupied slot holds its value, and the storage is never
+        // used again
+        unsafe { ManuallyDrop::drop(&mut self.contents[index].value) };
+      }
+    }
+  }
+}
+
+impl<T, I, const G: bool> Clone for PackedStorage<T, I, G>
+where
+  T: Clone,
+  I: SlotIndex,
+{
+  fn clone(&self) -> Self {
+    // Slots are copied one at a time, so that if cloning a value panics, the
+    // values cloned so far are dropped
+    let mut storage = PackedStorage::allocate(self.contents.len());
+    for index in 0..self.contents.len() {
+      storage.push(self.get(index).unwrap().cloned());
+    }
+    storage
+  }
+}
+
+impl<T, I, const G: bool> core::fmt::Debug for PackedStorage<T, I, G>
+where
+  T: core::fmt::Debug,
+  I: SlotIndex,
+{
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    formatter.debug_list()
+      .entries((0..self.contents.len()).map(|index| self.get(index).unwrap()))
+      .finish()
+  }
+}
+
+impl<T, I, const G: bool> sealed::Sealed for PackedStorage<T, I, G>
+where
+  I: SlotIndex,
+{
+}
+
+impl<T, I: SlotIndex, const G: bool> Storage<T, I> for PackedStorage<T, I, G> {
+  type Iter<'a> = PackedIter<'a, T, I> where T: 'a, I: 'a;
+  type IterMut<'a> = PackedIterMut<'a, T, I> where T: 'a, I: 'a;
+  type IntoIter = PackedIntoIter<T, I, G>;
+
+  fn slot_count(&self) -> usize {
+    self.contents.len()
+  }
+
+  fn get(&self, index: usize) -> Option<Slot<&T, I>> {
+    if index >= self.contents.len() {
+      return None;
+    }
+    let generation = self.generation(index);
+    if !self.used.is_set(index) {
+      // SAFETY: a vacant slot holds its links
+      let links = unsafe { self.contents[index].links };
+      Some(Slot::Empty { generation, prev: links.prev, next: links.next })
+    } else if self.reserved.is_set(index) {
+      Some(Slot::Reserved { generation })
+    } else {
+      // SAFETY: an occupied slot holds its value
+      let value = unsafe { &*self.contents[index].value };
+      Some(Slot::Occupied { generation, value })
+    }
+  }
+
+  fn get_value(&self, index: usize) -> Option<&T> {
+    if self.is_occupied(index) {
+      // SAFETY: an occupied slot holds its value
+      Some(unsafe { &*self.contents[index].value })
+    } else {
+      None
+    }
+  }
+
+  fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    if self.is_occupied(index) {
+      // SAFETY: an occupied slot holds its value
+      Some(unsafe { &mut *self.contents[index].value })
+    } else {
+      None
+    }
+  }
+
+  fn replace(&mut self, index: usize, slot: Slot<T, I>) -> Slot<T, I> {
+    let generation = if G {
+      core::mem::replace(&mut self.generations[index], slot.generation())
+    } else {
+      0
+    };
+    let prev = self.read(index, generation);
+    self.write(index, slot);
+    prev
+  }
+
+  fn set_next_empty(
+    &mut self,
+    index: usize,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.links_mut(index)?.next = Link::new(next);
+    Ok(())
+  }
+
+  fn set_prev_empty(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.links_mut(index)?.prev = Link::new(prev);
+    Ok(())
+  }
+
+  fn push(&mut self, slot: Slot<T, I>) {
+    // The new slot starts out vacant, since bits past the end are always clear
+    self.contents.push(Contents { links: Links::NONE });
+    if G {
+      self.generations.push(slot.generation());
+    }
+    self.write(self.contents.len() - 1, slot);
+  }
+
+  fn pop(&mut self) -> Option<Slot<T, I>> {
+    let index = self.contents.len().checked_sub(1)?;
+    let slot = self.read(index, self.generation(index));
+    self.used.clear(index);
+    self.reserved.clear(index);
+    self.contents.pop();
+    self.generations.pop();
+    Some(slot)
+  }
+
+  fn reserve(&mut self, additional: usize) -> Result<(), SlotListError> {
+    let slots = self.contents.len().saturating_add(additional);
+    self.contents
+      .try_reserve(additional)
+      .map_err(|_| SlotListError::AllocFailed)?;
+    if G {
+      self.generations
+        .try_reserve(additional)
+        .map_err(|_| SlotListError::AllocFailed)?;
+    }
+    self.used.try_reserve(slots).map_err(|_| SlotListError::AllocFailed)?;
+    self.reserved.try_reserve(slots).map_err(|_| SlotListError::AllocFailed)
+  }
+
+  fn capacity(&self) -> usize {
+    self.contents.capacity()
+  }
+
+  fn shrink_to_fit(&mut self) {
+    self.contents.shrink_to_fit();
+    self.generations.shrink_to_fit();
+    self.used.truncate(self.contents.len());
+    self.reserved.truncate(self.contents.len());
+  }
+
+  fn rebuild_occupancy(&mut self) {
+    // The bitmaps are the only record of what each slot holds, so only their
+    // summaries, and any bits past the end, can be repaired
+    self.used.rebuild_summaries(self.contents.len());
+    self.reserved.rebuild_summaries(self.contents.len());
+  }
+
+  fn check_occupancy(
+    &self,
+    occupied: usize,
+    reserved: usize,
+  ) -> Result<(), InvariantViolation> {
+    let slots = self.contents.len();
+    let inconsistent = self.used
+      .first_inconsistency(slots)
+      .or_else(|| self.reserved.first_inconsistency(slots))
+      .or_else(|| {
+        // Every reserved slot must also be marked as used
+        let mut reserved = self.reserved.set_bits(slots);
+        reserved.find(|index| !self.used.is_set(*index))
+      });
+    if let Some(index) = inconsistent {
+      return Err(InvariantViolation::OccupancyMismatch { index });
+    }
+    let found_reserved = self.reserved.count_set();
+    let found_occupied = self.used.count_set() - found_reserved;
+    if found_occupied != occupied {
+      return Err(InvariantViolation::WrongLength {
+        expected: found_occupied,
+        found: occupied,
+      });
+    }
+    if found_reserved != reserved {
+      return Err(InvariantViolation::WrongReservedCount {
+        expected: found_reserved,
+        found: reserved,
+      });
+    }
+    Ok(())
+  }
+
+  fn is_marked_used(&self, index: usize) -> bool {
+    self.used.is_set(index)
+  }
+
+  fn next_vacant(&self, from: usize, limit: usize) -> Option<usize> {
+    self.used.next_clear(from, limit)
+  }
+
+  fn next_used(&self, from: usize, limit: usize) -> Option<usize> {
+    self.used.next_set(from, core::cmp::min(limit, self.contents.len()))
+  }
+
+  fn first_vacant(&self, limit: usize) -> Option<usize> {
+    self.used.first_clear(limit)
+  }
+
+  fn iter(&self) -> PackedIter<'_, T, I> {
+    PackedIter {
+      contents: &self.contents,
+      used: self.used.set_bits(self.contents.len()),
+      reserved: &self.reserved,
+    }
+  }
+
+  fn iter_mut(&mut self) -> PackedIterMut<'_, T, I> {
+    PackedIterMut {
+      used: self.used.set_bits(self.contents.len()),
+      contents: SliceCursor::new(&mut self.contents),
+      reserved: &self.reserved,
+    }
+  }
+
+  fn into_iter(self) -> PackedIntoIter<T, I, G> {
+    let back = self.contents.len();
+    PackedIntoIter { storage: self, front: 0, back }
+  }
+}
+
+/// Iterator over the occupied slots of a PackedStorage, which visits only the
+/// slots marked as used
+pub struct PackedIter<'a, T, I: SlotIndex> {
+  contents: &'a [Contents<T, I>],
+  used: SetBits<'a>,
+  reserved: &'a OccupancyBitmap,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for PackedIter<'a, T, I> {
+  type Item = (usize, &'a T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let reserved = self.reserved;
+    let index = self.used.by_ref().find(|index| !reserved.is_set(*index))?;
+    // SAFETY: a slot that is used but not reserved holds its value
+    Some((index, unsafe { &*self.contents[index].value }))
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for PackedIter<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let reserved = self.reserved;
+    let index = self.used
+      .by_ref()
+      .rev()
+      .find(|index| !reserved.is_set(*index))?;
+    // SAFETY: a slot that is used but not reserved holds its value
+    Some((index, unsafe { &*self.contents[index].value }))
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for PackedIter<'a, T, I> {}
+
+impl<'a, T, I: SlotIndex> Clone for PackedIter<'a, T, I> {
+  fn clone(&self) -> Self {
+    PackedIter {
+      contents: self.contents,
+      used: self.used.clone(),
+      reserved: self.reserved,
+    }
+  }
+}
+
+/// Iterator over the occupied slots of a PackedStorage, yielding mutable
+/// references
+pub struct PackedIterMut<'a, T, I: SlotIndex> {
+  contents: SliceCursor<'a, Contents<T, I>>,
+  used: SetBits<'a>,
+  reserved: &'a OccupancyBitmap,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for PackedIterMut<'a, T, I> {
+  type Item = (usize, &'a mut T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let reserved = self.reserved;
+    let index = self.used.by_ref().find(|index| !reserved.is_set(*index))?;
+    // SAFETY: a slot that is used but not reserved holds its value
+    Some((index, unsafe { &mut *self.contents.take_front(index).value }))
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for PackedIterMut<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let reserved = self.reserved;
+    let index = self.used
+      .by_ref()
+      .rev()
+      .find(|index| !reserved.is_set(*index))?;
+    // SAFETY: a slot that is used but not reserved holds its value
+    Some((index, unsafe { &mut *self.contents.take_back(index).value }))
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for PackedIterMut<'a, T, I> {}
+
+/// Iterator that consumes a PackedStorage. Each value is moved out of its
+/// slot as it is yielded, and any left over are dropped with the storage.
+pub struct PackedIntoIter<T, I: SlotIndex, const GENERATIONS: bool = true> {
+  storage: PackedStorage<T, I, GENERATIONS>,
+  front: usize,
+  back: usize,
+}
+
+impl<T, I: SlotIndex, const G: bool> Iterator for PackedIntoIter<T, I, G> {
+  type Item = (usize, T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while let Some(index) = self.storage.used.next_set(self.front, self.back) {
+      self.front = index + 1;
+      if self.storage.is_occupied(index) {
+        return Some((index, self.storage.take(index).occupied().unwrap()));
+      }
+    }
+    self.front = self.back;
+    None
+  }
+}
+
+impl<T, I, const G: bool> DoubleEndedIterator for PackedIntoIter<T, I, G>
+where
+  I: SlotIndex,
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    while let Some(index) = self.storage.used.prev_set(self.front, self.back) {
+      self.back = index;
+      if self.storage.is_occupied(index) {
+        return Some((index, self.storage.take(index).occupied().unwrap()));
+      }
+    }
+    self.back = self.front;
+    None
+  }
+}
+
+impl<T, I, const G: bool> FusedIterator for PackedIntoIter<T, I, G>
+where
+  I: SlotIndex,
+{
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Contents, PackedStorage};
+  use crate::error::{InvariantViolation, SlotListError};
+  use crate::list::{Slot, SlotList};
+  use crate::policy::AllocationPolicy;
+  use crate::storage::Storage;
+  use core::cell::Cell;
+
+  /// Counts how many times values have been dropped
+  struct Counted<'a>(&'a Cell<usize>);
+
+  impl<'a> Drop for Counted<'a> {
+    fn drop(&mut self) {
+      self.0.set(self.0.get() + 1);
+    }
+  }
+
+  #[test]
+  fn packing_slots() {
+    use core::mem::size_of;
+    assert_eq!(size_of::<Contents<u64, u32>>(), 8);
+    assert_eq!(size_of::<Contents<u32, u16>>(), 4);
+    assert!(size_of::<Slot<u64, u32>>() >= 16);
+  }
+
+  #[test]
+  fn packing_slots_without_generations() {
+    use core::mem::size_of;
+    // A slot holds a u32 value or two u32 links, and nothing else
+    assert_eq!(size_of::<Contents<u32, u32>>(), 8);
+    let mut storage = PackedStorage::<u32, u32, false>::without_generations();
+    storage.push(Slot::Occupied { generation: 3, value: 1 });
+    storage.push(Slot::unlinked(5));
+    assert_eq!(storage.generations.capacity(), 0);
+    assert_eq!(storage.get(0).unwrap().generation(), 0);
+    assert_eq!(storage.get(1).unwrap().generation(), 0);
+    assert_eq!(storage.get_value(0), Some(&1));
+
+    let mut list = SlotList::with_storage(
+      PackedStorage::<u32, u32, false>::without_generations(),
+    );
+    for value in 0..100 {
+      list.insert(value);
+    }
+    let key = list.key_of(10).unwrap();
+    list.remove(10);
+    assert_eq!(list.check_invariants(), Ok(()));
+    // The slot stays at generation 0, so the old key matches its new value
+    list.try_insert_at(10, 500).unwrap();
+    assert_eq!(list.get_by_key(key), Some(&500));
+    assert_eq!(list.iter().count(), 100);
+  }
+
+  #[test]
+  fn matching_enum_slots() {
+    for policy in [AllocationPolicy::Fifo, AllocationPolicy::LowestIndex] {
+      let mut packed = SlotList::with_storage_policy_and_limit(
+        PackedStorage::<u64>::new(),
+        policy,
+        usize::MAX,
+      );
+      let mut plain: SlotList<u64> = SlotList::with_policy(policy);
+      let mut reservations = [None, None];
+      let mut seed: u32 = 0x1234_5678;
+      // Miri is far slower, but still covers every operation in fewer steps
+      let steps = if cfg!(miri) { 60 } else { 500 };
+      for step in 0..steps {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let target = (seed >> 4) as usize % (plain.slot_count() + 1);
+        match (seed >> 16) % 8 {
+          0..=2 => assert_eq!(packed.insert(step), plain.insert(step)),
+          3 | 4 => assert_eq!(packed.remove(target), plain.remove(target)),
+          5 => assert_eq!(
+            packed.try_insert_at(target + 1, step).ok(),
+            plain.try_insert_at(target + 1, step).ok(),
+          ),
+          6 => {
+            let slot = target % 2;
+            match reservations[slot].take() {
+              Some((a, b)) if step % 2 == 0 => {
+                assert_eq!(packed.commit(a, step), plain.commit(b, step));
+              },
+              Some((a, b)) => {
+                packed.cancel(a);
+                plain.cancel(b);
+              },
+              None => {
+                reservations[slot] = Some((packed.reserve(), plain.reserve()));
+              },
+            }
+          },
+          _ => {
+            if let Some(value) = packed.get_mut(target) {
+              *value += 1;
+            }
+            if let Some(value) = plain.get_mut(target) {
+              *value += 1;
+            }
+          },
+        }
+        assert_eq!(packed.check_invariants(), Ok(()));
+        assert_eq!(packed.slot_count(), plain.slot_count());
+        for index in 0..plain.slot_count() {
+          assert_eq!(packed.key_of(index), plain.key_of(index));
+        }
+        assert!(packed.iter().eq(plain.iter()));
+        assert!(packed.iter().rev().eq(plain.iter().rev()));
+      }
+      assert_eq!(packed.compact(), plain.compact());
+      assert!(packed.into_iter().eq(plain.into_iter()));
+    }
+  }
+
+  #[test]
+  fn checking_bitmaps() {
+    let mut list = SlotList::with_storage(PackedStorage::<u64>::new());
+    for value in 0..100 {
+      list.insert(value);
+    }
+    list.remove(70);
+    let reservation = list.reserve();
+    let index = reservation.index();
+    assert_eq!(list.check_invariants(), Ok(()));
+
+    // Bits past the end of the list can be cleared
+    list.storage_mut().used.set(300);
+    let err = InvariantViolation::OccupancyMismatch { index: 300 };
+    assert_eq!(list.check_invariants(), Err(err));
+    list.rebuild_free_chain();
+    assert_eq!(list.check_invariants(), Ok(()));
+
+    // A reserved slot that isn't marked as used can't be repaired
+    list.storage_mut().used.clear(index);
+    let err = InvariantViolation::OccupancyMismatch { index };
+    assert_eq!(list.check_invariants(), Err(err));
+    let err = SlotListError::OccupancyCorrupted;
+    assert_eq!(list.try_rebuild_free_chain(), Err(err));
+    assert_eq!(list.try_compact(), Err(err));
+    list.storage_mut().used.set(index);
+
+    // Nor can an occupied slot that is marked as vacant, which would otherwise
+    // be read as a pair of links
+    list.storage_mut().used.clear(5);
+    let err = InvariantViolation::WrongLength { expected: 98, found: 99 };
+    assert_eq!(list.check_invariants(), Err(err));
+    let err = SlotListError::OccupancyCorrupted;
+    assert_eq!(list.try_rebuild_free_chain(), Err(err));
+    list.storage_mut().used.set(5);
+
+    assert_eq!(list.check_invariants(), Ok(()));
+    list.commit(reservation, 1000);
+    assert_eq!(list.len(), 100);
+    assert_eq!(list.get(5), Some(&5));
+  }
+
+  #[test]
+  fn dropping_values() {
+    let drops = Cell::new(0);
+    let mut list = SlotList::with_storage(PackedStorage::<Counted>::new());
+    for _ in 0..10 {
+      list.insert(Counted(&drops));
+    }
+    drop(list.remove(3));
+    drop(list.replace(4, Counted(&drops)));
+    let reservation = list.reserve();
+    assert_eq!(drops.get(), 2);
+    let copy = list.clone();
+    let mut values = copy.into_iter();
+    drop(values.next());
+    drop(values.next_back());
+    assert_eq!(drops.get(), 4);
+    drop(values);
+    assert_eq!(drops.get(), 11);
+    list.cancel(reservation);
+    drop(list);
+    assert_eq!(drops.get(), 20);
+  }
+
+  impl<'a> Clone for Counted<'a> {
+    fn clone(&self) -> Self {
+      Counted(self.0)
+    }
+  }
+}
@@ -0,0 +1,60 @@
+/// Determines which empty slot a SlotList re-uses when a new value is inserted
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AllocationPolicy {
+  /// Re-use slots in the order they were emptied. This is the default.
+  #[default]
+  Fifo,
+  /// Re-use the most recently emptied slot first. Recently used slots are more
+  /// likely to still be in cache.
+  Lifo,
+  /// Always re-use the lowest available index, the way POSIX requires `open()`
+  /// to pick the lowest free file descriptor.
+  LowestIndex,
+  /// Re-use the first available index after the most recently allocated one,
+  /// wrapping around at the end of the list. This is how most kernels assign
+  /// process IDs, and it delays re-use of any particular index for as long as
+  /// possible.
+  Cyclic,
+}
+
+impl AllocationPolicy {
+  /// Chain-based policies track empty slots with a linked list threaded through
+  /// the slots themselves. The others search the occupancy bitmap instead.
+  pub(crate) fn uses_empty_chain(self) -> bool {
+    match self {
+      AllocationPolicy::Fifo | AllocationPolicy::Lifo => true,
+      AllocationPolicy::LowestIndex | AllocationPolicy::Cyclic => false,
+    }
+  }
+}
+
+/// Determines whether a SlotList gives memory back as values are removed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShrinkPolicy {
+  /// Never release memory automatically. This is the default.
+  #[default]
+  Never,
+  /// Once fewer than this percentage of the allocated slots hold values or
+  /// reservations, trim the empty slots off of the end of the list and
+  /// release the unused memory, the way `shrink_to_fit` does. Values are never
+  /// moved, so a single value near the end of the list keeps the slots below
+  /// it alive.
+  /// A list that is shrunk to fit doubles its allocation when it next grows,
+  /// so percentages above 50 will release and re-allocate memory repeatedly.
+  BelowPercent(u8),
+}
+
+impl ShrinkPolicy {
+  /// Determine whether a list using `used` slots out of an allocation of
+  /// `capacity` slots should be shrunk
+  pub(crate) fn should_shrink(self, used: usize, capacity: usize) -> bool {
+    match self {
+      ShrinkPolicy::Never => false,
+      ShrinkPolicy::BelowPercent(percent) => {
+        used.saturating_mul(100) < capacity.saturating_mul(percent as usize)
+      },
+    }
+  }
+}
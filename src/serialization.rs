@@ -0,0 +1,290 @@
+#[cfg(feature = "std")]
+use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::index::SlotIndex;
+use crate::list::{SavedState, Slot, SlotList};
+use crate::policy::{AllocationPolicy, ShrinkPolicy};
+use crate::storage::Storage;
+
+/// A slot as it is saved. Reserved slots are saved as empty slots.
+#[derive(Serialize, Deserialize)]
+enum SavedSlot<T> {
+  Occupied { generation: u32, value: T },
+  Empty { generation: u32 },
+}
+
+/// A list as it is saved: every slot in index order, and the order in which
+/// empty slots will be re-used
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SlotList", deny_unknown_fields)]
+struct SavedList<Slots, Chain> {
+  policy: AllocationPolicy,
+  shrink_policy: ShrinkPolicy,
+  limit: Option<usize>,
+  next_cyclic_slot: usize,
+  fresh_generation: u32,
+  slots: Slots,
+  empty_chain: Chain,
+}
+
+/// Serializes the slots of a list one at a time, without collecting them
+struct SerializeSlots<'a, T, I, S>(&'a SlotList<T, I, S>);
+
+impl<'a, T, I, S> Serialize for SerializeSlots<'a, T, I, S>
+where
+  T: Serialize,
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn serialize<Ser: Serializer>(
+    &self,
+    serializer: Ser,
+  ) -> Result<Ser::Ok, Ser::Error> {
+    let list = self.0;
+    let slots = (0..list.slot_count()).map(|index| {
+      match list.get_raw_slot(index).unwrap() {
+        Slot::Occupied { generation, value } => {
+          SavedSlot::Occupied { generation, value }
+        },
+        Slot::Empty { generation, .. } | Slot::Reserved { generation } => {
+          SavedSlot::Empty { generation }
+        },
+      }
+    });
+    serializer.collect_seq(slots)
+  }
+}
+
+/// Serializes the order of a list's empty chain
+struct SerializeChain<'a, T, I, S>(&'a SlotList<T, I, S>);
+
+impl<'a, T, I, S> Serialize for SerializeChain<'a, T, I, S>
+where
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn serialize<Ser: Serializer>(
+    &self,
+    serializer: Ser,
+  ) -> Result<Ser::Ok, Ser::Error> {
+    serializer.collect_seq(self.0.saved_chain())
+  }
+}
+
+/// A list is saved with its slots at their exact indices, along with the order
+/// of its empty chain, so that a restored list re-uses slots in the same order
+/// as the original would have. Reservations are not saved: reserved slots are
+/// saved as empty slots at the end of the chain.
+impl<T, I, S> Serialize for SlotList<T, I, S>
+where
+  T: Serialize,
+  I: SlotIndex,
+  S: Storage<T, I>,
+{
+  fn serialize<Ser: Serializer>(
+    &self,
+    serializer: Ser,
+  ) -> Result<Ser::Ok, Ser::Error> {
+    let state = self.saved_state();
+    SavedList {
+      policy: state.policy,
+      shrink_policy: state.shrink_policy,
+      limit: if state.limit == usize::MAX { None } else { Some(state.limit) },
+      next_cyclic_slot: state.next_cyclic_slot,
+      fresh_generation: state.fresh_generation,
+      slots: SerializeSlots(self),
+      empty_chain: SerializeChain(self),
+    }.serialize(serializer)
+  }
+}
+
+/// Restoring a list validates it completely, so that untrusted input can't
+/// produce a list with a broken empty chain. Every empty slot must appear in
+/// the chain exactly once, or the chain must be empty for policies that don't
+/// use one.
+impl<'de, T, I, S> Deserialize<'de> for SlotList<T, I, S>
+where
+  T: Deserialize<'de>,
+  I: SlotIndex,
+  S: Storage<T, I> + Default,
+{
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    let saved: SavedList<Vec<SavedSlot<T>>, Vec<usize>> =
+      SavedList::deserialize(deserializer)?;
+    let limit = saved.limit.unwrap_or(usize::MAX);
+    if saved.slots.len() > limit {
+      return Err(D::Error::custom("list has more slots than its limit"));
+    }
+    if saved.slots.len() > S::MAX_SLOTS || saved.slots.len() > I::MAX_SLOTS {
+      return Err(D::Error::custom(
+        "list has more slots than its storage or index type can hold",
+      ));
+    }
+    if !saved.policy.uses_empty_chain() && !saved.empty_chain.is_empty() {
+      return Err(D::Error::custom(
+        "empty chain given for a policy that doesn't use one",
+      ));
+    }
+
+    let mut storage = S::default();
+    storage.reserve(saved.slots.len()).map_err(D::Error::custom)?;
+    for slot in saved.slots {
+      storage.push(match slot {
+        SavedSlot::Occupied { generation, value } => {
+          Slot::Occupied { generation, value }
+        },
+        SavedSlot::Empty { generation } => Slot::unlinked(generation),
+      });
+    }
+    let state = SavedState {
+      policy: saved.policy,
+      shrink_policy: saved.shrink_policy,
+      limit,
+      next_cyclic_slot: saved.next_cyclic_slot,
+      fresh_generation: saved.fresh_generation,
+    };
+    let restored = SlotList::restore(state, storage, saved.empty_chain);
+    restored.map_err(D::Error::custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  #[cfg(feature = "std")]
+  use std::{format, string::{String, ToString}, vec};
+  #[cfg(not(feature = "std"))]
+  extern crate alloc;
+  #[cfg(not(feature = "std"))]
+  use alloc::{format, string::{String, ToString}, vec};
+
+  use crate::list::SlotList;
+  use crate::packed::PackedStorage;
+  use crate::policy::AllocationPolicy;
+  use crate::storage::ArrayStorage;
+
+  fn round_trip<T>(list: &SlotList<T>) -> SlotList<T>
+  where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+  {
+    serde_json::from_str(&serde_json::to_string(list).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn restoring_indices_and_reuse_order() {
+    let policies = [
+      AllocationPolicy::Fifo,
+      AllocationPolicy::Lifo,
+      AllocationPolicy::LowestIndex,
+      AllocationPolicy::Cyclic,
+    ];
+    for policy in policies {
+      let mut list: SlotList<u32> = SlotList::with_policy(policy);
+      for value in 0..10 {
+        list.insert(value);
+      }
+      list.remove(7);
+      list.remove(2);
+      list.remove(5);
+      let key = list.insert_keyed(20);
+      let mut restored = round_trip(&list);
+      assert_eq!(restored.check_invariants(), Ok(()));
+      assert!(restored.iter().eq(list.iter()));
+      assert_eq!(restored.get_by_key(key), Some(&20));
+      // Both lists re-use the same slots in the same order
+      for value in 30..35 {
+        assert_eq!(restored.insert(value), list.insert(value), "{:?}", policy);
+      }
+    }
+  }
+
+  #[test]
+  fn restoring_stale_keys() {
+    let mut list: SlotList<u32> = SlotList::new();
+    let key = list.insert_keyed(1);
+    list.remove_by_key(key);
+    let mut restored = round_trip(&list);
+    restored.insert(2);
+    let new_key = restored.insert_keyed(3);
+    assert_eq!(new_key.index(), key.index());
+    assert_eq!(restored.get_by_key(key), None);
+    assert_eq!(restored.get_by_key(new_key), Some(&3));
+  }
+
+  #[test]
+  fn saving_reservations_as_empty_slots() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(1);
+    let reservation = list.reserve();
+    list.insert(2);
+    let mut restored = round_trip(&list);
+    assert_eq!(restored.reserved_count(), 0);
+    assert_eq!(restored.insert(3), list.insert(3));
+    // The reserved slot is the last to be re-used
+    assert_eq!(restored.insert(4), reservation.index());
+  }
+
+  #[test]
+  fn restoring_other_storage() {
+    let mut list = SlotList::with_storage(PackedStorage::<u32, u16>::new());
+    list.insert(1);
+    list.insert(2);
+    list.remove(0);
+    let json = serde_json::to_string(&list).unwrap();
+    let restored: SlotList<u32, u16, ArrayStorage<u32, 4, u16>> =
+      serde_json::from_str(&json).unwrap();
+    assert!(restored.iter().eq(list.iter()));
+    assert_eq!(restored.limit(), Some(4));
+    let too_small: Result<SlotList<u32, u16, ArrayStorage<u32, 2, u16>>, _> =
+      serde_json::from_str(&json);
+    assert!(too_small.is_err());
+  }
+
+  #[test]
+  fn rejecting_inconsistent_input() {
+    let header = concat!(
+      r#""policy":"Fifo","shrink_policy":"Never","limit":null,"#,
+      r#""next_cyclic_slot":0,"fresh_generation":0"#,
+    );
+    let slots = concat!(
+      r#""slots":[{"Occupied":{"generation":0,"value":5}},"#,
+      r#"{"Empty":{"generation":0}},{"Empty":{"generation":0}}]"#,
+    );
+    let parse = |chain: &str| -> Result<SlotList<u32>, String> {
+      let json = format!("{{{},{},\"empty_chain\":{}}}", header, slots, chain);
+      serde_json::from_str(&json).map_err(|err| err.to_string())
+    };
+    assert!(parse("[2,1]").is_ok());
+    assert_eq!(parse("[2,1]").unwrap().insert(9), 2);
+    // Chains that skip a slot, visit one twice, or reach an occupied or
+    // missing slot
+    assert!(parse("[1]").unwrap_err().contains("not part of the empty chain"));
+    assert!(parse("[1,2,1]").is_err());
+    assert!(parse("[1,1]").is_err());
+    assert!(parse("[0,1,2]").unwrap_err().contains("non-empty slot 0"));
+    assert!(parse("[1,2,3]").unwrap_err().contains("out-of-bounds"));
+
+    let limited = header.replace(r#""limit":null"#, r#""limit":2"#);
+    let limited = format!(r#"{{{},{},"empty_chain":[1,2]}}"#, limited, slots);
+    assert!(serde_json::from_str::<SlotList<u32>>(&limited).is_err());
+    let unchained = header.replace("Fifo", "LowestIndex");
+    let unchained =
+      format!(r#"{{{},{},"empty_chain":[1,2]}}"#, unchained, slots);
+    assert!(serde_json::from_str::<SlotList<u32>>(&unchained).is_err());
+    let occupied = r#"{"Occupied":{"generation":0,"value":5}}"#;
+    let narrow = format!(
+      r#"{{{},"slots":[{}],"empty_chain":[]}}"#,
+      header,
+      vec![occupied; 70_000].join(","),
+    );
+    assert!(serde_json::from_str::<SlotList<u32, u16>>(&narrow).is_err());
+  }
+}
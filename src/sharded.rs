@@ -0,0 +1,424 @@
+use std::boxed::Box;
+use std::cell::Cell;
+use std::iter::Peekable;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::vec::Vec;
+
+use crate::error::InsertError;
+use crate::iter::Iter;
+use crate::list::SlotList;
+
+/// Hands each thread the next shard in turn, the first time it inserts
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+std::thread_local! {
+  static THREAD_NUMBER: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn thread_number() -> usize {
+  THREAD_NUMBER.with(|number| match number.get() {
+    Some(number) => number,
+    None => {
+      let assigned = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+      number.set(Some(assigned));
+      assigned
+    },
+  })
+}
+
+/// A slot list split into several shards, each behind its own lock, so that
+/// threads inserting at the same time don't all wait on one lock.
+/// Each thread inserts into its own shard, chosen the first time it inserts,
+/// and threads only share a shard once there are more threads than shards.
+/// The shard holding a value is encoded in the low bits of its index, so that
+/// `get` and `remove` lock only that shard.
+pub struct ShardedSlotList<T> {
+  shards: Box<[Mutex<SlotList<T>>]>,
+  shard_bits: u32,
+}
+
+impl<T> ShardedSlotList<T> {
+  /// Create a list with a shard for each thread the system can run at once
+  pub fn new() -> ShardedSlotList<T> {
+    let parallelism =
+      std::thread::available_parallelism().map_or(1, |count| count.get());
+    ShardedSlotList::with_shards(parallelism)
+  }
+
+  /// Create a list with a specific number of shards, rounded up to a power of
+  /// two. Each shard is limited to the slots whose indices still fit in a
+  /// `usize` once the shard is encoded in their low bits.
+  pub fn with_shards(shard_count: usize) -> ShardedSlotList<T> {
+    let shard_count = shard_count.max(1).next_power_of_two();
+    let shard_bits = shard_count.trailing_zeros();
+    let limit = usize::MAX >> shard_bits;
+    ShardedSlotList {
+      shards: (0..shard_count)
+        .map(|_| Mutex::new(SlotList::with_limit(limit)))
+        .collect(),
+      shard_bits,
+    }
+  }
+
+  pub fn shard_count(&self) -> usize {
+    self.shards.len()
+  }
+
+  /// The number of values in the list. Each shard is counted in turn, so
+  /// other threads may change the total while it is being counted.
+  pub fn len(&self) -> usize {
+    (0..self.shards.len()).map(|shard| self.lock_shard(shard).len()).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    (0..self.shards.len()).all(|shard| self.lock_shard(shard).is_empty())
+  }
+
+  /// Insert a new value into the calling thread's shard, returning its index
+  pub fn insert(&self, item: T) -> usize {
+    match self.try_insert(item) {
+      Ok(index) => index,
+      Err(err) => panic!("{}", err),
+    }
+  }
+
+  /// Insert a new value into the list, like `insert`. If the calling thread's
+  /// shard can't hold it, the value is handed back inside of the error
+  /// instead.
+  pub fn try_insert(&self, item: T) -> Result<usize, InsertError<T>> {
+    let shard = thread_number() & (self.shards.len() - 1);
+    let local = self.lock_shard(shard).try_insert(item)?;
+    Ok(self.global_index(shard, local))
+  }
+
+  /// Call a function with a reference to the value at an index, returning
+  /// what it returns. The value's shard is locked only for the duration of
+  /// the call.
+  pub fn with<R, F: FnOnce(&T) -> R>(&self, index: usize, f: F) -> Option<R> {
+    let (shard, local) = self.split_index(index);
+    self.lock_shard(shard).get(local).map(f)
+  }
+
+  /// Call a function with a mutable reference to the value at an index,
+  /// returning what it returns. The value's shard is locked only for the
+  /// duration of the call.
+  pub fn with_mut<R, F: FnOnce(&mut T) -> R>(
+    &self,
+    index: usize,
+    f: F,
+  ) -> Option<R> {
+    let (shard, local) = self.split_index(index);
+    self.lock_shard(shard).get_mut(local).map(f)
+  }
+
+  /// Get a reference to the value at an index. The value's shard stays locked
+  /// until the reference is dropped, so the thread holding it deadlocks if it
+  /// calls any other method that locks the same shard, including `insert`.
+  /// `with` holds the lock only while it runs.
+  pub fn get(&self, index: usize) -> Option<ShardRef<'_, T>> {
+    let (shard, local) = self.split_index(index);
+    let guard = self.lock_shard(shard);
+    guard.get(local)?;
+    Some(ShardRef { guard, index: local })
+  }
+
+  /// Get a mutable reference to the value at an index. The value's shard
+  /// stays locked until the reference is dropped, with the same hazard as
+  /// `get`. `with_mut` holds the lock only while it runs.
+  pub fn get_mut(&self, index: usize) -> Option<ShardRefMut<'_, T>> {
+    let (shard, local) = self.split_index(index);
+    let mut guard = self.lock_shard(shard);
+    guard.get_mut(local)?;
+    Some(ShardRefMut { guard, index: local })
+  }
+
+  pub fn remove(&self, index: usize) -> Option<T> {
+    let (shard, local) = self.split_index(index);
+    self.lock_shard(shard).remove(local)
+  }
+
+  /// Lock every shard, for as long as the returned value is held. Shards are
+  /// always locked in the same order, so two threads locking them all at once
+  /// can't deadlock, but the thread holding them deadlocks if it calls any
+  /// other method of the list.
+  pub fn lock(&self) -> LockedShards<'_, T> {
+    LockedShards {
+      shards: (0..self.shards.len())
+        .map(|shard| self.lock_shard(shard))
+        .collect(),
+      shard_bits: self.shard_bits,
+    }
+  }
+
+  /// Lock a shard. If a thread panicked while holding its lock, the panic may
+  /// have interrupted an update, so the shard's list is checked, and its chain
+  /// and counters are rebuilt if they are inconsistent, before it is used
+  /// again.
+  fn lock_shard(&self, shard: usize) -> MutexGuard<'_, SlotList<T>> {
+    match self.shards[shard].lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => {
+        let mut guard = poisoned.into_inner();
+        if guard.check_invariants().is_err() {
+          guard.rebuild_free_chain();
+        }
+        self.shards[shard].clear_poison();
+        guard
+      },
+    }
+  }
+
+  fn split_index(&self, index: usize) -> (usize, usize) {
+    (index & (self.shards.len() - 1), index >> self.shard_bits)
+  }
+
+  fn global_index(&self, shard: usize, local: usize) -> usize {
+    (local << self.shard_bits) | shard
+  }
+}
+
+impl<T> Default for ShardedSlotList<T> {
+  fn default() -> Self {
+    ShardedSlotList::new()
+  }
+}
+
+/// A reference to a value in a `ShardedSlotList`, which holds the lock on the
+/// value's shard until it is dropped
+pub struct ShardRef<'a, T> {
+  guard: MutexGuard<'a, SlotList<T>>,
+  index: usize,
+}
+
+impl<'a, T> Deref for ShardRef<'a, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    self.guard.get(self.index).unwrap()
+  }
+}
+
+/// A mutable reference to a value in a `ShardedSlotList`, which holds the lock
+/// on the value's shard until it is dropped
+pub struct ShardRefMut<'a, T> {
+  guard: MutexGuard<'a, SlotList<T>>,
+  index: usize,
+}
+
+impl<'a, T> Deref for ShardRefMut<'a, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    self.guard.get(self.index).unwrap()
+  }
+}
+
+impl<'a, T> DerefMut for ShardRefMut<'a, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    self.guard.get_mut(self.index).unwrap()
+  }
+}
+
+/// Every shard of a `ShardedSlotList`, locked so that the whole list can be
+/// read at once
+pub struct LockedShards<'a, T> {
+  shards: Vec<MutexGuard<'a, SlotList<T>>>,
+  shard_bits: u32,
+}
+
+impl<'a, T> LockedShards<'a, T> {
+  pub fn len(&self) -> usize {
+    self.shards.iter().map(|shard| shard.len()).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.shards.iter().all(|shard| shard.is_empty())
+  }
+
+  pub fn get(&self, index: usize) -> Option<&T> {
+    let shard = index & (self.shards.len() - 1);
+    self.shards[shard].get(index >> self.shard_bits)
+  }
+
+  /// Iterate over every value in the list, in order of index, yielding each
+  /// index along with its value
+  pub fn iter_indexed(&self) -> ShardedIter<'_, T> {
+    ShardedIter {
+      shards: self.shards
+        .iter()
+        .map(|shard| shard.iter_indexed().peekable())
+        .collect(),
+      shard_bits: self.shard_bits,
+    }
+  }
+}
+
+/// Merges the values of each shard in order of index. Indices in the same
+/// shard are in order already, so each step takes whichever shard's next
+/// value has the lowest index.
+pub struct ShardedIter<'a, T> {
+  shards: Vec<Peekable<Iter<'a, T>>>,
+  shard_bits: u32,
+}
+
+impl<'a, T> Iterator for ShardedIter<'a, T> {
+  type Item = (usize, &'a T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let shard_bits = self.shard_bits;
+    let (shard, _) = self.shards
+      .iter_mut()
+      .enumerate()
+      .filter_map(|(shard, values)| {
+        let (local, _) = values.peek()?;
+        Some((shard, (*local << shard_bits) | shard))
+      })
+      .min_by_key(|(_, index)| *index)?;
+    let (local, value) = self.shards[shard].next()?;
+    Some(((local << shard_bits) | shard, value))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let remaining = self.shards.iter().map(|values| values.len()).sum();
+    (remaining, Some(remaining))
+  }
+}
+
+impl<'a, T> ExactSizeIterator for ShardedIter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+  use super::ShardedSlotList;
+  use crate::storage::Storage;
+  use std::panic::AssertUnwindSafe;
+  use std::sync::Arc;
+  use std::thread;
+  use std::vec::Vec;
+
+  #[test]
+  fn routing_by_index() {
+    let list = ShardedSlotList::with_shards(3);
+    assert_eq!(list.shard_count(), 4);
+    let first = list.insert(10);
+    let second = list.insert(20);
+    // Both values come from this thread's shard
+    assert_eq!(first % 4, second % 4);
+    assert_eq!(*list.get(first).unwrap(), 10);
+    *list.get_mut(second).unwrap() += 1;
+    assert_eq!(*list.get(second).unwrap(), 21);
+    assert_eq!(list.remove(first), Some(10));
+    assert!(list.get(first).is_none());
+    assert!(list.get(first + 1).is_none());
+    assert_eq!(list.len(), 1);
+    // Later values go to the same shard
+    assert_eq!(list.insert(30) % 4, first % 4);
+  }
+
+  #[test]
+  fn accessing_through_closures() {
+    let list = ShardedSlotList::with_shards(2);
+    let index = list.insert(10);
+    assert_eq!(list.with(index, |value| *value + 1), Some(11));
+    // The shard isn't locked once the closure returns, so it can be used
+    // again right away
+    let doubled = list.with_mut(index, |value| {
+      *value *= 2;
+      *value
+    });
+    let other = list.insert(doubled.unwrap());
+    assert_eq!(list.with(other, |value| *value), Some(20));
+    list.remove(index);
+    assert_eq!(list.with(index, |value| *value), None);
+    assert_eq!(list.with_mut(index, |value| *value), None);
+  }
+
+  #[test]
+  fn recovering_poisoned_shards() {
+    let list = ShardedSlotList::with_shards(1);
+    for value in 0..4 {
+      list.insert(value);
+    }
+    list.remove(1);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+      let mut shard = list.shards[0].lock().unwrap();
+      // Break the chain, as a panic partway through an update could
+      shard.storage_mut().set_next_empty(4, Some(2)).unwrap();
+      panic!("interrupted");
+    }));
+    assert!(result.is_err());
+    assert!(list.shards[0].is_poisoned());
+    assert!(list.lock_shard(0).check_invariants().is_ok());
+    assert!(!list.shards[0].is_poisoned());
+    // The rebuilt chain re-uses slots in index order
+    assert_eq!(list.insert(10), 1);
+    assert_eq!(list.insert(11), 4);
+  }
+
+  #[test]
+  fn limiting_shards_to_addressable_indices() {
+    let list: ShardedSlotList<u32> = ShardedSlotList::with_shards(4);
+    for shard in 0..4 {
+      assert_eq!(list.lock_shard(shard).limit(), Some(usize::MAX >> 2));
+    }
+    let list: ShardedSlotList<u32> = ShardedSlotList::with_shards(1);
+    assert_eq!(list.lock_shard(0).limit(), None);
+  }
+
+  #[test]
+  fn inserting_from_many_threads() {
+    let threads = 8;
+    let steps = if cfg!(miri) { 20 } else { 1000 };
+    let list = Arc::new(ShardedSlotList::with_shards(4));
+    let handles: Vec<_> = (0..threads).map(|thread| {
+      let list = list.clone();
+      thread::spawn(move || {
+        let mut indices = Vec::new();
+        for step in 0..steps {
+          indices.push(list.insert(thread * steps + step));
+          if step % 4 == 3 {
+            let index = indices.swap_remove(step % indices.len());
+            assert!(list.remove(index).is_some());
+          }
+        }
+        let shard = indices[0] % 4;
+        assert!(indices.iter().all(|index| index % 4 == shard));
+        indices
+      })
+    }).collect();
+    let mut indices: Vec<_> = handles
+      .into_iter()
+      .flat_map(|handle| handle.join().unwrap())
+      .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    assert_eq!(indices.len(), list.len());
+    let locked = list.lock();
+    assert_eq!(locked.iter_indexed().len(), indices.len());
+    let keys = locked.iter_indexed().map(|(index, _)| index);
+    assert!(keys.eq(indices.iter().copied()));
+    for (index, value) in locked.iter_indexed() {
+      assert_eq!(locked.get(index), Some(value));
+    }
+  }
+
+  #[test]
+  fn iterating_in_index_order() {
+    let list = Arc::new(ShardedSlotList::with_shards(2));
+    let mut expected = Vec::new();
+    for value in 0..4 {
+      expected.push((list.insert(value), value));
+    }
+    let other = list.clone();
+    let handle = thread::spawn(move || {
+      (10..13).map(|value| (other.insert(value), value)).collect::<Vec<_>>()
+    });
+    expected.extend(handle.join().unwrap());
+    list.remove(expected.remove(1).0);
+    expected.sort_unstable();
+    let locked = list.lock();
+    let values = locked.iter_indexed().map(|(index, value)| (index, *value));
+    assert!(values.eq(expected.iter().copied()));
+  }
+}
@@ -0,0 +1,532 @@
+#[cfg(feature = "std")]
+use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use core::iter::FusedIterator;
+
+use crate::error::SlotListError;
+use crate::index::SlotIndex;
+use crate::list::Slot;
+use crate::storage::{sealed, SliceCursor, Storage, VecStorage};
+
+/// Heap-allocated storage that keeps a jump-counting skipfield alongside its
+/// slots, in the style of `plf::colony`. Each run of vacant slots records its
+/// length at both of its ends, so that iteration steps over the whole run at
+/// once, from either direction. The cost of iterating depends on the number of
+/// values and runs, rather than on the number of slots.
+/// Reserved slots don't belong to any run, and are stepped over one at a time.
+/// Keeping the skipfield up to date adds a little work to every insert and
+/// remove, and one index per slot.
+#[derive(Clone)]
+pub struct SkipfieldStorage<T, I = usize> {
+  inner: VecStorage<T, I>,
+  /// `NONE` for a slot in use. For a vacant slot at either end of a run, the
+  /// length of the run minus one, so that a run spanning every slot still
+  /// fits. A vacant slot inside a run may hold any other value.
+  skipfield: Vec<I>,
+}
+
+/// Read an entry of the skipfield as the length of the run it records, or
+/// zero for a slot in use
+fn run_length<I: SlotIndex>(entry: I) -> usize {
+  if entry == I::NONE {
+    0
+  } else {
+    entry.to_usize() + 1
+  }
+}
+
+impl<T, I> SkipfieldStorage<T, I> {
+  pub const fn new() -> SkipfieldStorage<T, I> {
+    SkipfieldStorage {
+      inner: VecStorage::new(),
+      skipfield: Vec::new(),
+    }
+  }
+
+  pub fn with_capacity(capacity: usize) -> SkipfieldStorage<T, I> {
+    SkipfieldStorage {
+      inner: VecStorage::with_capacity(capacity),
+      skipfield: Vec::with_capacity(capacity),
+    }
+  }
+}
+
+impl<T, I: SlotIndex> SkipfieldStorage<T, I> {
+  fn run_length(&self, index: usize) -> usize {
+    run_length(self.skipfield[index])
+  }
+
+  fn set_run_length(&mut self, index: usize, length: usize) {
+    self.skipfield[index] = match length {
+      0 => I::NONE,
+      length => I::from_usize(length - 1),
+    };
+  }
+
+  /// Record that a slot has become vacant, joining it to the runs on either
+  /// side of it
+  fn skip(&mut self, index: usize) {
+    let before = if index > 0 { self.run_length(index - 1) } else { 0 };
+    let after = if index + 1 < self.skipfield.len() {
+      self.run_length(index + 1)
+    } else {
+      0
+    };
+    let length = before + 1 + after;
+    self.set_run_length(index, length);
+    self.set_run_length(index - before, length);
+    self.set_run_length(index + after, length);
+  }
+
+  /// Record that a vacant slot has come into use, splitting its run in two
+  fn unskip(&mut self, index: usize) {
+    let length = self.run_length(index);
+    let starts_run = index == 0 || self.run_length(index - 1) == 0;
+    let ends_run =
+      index + 1 == self.skipfield.len() || self.run_length(index + 1) == 0;
+    let (start, end) = match (starts_run, ends_run) {
+      (true, _) => (index, index + length - 1),
+      (false, true) => (index + 1 - length, index),
+      // Only the ends of a run know its length, so find them from the
+      // occupancy bitmap instead
+      (false, false) => {
+        let occupancy = &self.inner.occupancy;
+        let start = occupancy.prev_set(0, index).map_or(0, |used| used + 1);
+        let len = self.skipfield.len();
+        let end = occupancy.next_set(index + 1, len).unwrap_or(len) - 1;
+        (start, end)
+      },
+    };
+    self.set_run_length(index, 0);
+    if start < index {
+      self.set_run_length(start, index - start);
+      self.set_run_length(index - 1, index - start);
+    }
+    if index < end {
+      self.set_run_length(index + 1, end - index);
+      self.set_run_length(end, end - index);
+    }
+  }
+}
+
+impl<T, I> Default for SkipfieldStorage<T, I> {
+  fn default() -> SkipfieldStorage<T, I> {
+    SkipfieldStorage::new()
+  }
+}
+
+impl<T, I> core::fmt::Debug for SkipfieldStorage<T, I>
+where
+  T: core::fmt::Debug,
+  I: SlotIndex,
+{
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    core::fmt::Debug::fmt(&self.inner, formatter)
+  }
+}
+
+impl<T, I> sealed::Sealed for SkipfieldStorage<T, I> {}
+
+impl<T, I: SlotIndex> Storage<T, I> for SkipfieldStorage<T, I> {
+  type Iter<'a> = SkipIter<'a, T, I> where T: 'a, I: 'a;
+  type IterMut<'a> = SkipIterMut<'a, T, I> where T: 'a, I: 'a;
+  type IntoIter = <VecStorage<T, I> as Storage<T, I>>::IntoIter;
+
+  fn slot_count(&self) -> usize {
+    self.skipfield.len()
+  }
+
+  fn get(&self, index: usize) -> Option<Slot<&T, I>> {
+    self.inner.get(index)
+  }
+
+  fn get_value(&self, index: usize) -> Option<&T> {
+    self.inner.get_value(index)
+  }
+
+  fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    self.inner.get_mut(index)
+  }
+
+  fn replace(&mut self, index: usize, slot: Slot<T, I>) -> Slot<T, I> {
+    let vacant = slot.is_vacant();
+    let prev = self.inner.replace(index, slot);
+    match (prev.is_vacant(), vacant) {
+      (true, false) => self.unskip(index),
+      (false, true) => self.skip(index),
+      _ => {},
+    }
+    prev
+  }
+
+  fn set_next_empty(
+    &mut self,
+    index: usize,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.inner.set_next_empty(index, next)
+  }
+
+  fn set_prev_empty(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.inner.set_prev_empty(index, prev)
+  }
+
+  fn push(&mut self, slot: Slot<T, I>) {
+    let vacant = slot.is_vacant();
+    self.inner.push(slot);
+    self.skipfield.push(I::NONE);
+    if vacant {
+      self.skip(self.skipfield.len() - 1);
+    }
+  }
+
+  fn pop(&mut self) -> Option<Slot<T, I>> {
+    let slot = self.inner.pop()?;
+    let last = self.skipfield.len() - 1;
+    let length = self.run_length(last);
+    if length > 1 {
+      // The run that ended with the popped slot now ends one slot earlier
+      self.set_run_length(last + 1 - length, length - 1);
+      self.set_run_length(last - 1, length - 1);
+    }
+    self.skipfield.pop();
+    Some(slot)
+  }
+
+  fn reserve(&mut self, additional: usize) -> Result<(), SlotListError> {
+    self.inner.reserve(additional)?;
+    self.skipfield
+      .try_reserve(additional)
+      .map_err(|_| SlotListError::AllocFailed)
+  }
+
+  fn capacity(&self) -> usize {
+    core::cmp::min(self.inner.capacity(), self.skipfield.capacity())
+  }
+
+  fn shrink_to_fit(&mut self) {
+    self.inner.shrink_to_fit();
+    self.skipfield.shrink_to_fit();
+  }
+
+  fn rebuild_occupancy(&mut self) {
+    self.inner.rebuild_occupancy();
+    self.skipfield.iter_mut().for_each(|entry| *entry = I::NONE);
+    for index in 0..self.skipfield.len() {
+      if self.inner.slots[index].is_vacant() {
+        self.skip(index);
+      }
+    }
+  }
+
+  fn is_marked_used(&self, index: usize) -> bool {
+    self.inner.is_marked_used(index)
+  }
+
+  fn next_vacant(&self, from: usize, limit: usize) -> Option<usize> {
+    self.inner.next_vacant(from, limit)
+  }
+
+  fn next_used(&self, from: usize, limit: usize) -> Option<usize> {
+    self.inner.next_used(from, limit)
+  }
+
+  fn first_vacant(&self, limit: usize) -> Option<usize> {
+    self.inner.first_vacant(limit)
+  }
+
+  fn iter(&self) -> SkipIter<'_, T, I> {
+    SkipIter {
+      slots: &self.inner.slots,
+      skipfield: &self.skipfield,
+      front: 0,
+      back: self.skipfield.len(),
+    }
+  }
+
+  fn iter_mut(&mut self) -> SkipIterMut<'_, T, I> {
+    SkipIterMut {
+      slots: SliceCursor::new(&mut self.inner.slots),
+      skipfield: &self.skipfield,
+      front: 0,
+      back: self.skipfield.len(),
+    }
+  }
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.inner.into_iter()
+  }
+}
+
+/// Iterator over the occupied slots of a SkipfieldStorage, which jumps over
+/// each run of vacant slots in a single step.
+/// A vacant slot reached from the front always starts a run, and one reached
+/// from the back always ends one, so the length of the run is always known.
+pub struct SkipIter<'a, T, I> {
+  slots: &'a [Slot<T, I>],
+  skipfield: &'a [I],
+  front: usize,
+  back: usize,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for SkipIter<'a, T, I> {
+  type Item = (usize, &'a T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while self.front < self.back {
+      let index = self.front;
+      match run_length(self.skipfield[index]) {
+        0 => {
+          self.front += 1;
+          // Reserved slots are in use, but have no value to yield
+          if let Some(value) = self.slots[index].as_option_of_ref() {
+            return Some((index, value));
+          }
+        },
+        length => self.front = core::cmp::min(index + length, self.back),
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for SkipIter<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    while self.front < self.back {
+      let index = self.back - 1;
+      match run_length(self.skipfield[index]) {
+        0 => {
+          self.back -= 1;
+          if let Some(value) = self.slots[index].as_option_of_ref() {
+            return Some((index, value));
+          }
+        },
+        length => self.back = core::cmp::max(self.back - length, self.front),
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for SkipIter<'a, T, I> {}
+
+impl<'a, T, I> Clone for SkipIter<'a, T, I> {
+  fn clone(&self) -> Self {
+    SkipIter {
+      slots: self.slots,
+      skipfield: self.skipfield,
+      front: self.front,
+      back: self.back,
+    }
+  }
+}
+
+/// Iterator over the occupied slots of a SkipfieldStorage, yielding mutable
+/// references
+pub struct SkipIterMut<'a, T, I> {
+  slots: SliceCursor<'a, Slot<T, I>>,
+  skipfield: &'a [I],
+  front: usize,
+  back: usize,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for SkipIterMut<'a, T, I> {
+  type Item = (usize, &'a mut T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    while self.front < self.back {
+      let index = self.front;
+      match run_length(self.skipfield[index]) {
+        0 => {
+          self.front += 1;
+          if let Some(value) = self.slots.take_front(index).as_mut() {
+            return Some((index, value));
+          }
+        },
+        length => self.front = core::cmp::min(index + length, self.back),
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for SkipIterMut<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    while self.front < self.back {
+      let index = self.back - 1;
+      match run_length(self.skipfield[index]) {
+        0 => {
+          self.back -= 1;
+          if let Some(value) = self.slots.take_back(index).as_mut() {
+            return Some((index, value));
+          }
+        },
+        length => self.back = core::cmp::max(self.back - length, self.front),
+      }
+    }
+    None
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for SkipIterMut<'a, T, I> {}
+
+#[cfg(test)]
+mod tests {
+  use super::{run_length, SkipfieldStorage, Vec};
+  use crate::index::SlotIndex;
+  use crate::list::{Slot, SlotList};
+  use crate::storage::Storage;
+
+  /// Read the runs of vacant slots out of the skipfield, checking that both
+  /// ends of every run agree, and that every slot inside a run is skipped
+  fn runs<I: SlotIndex>(
+    storage: &SkipfieldStorage<u32, I>,
+  ) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut index = 0;
+    while index < storage.skipfield.len() {
+      match run_length(storage.skipfield[index]) {
+        0 => index += 1,
+        length => {
+          let run = &storage.skipfield[index..index + length];
+          let end = run_length(run[length - 1]);
+          assert_eq!(end, length, "run at {} has mismatched ends", index);
+          assert!(run.iter().all(|entry| run_length(*entry) != 0));
+          runs.push((index, length));
+          index += length;
+        },
+      }
+    }
+    runs
+  }
+
+  /// Compute the runs of vacant slots directly from the slots
+  fn expected_runs<I: SlotIndex>(
+    storage: &SkipfieldStorage<u32, I>,
+  ) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for index in 0..storage.slot_count() {
+      if storage.get(index).unwrap().is_vacant() {
+        match runs.last_mut() {
+          Some((start, length)) if *start + *length == index => *length += 1,
+          _ => runs.push((index, 1)),
+        }
+      }
+    }
+    runs
+  }
+
+  #[test]
+  fn joining_and_splitting_runs() {
+    let mut storage: SkipfieldStorage<u32> = SkipfieldStorage::new();
+    for value in 0..8 {
+      storage.push(Slot::Occupied { generation: 0, value });
+    }
+    storage.take(2);
+    storage.take(4);
+    assert_eq!(runs(&storage), [(2, 1), (4, 1)]);
+    // Joining a run on each side
+    storage.take(3);
+    assert_eq!(runs(&storage), [(2, 3)]);
+    storage.take(5);
+    storage.take(1);
+    assert_eq!(runs(&storage), [(1, 5)]);
+    // Filling the middle of a run, then either end
+    storage.fill(3, 10);
+    assert_eq!(runs(&storage), [(1, 2), (4, 2)]);
+    storage.fill(4, 11);
+    storage.fill(2, 12);
+    assert_eq!(runs(&storage), [(1, 1), (5, 1)]);
+    // Reserved slots are not skipped
+    storage.replace(5, Slot::Reserved { generation: 1 });
+    assert_eq!(runs(&storage), [(1, 1)]);
+    let values: Vec<(usize, &u32)> = storage.iter().collect();
+    let expected = [(0, &0), (2, &12), (3, &10), (4, &11), (6, &6), (7, &7)];
+    assert_eq!(values, expected);
+  }
+
+  #[test]
+  fn popping_the_end_of_a_run() {
+    let mut storage: SkipfieldStorage<u32, u16> = SkipfieldStorage::new();
+    storage.push(Slot::Occupied { generation: 0, value: 0 });
+    for _ in 0..3 {
+      storage.push(Slot::unlinked(0));
+    }
+    assert_eq!(runs(&storage), [(1, 3)]);
+    storage.pop();
+    assert_eq!(runs(&storage), [(1, 2)]);
+    storage.pop();
+    storage.pop();
+    assert_eq!(runs(&storage), []);
+    assert_eq!(storage.iter().rev().count(), 1);
+  }
+
+  #[test]
+  fn matching_the_slots() {
+    let mut storage: SkipfieldStorage<u32, u16> = SkipfieldStorage::new();
+    let mut seed: u32 = 0x1234_5678;
+    let steps = if cfg!(miri) { 200 } else { 5000 };
+    for step in 0..steps {
+      seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+      let roll = (seed >> 16) % 10;
+      let target = (seed >> 4) as usize % (storage.slot_count() + 1);
+      if roll < 2 || target == storage.slot_count() {
+        let slot = if roll == 0 {
+          Slot::Occupied { generation: 0, value: step }
+        } else {
+          Slot::unlinked(0)
+        };
+        storage.push(slot);
+      } else if roll < 3 {
+        storage.pop();
+      } else if roll < 6 {
+        storage.take(target);
+      } else if roll < 9 {
+        storage.fill(target, step);
+      } else {
+        storage.replace(target, Slot::Reserved { generation: 0 });
+      }
+      assert_eq!(runs(&storage), expected_runs(&storage));
+      let expected: Vec<usize> = (0..storage.slot_count())
+        .filter(|index| storage.get_value(*index).is_some())
+        .collect();
+      let forward: Vec<usize> =
+        storage.iter().map(|(index, _)| index).collect();
+      assert_eq!(forward, expected);
+      let mut backward: Vec<usize> =
+        storage.iter_mut().rev().map(|(index, _)| index).collect();
+      backward.reverse();
+      assert_eq!(backward, expected);
+    }
+  }
+
+  #[test]
+  fn iterating_a_list() {
+    let mut list = SlotList::with_storage(SkipfieldStorage::<u32>::new());
+    for value in 0..100 {
+      list.insert(value);
+    }
+    for index in (0..100).filter(|index| index % 10 != 0) {
+      list.remove(index);
+    }
+    let keys: Vec<usize> = list.keys().collect();
+    assert_eq!(keys, [0, 10, 20, 30, 40, 50, 60, 70, 80, 90]);
+    let mut iter = list.values();
+    assert_eq!(iter.next(), Some(&0));
+    assert_eq!(iter.next_back(), Some(&90));
+    assert_eq!(iter.len(), 8);
+    for value in list.values_mut() {
+      *value += 1;
+    }
+    assert_eq!(list.get(50), Some(&51));
+    assert_eq!(list.check_invariants(), Ok(()));
+  }
+}
@@ -0,0 +1,672 @@
+#[cfg(feature = "std")]
+use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use core::convert::TryFrom;
+
+use crate::error::SnapshotError;
+use crate::index::SlotIndex;
+use crate::list::{SavedState, Slot, SlotList};
+use crate::policy::{AllocationPolicy, ShrinkPolicy};
+use crate::storage::Storage;
+
+// A snapshot is laid out as follows. Every integer other than the checksum is
+// an unsigned LEB128 varint.
+//
+//   magic            b"SLST"
+//   version          1 byte
+//   index width      1 byte, the size of the list's index type
+//   policy           1 byte
+//   shrink policy    1 byte: 0 for Never, or 1 followed by a percentage byte
+//   limit            0 for no limit, or the limit plus one
+//   cyclic cursor
+//   fresh generation
+//   slot count
+//   run count, then the length of each run of slots, alternating between
+//                    runs of empty slots and runs of occupied slots, starting
+//                    with a (possibly empty) run of empty slots
+//   the generation of every slot
+//   the length and encoding of every value, in index order
+//   chain length, then the index of each empty slot in chain order
+//   checksum         CRC-32 of everything before it, 4 bytes little-endian
+
+const MAGIC: &[u8; 4] = b"SLST";
+const VERSION: u8 = 1;
+
+/// A destination for the bytes of a snapshot
+pub trait SnapshotSink {
+  fn write(&mut self, bytes: &[u8]) -> Result<(), SnapshotError>;
+}
+
+impl SnapshotSink for Vec<u8> {
+  fn write(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+    self.try_reserve(bytes.len()).map_err(|_| SnapshotError::AllocFailed)?;
+    self.extend_from_slice(bytes);
+    Ok(())
+  }
+}
+
+/// Writing to a slice fills it from the front, leaving the slice pointing at
+/// whatever is left over
+impl SnapshotSink for &mut [u8] {
+  fn write(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+    if bytes.len() > self.len() {
+      return Err(SnapshotError::SinkFull);
+    }
+    let (written, rest) = core::mem::take(self).split_at_mut(bytes.len());
+    written.copy_from_slice(bytes);
+    *self = rest;
+    Ok(())
+  }
+}
+
+/// Converts the values stored in a list to and from bytes. The snapshot
+/// records the length of each encoded value, so encodings don't need to be
+/// self-delimiting.
+pub trait ValueCodec<T> {
+  /// Write the encoding of a value to the sink. An error, whether from the
+  /// sink or from the codec itself, stops the snapshot and is returned by
+  /// `write_snapshot`.
+  fn encode<W: SnapshotSink>(
+    &self,
+    value: &T,
+    sink: &mut W,
+  ) -> Result<(), SnapshotError>;
+
+  /// Reconstruct a value from the bytes that `encode` produced for it, or
+  /// return `None` if they don't describe a valid value
+  fn decode(&self, bytes: &[u8]) -> Option<T>;
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Build the lookup table for the reflected CRC-32 polynomial used by zlib
+/// and Ethernet
+const fn crc_table() -> [u32; 256] {
+  let mut table = [0; 256];
+  let mut byte = 0;
+  while byte < 256 {
+    let mut crc = byte as u32;
+    let mut bit = 0;
+    while bit < 8 {
+      crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+      bit += 1;
+    }
+    table[byte] = crc;
+    byte += 1;
+  }
+  table
+}
+
+/// Extend a running CRC-32 with more bytes. A new CRC starts from `!0`, and is
+/// complemented once all bytes have been added.
+fn update_crc(crc: u32, bytes: &[u8]) -> u32 {
+  bytes.iter().fold(crc, |crc, byte| {
+    CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+  })
+}
+
+/// Wraps a sink, computing the checksum of everything written through it
+struct Writer<'a, W> {
+  sink: &'a mut W,
+  crc: u32,
+}
+
+impl<'a, W: SnapshotSink> Writer<'a, W> {
+  fn bytes(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+    self.crc = update_crc(self.crc, bytes);
+    self.sink.write(bytes)
+  }
+
+  fn varint(&mut self, value: u64) -> Result<(), SnapshotError> {
+    let mut encoded = [0; 10];
+    let mut length = 0;
+    let mut value = value;
+    loop {
+      let byte = (value & 0x7f) as u8;
+      value >>= 7;
+      if value == 0 {
+        encoded[length] = byte;
+        length += 1;
+        break;
+      }
+      encoded[length] = byte | 0x80;
+      length += 1;
+    }
+    self.bytes(&encoded[..length])
+  }
+
+  fn usize(&mut self, value: usize) -> Result<(), SnapshotError> {
+    self.varint(value as u64)
+  }
+}
+
+/// Reads the fields of a snapshot from a position in the input
+#[derive(Clone)]
+struct Reader<'a> {
+  input: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn at(input: &'a [u8], position: usize) -> Reader<'a> {
+    Reader { input, position }
+  }
+
+  fn bytes(&mut self, length: usize) -> Result<&'a [u8], SnapshotError> {
+    let remaining = &self.input[self.position..];
+    if length > remaining.len() {
+      return Err(SnapshotError::Truncated);
+    }
+    self.position += length;
+    Ok(&remaining[..length])
+  }
+
+  fn byte(&mut self) -> Result<u8, SnapshotError> {
+    Ok(self.bytes(1)?[0])
+  }
+
+  fn varint(&mut self) -> Result<u64, SnapshotError> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+      let byte = self.byte()?;
+      let bits = (byte & 0x7f) as u64;
+      if shift == 63 && bits > 1 || shift > 63 {
+        return Err(SnapshotError::Malformed);
+      }
+      value |= bits << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+      shift += 7;
+    }
+  }
+
+  fn usize(&mut self) -> Result<usize, SnapshotError> {
+    usize::try_from(self.varint()?).map_err(|_| SnapshotError::Malformed)
+  }
+
+  fn u32(&mut self) -> Result<u32, SnapshotError> {
+    u32::try_from(self.varint()?).map_err(|_| SnapshotError::Malformed)
+  }
+
+  /// Read the number of items in a section. Every item takes at least one
+  /// byte, so a count larger than the rest of the input means the input was
+  /// cut short, and nothing should be allocated for it.
+  fn count(&mut self) -> Result<usize, SnapshotError> {
+    let count = self.usize()?;
+    if count > self.input.len() - self.position {
+      return Err(SnapshotError::Truncated);
+    }
+    Ok(count)
+  }
+}
+
+/// Where each section of a snapshot begins, found by walking the snapshot once
+/// so that its checksum can be verified before anything is decoded
+struct Layout {
+  state: SavedState,
+  slot_count: usize,
+  run_count: usize,
+  runs: usize,
+  generations: usize,
+  values: usize,
+  chain_length: usize,
+  chain: usize,
+}
+
+impl Layout {
+  fn find(input: &[u8], index_width: u8) -> Result<Layout, SnapshotError> {
+    let mut reader = Reader::at(input, 0);
+    if reader.bytes(MAGIC.len())? != MAGIC {
+      return Err(SnapshotError::NotASnapshot);
+    }
+    let version = reader.byte()?;
+    if version != VERSION {
+      return Err(SnapshotError::UnsupportedVersion { version });
+    }
+    let found = reader.byte()?;
+    if found != index_width {
+      let expected = index_width;
+      return Err(SnapshotError::IndexWidthMismatch { expected, found });
+    }
+    let policy = match reader.byte()? {
+      0 => AllocationPolicy::Fifo,
+      1 => AllocationPolicy::Lifo,
+      2 => AllocationPolicy::LowestIndex,
+      3 => AllocationPolicy::Cyclic,
+      _ => return Err(SnapshotError::Malformed),
+    };
+    let shrink_policy = match reader.byte()? {
+      0 => ShrinkPolicy::Never,
+      1 => ShrinkPolicy::BelowPercent(reader.byte()?),
+      _ => return Err(SnapshotError::Malformed),
+    };
+    let limit = match reader.usize()? {
+      0 => usize::MAX,
+      limit => limit - 1,
+    };
+    let state = SavedState {
+      policy,
+      shrink_policy,
+      limit,
+      next_cyclic_slot: reader.usize()?,
+      fresh_generation: reader.u32()?,
+    };
+
+    let slot_count = reader.count()?;
+    let run_count = reader.count()?;
+    let runs = reader.position;
+    let mut total: usize = 0;
+    let mut occupied = 0;
+    for run in 0..run_count {
+      let length = reader.usize()?;
+      if run > 0 && length == 0 {
+        return Err(SnapshotError::Malformed);
+      }
+      total = total.checked_add(length).ok_or(SnapshotError::Malformed)?;
+      if run % 2 == 1 {
+        occupied += length;
+      }
+    }
+    if total != slot_count {
+      return Err(SnapshotError::Malformed);
+    }
+    let generations = reader.position;
+    for _ in 0..slot_count {
+      reader.u32()?;
+    }
+    let values = reader.position;
+    for _ in 0..occupied {
+      let length = reader.usize()?;
+      reader.bytes(length)?;
+    }
+    let chain_length = reader.count()?;
+    let chain = reader.position;
+    for _ in 0..chain_length {
+      reader.usize()?;
+    }
+
+    let end = reader.position;
+    let checksum = reader.bytes(4)?;
+    let expected = !update_crc(!0, &input[..end]);
+    if checksum != expected.to_le_bytes() {
+      return Err(SnapshotError::ChecksumMismatch);
+    }
+    Ok(Layout {
+      state,
+      slot_count,
+      run_count,
+      runs,
+      generations,
+      values,
+      chain_length,
+      chain,
+    })
+  }
+}
+
+impl<T, I: SlotIndex, S: Storage<T, I>> SlotList<T, I, S> {
+  /// Write a compact, self-describing snapshot of the list to a sink, using
+  /// the codec to encode each value. Indices, generations, and the order of
+  /// the empty chain are all preserved, so that a list restored with
+  /// `read_snapshot` re-uses slots in the same order as this one.
+  /// Reservations are not saved; reserved slots are saved as empty slots at
+  /// the end of the chain.
+  pub fn write_snapshot<C: ValueCodec<T>, W: SnapshotSink>(
+    &self,
+    codec: &C,
+    sink: &mut W,
+  ) -> Result<(), SnapshotError> {
+    let mut writer = Writer { sink, crc: !0 };
+    let state = self.saved_state();
+    writer.bytes(MAGIC)?;
+    writer.bytes(&[VERSION, core::mem::size_of::<I>() as u8])?;
+    writer.bytes(&[match state.policy {
+      AllocationPolicy::Fifo => 0,
+      AllocationPolicy::Lifo => 1,
+      AllocationPolicy::LowestIndex => 2,
+      AllocationPolicy::Cyclic => 3,
+    }])?;
+    match state.shrink_policy {
+      ShrinkPolicy::Never => writer.bytes(&[0])?,
+      ShrinkPolicy::BelowPercent(percent) => writer.bytes(&[1, percent])?,
+    }
+    writer.usize(if state.limit == usize::MAX { 0 } else { state.limit + 1 })?;
+    writer.usize(state.next_cyclic_slot)?;
+    writer.varint(state.fresh_generation as u64)?;
+
+    let slot_count = self.slot_count();
+    writer.usize(slot_count)?;
+    let is_occupied = |index: usize| {
+      matches!(self.get_raw_slot(index), Some(Slot::Occupied { .. }))
+    };
+    let run_ends = || {
+      (0..slot_count).filter(move |index| {
+        *index + 1 == slot_count
+          || is_occupied(*index) != is_occupied(*index + 1)
+      })
+    };
+    // The first run is always a run of empty slots, even if it has none
+    let leading_empty_run = slot_count > 0 && is_occupied(0);
+    writer.usize(run_ends().count() + leading_empty_run as usize)?;
+    if leading_empty_run {
+      writer.usize(0)?;
+    }
+    let mut run_start = 0;
+    for run_end in run_ends() {
+      writer.usize(run_end + 1 - run_start)?;
+      run_start = run_end + 1;
+    }
+
+    for index in 0..slot_count {
+      writer.varint(self.get_raw_slot(index).unwrap().generation() as u64)?;
+    }
+    // Each value is encoded into a scratch buffer first, so that its length
+    // can be written ahead of it. The buffer only grows fallibly.
+    let mut buffer = Vec::new();
+    for value in self.iter() {
+      buffer.clear();
+      codec.encode(value, &mut buffer)?;
+      writer.usize(buffer.len())?;
+      writer.bytes(&buffer)?;
+    }
+    writer.usize(self.saved_chain().count())?;
+    for index in self.saved_chain() {
+      writer.usize(index)?;
+    }
+
+    let checksum = !writer.crc;
+    writer.sink.write(&checksum.to_le_bytes())
+  }
+}
+
+impl<T, I: SlotIndex, S: Storage<T, I> + Default> SlotList<T, I, S> {
+  /// Restore a list from a snapshot written by `write_snapshot`, using the
+  /// codec to decode each value. The checksum is verified before any value is
+  /// decoded, and the restored list is checked for consistency, so truncated,
+  /// corrupted, or malicious input is rejected rather than producing a broken
+  /// list. Any bytes after the end of the snapshot are ignored.
+  pub fn read_snapshot<C: ValueCodec<T>>(
+    input: &[u8],
+    codec: &C,
+  ) -> Result<SlotList<T, I, S>, SnapshotError> {
+    let layout = Layout::find(input, core::mem::size_of::<I>() as u8)?;
+    let state = layout.state;
+    let slot_count = layout.slot_count;
+    if slot_count > state.limit
+      || slot_count > S::MAX_SLOTS
+      || slot_count > I::MAX_SLOTS
+    {
+      return Err(SnapshotError::TooManySlots);
+    }
+    if !state.policy.uses_empty_chain() && layout.chain_length > 0 {
+      return Err(SnapshotError::Malformed);
+    }
+
+    let mut storage = S::default();
+    storage.reserve(layout.slot_count).map_err(|_| SnapshotError::AllocFailed)?;
+    let mut runs = Reader::at(input, layout.runs);
+    let mut generations = Reader::at(input, layout.generations);
+    let mut values = Reader::at(input, layout.values);
+    for run in 0..layout.run_count {
+      let occupied = run % 2 == 1;
+      for _ in 0..runs.usize()? {
+        let generation = generations.u32()?;
+        let slot = if occupied {
+          let index = storage.slot_count();
+          let length = values.usize()?;
+          let value = codec
+            .decode(values.bytes(length)?)
+            .ok_or(SnapshotError::InvalidValue { index })?;
+          Slot::Occupied { generation, value }
+        } else {
+          Slot::unlinked(generation)
+        };
+        storage.push(slot);
+      }
+    }
+
+    let mut chain = Reader::at(input, layout.chain);
+    // Every link was already read once while finding the layout. Should one
+    // fail anyway, an out-of-bounds index makes the restore fail too.
+    let links = (0..layout.chain_length)
+      .map(|_| chain.usize().unwrap_or(usize::MAX));
+    SlotList::restore(state, storage, links).map_err(SnapshotError::InvalidList)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use core::convert::TryInto;
+
+  use super::{update_crc, SnapshotSink, ValueCodec, Vec};
+  use crate::error::{InvariantViolation, SnapshotError};
+  use crate::list::SlotList;
+  use crate::packed::PackedStorage;
+  use crate::policy::{AllocationPolicy, ShrinkPolicy};
+  use crate::storage::ArrayStorage;
+
+  /// Stores each value as four little-endian bytes, rejecting odd values
+  struct EvenU32;
+
+  impl ValueCodec<u32> for EvenU32 {
+    fn encode<W: SnapshotSink>(
+      &self,
+      value: &u32,
+      sink: &mut W,
+    ) -> Result<(), SnapshotError> {
+      sink.write(&value.to_le_bytes())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u32> {
+      let value = u32::from_le_bytes(bytes.try_into().ok()?);
+      if value % 2 == 0 {
+        Some(value)
+      } else {
+        None
+      }
+    }
+  }
+
+  fn sample_list(policy: AllocationPolicy) -> SlotList<u32> {
+    let mut list = SlotList::with_policy(policy);
+    for value in 0..12 {
+      list.insert(value * 2);
+    }
+    for index in [0, 1, 7, 4, 11] {
+      list.remove(index);
+    }
+    list
+  }
+
+  fn snapshot<I, S>(list: &SlotList<u32, I, S>) -> Vec<u8>
+  where
+    I: crate::SlotIndex,
+    S: crate::Storage<u32, I>,
+  {
+    let mut bytes = Vec::new();
+    list.write_snapshot(&EvenU32, &mut bytes).unwrap();
+    bytes
+  }
+
+  /// Overwrite the checksum, so that deliberate changes to a snapshot reach
+  /// the checks made after it is verified
+  fn fix_checksum(bytes: &mut [u8]) {
+    let end = bytes.len() - 4;
+    let checksum = !update_crc(!0, &bytes[..end]);
+    bytes[end..].copy_from_slice(&checksum.to_le_bytes());
+  }
+
+  #[test]
+  fn restoring_indices_and_reuse_order() {
+    let policies = [
+      AllocationPolicy::Fifo,
+      AllocationPolicy::Lifo,
+      AllocationPolicy::LowestIndex,
+      AllocationPolicy::Cyclic,
+    ];
+    for policy in policies {
+      let mut list = sample_list(policy);
+      list.set_shrink_policy(ShrinkPolicy::BelowPercent(10));
+      let key = list.insert_keyed(100);
+      let bytes = snapshot(&list);
+      let mut restored: SlotList<u32> =
+        SlotList::read_snapshot(&bytes, &EvenU32).unwrap();
+      assert!(restored.iter().eq(list.iter()));
+      assert_eq!(restored.get_by_key(key), Some(&100));
+      assert_eq!(restored.policy(), policy);
+      assert_eq!(restored.shrink_policy(), ShrinkPolicy::BelowPercent(10));
+      for value in 0..6 {
+        let value = value * 2;
+        assert_eq!(restored.insert(value), list.insert(value), "{:?}", policy);
+      }
+    }
+  }
+
+  #[test]
+  fn restoring_other_storage() {
+    let mut list = SlotList::with_storage(PackedStorage::<u32, u16>::new());
+    list.insert(2);
+    list.insert(4);
+    list.remove(0);
+    let bytes = snapshot(&list);
+    let restored: SlotList<u32, u16, ArrayStorage<u32, 3, u16>> =
+      SlotList::read_snapshot(&bytes, &EvenU32).unwrap();
+    assert!(restored.iter().eq(list.iter()));
+    let too_small: Result<SlotList<u32, u16, ArrayStorage<u32, 2, u16>>, _> =
+      SlotList::read_snapshot(&bytes, &EvenU32);
+    assert_eq!(too_small.unwrap_err(), SnapshotError::TooManySlots);
+    let too_wide = SlotList::<u32, u32>::read_snapshot(&bytes, &EvenU32);
+    assert_eq!(
+      too_wide.unwrap_err(),
+      SnapshotError::IndexWidthMismatch { expected: 4, found: 2 },
+    );
+  }
+
+  #[test]
+  fn writing_to_a_slice() {
+    let list = sample_list(AllocationPolicy::Fifo);
+    let expected = snapshot(&list);
+    let mut buffer = [0; 256];
+    let mut sink: &mut [u8] = &mut buffer;
+    list.write_snapshot(&EvenU32, &mut sink).unwrap();
+    let remaining = sink.len();
+    assert_eq!(&buffer[..256 - remaining], &expected[..]);
+    // Trailing bytes after the snapshot are ignored
+    assert!(SlotList::<u32>::read_snapshot(&buffer, &EvenU32).is_ok());
+
+    let mut small = [0; 16];
+    let mut sink: &mut [u8] = &mut small;
+    let result = list.write_snapshot(&EvenU32, &mut sink);
+    assert_eq!(result, Err(SnapshotError::SinkFull));
+    // Whatever fit was written before the sink filled up
+    let written = 16 - sink.len();
+    assert!(written > 0);
+    assert_eq!(&small[..written], &expected[..written]);
+  }
+
+  #[test]
+  fn failing_to_encode() {
+    /// Refuses to encode values larger than a byte
+    struct Bytes;
+
+    impl ValueCodec<u32> for Bytes {
+      fn encode<W: SnapshotSink>(
+        &self,
+        value: &u32,
+        sink: &mut W,
+      ) -> Result<(), SnapshotError> {
+        let byte: u8 =
+          (*value).try_into().map_err(|_| SnapshotError::Malformed)?;
+        sink.write(&[byte])
+      }
+
+      fn decode(&self, bytes: &[u8]) -> Option<u32> {
+        Some(*bytes.first()? as u32)
+      }
+    }
+
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(7);
+    let mut bytes = Vec::new();
+    list.write_snapshot(&Bytes, &mut bytes).unwrap();
+    let restored = SlotList::<u32>::read_snapshot(&bytes, &Bytes).unwrap();
+    assert_eq!(restored.get(0), Some(&7));
+
+    list.insert(300);
+    let mut bytes = Vec::new();
+    let result = list.write_snapshot(&Bytes, &mut bytes);
+    assert_eq!(result, Err(SnapshotError::Malformed));
+  }
+
+  #[test]
+  fn rejecting_truncated_input() {
+    let bytes = snapshot(&sample_list(AllocationPolicy::Fifo));
+    for length in 0..bytes.len() {
+      let result = SlotList::<u32>::read_snapshot(&bytes[..length], &EvenU32);
+      let error = result.unwrap_err();
+      assert_eq!(error, SnapshotError::Truncated, "cut at {}", length);
+    }
+  }
+
+  #[test]
+  fn rejecting_corrupted_input() {
+    let bytes = snapshot(&sample_list(AllocationPolicy::Fifo));
+    for position in 0..bytes.len() {
+      for flip in [0x01, 0x80] {
+        let mut corrupted = bytes.clone();
+        corrupted[position] ^= flip;
+        let result = SlotList::<u32>::read_snapshot(&corrupted, &EvenU32);
+        assert!(result.is_err(), "flipped byte {}", position);
+      }
+    }
+    let mut corrupted = bytes.clone();
+    corrupted[4] = 9;
+    let result = SlotList::<u32>::read_snapshot(&corrupted, &EvenU32);
+    let error = result.unwrap_err();
+    assert_eq!(error, SnapshotError::UnsupportedVersion { version: 9 });
+    let result = SlotList::<u32>::read_snapshot(b"JUNKJUNK", &EvenU32);
+    assert_eq!(result.unwrap_err(), SnapshotError::NotASnapshot);
+  }
+
+  #[test]
+  fn rejecting_inconsistent_input() {
+    let mut list: SlotList<u32> = SlotList::new();
+    list.insert(2);
+    list.insert(4);
+    list.remove(0);
+    // Slot 1 holds 4, and the empty chain is [2, 0]; the last two bytes
+    // before the checksum are the chain
+    let bytes = snapshot(&list);
+    let chain = bytes.len() - 6;
+    assert_eq!(&bytes[chain - 1..chain + 2], &[2, 2, 0]);
+
+    let mut repeated = bytes.clone();
+    repeated[chain + 1] = 2;
+    fix_checksum(&mut repeated);
+    let result = SlotList::<u32>::read_snapshot(&repeated, &EvenU32);
+    assert!(matches!(result, Err(SnapshotError::InvalidList(_))));
+    let mut occupied = bytes.clone();
+    occupied[chain + 1] = 1;
+    fix_checksum(&mut occupied);
+    let violation = InvariantViolation::OccupiedInChain { index: 1 };
+    assert_eq!(
+      SlotList::<u32>::read_snapshot(&occupied, &EvenU32).unwrap_err(),
+      SnapshotError::InvalidList(violation),
+    );
+
+    // The value 4 is stored in the four bytes before the chain length
+    let mut odd = bytes.clone();
+    odd[chain - 5] = 5;
+    fix_checksum(&mut odd);
+    assert_eq!(
+      SlotList::<u32>::read_snapshot(&odd, &EvenU32).unwrap_err(),
+      SnapshotError::InvalidValue { index: 1 },
+    );
+  }
+}
@@ -0,0 +1,711 @@
+#[cfg(feature = "std")]
+use std::vec::{self, Vec};
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+use alloc::vec::{self, Vec};
+
+use core::iter::{Enumerate, FusedIterator};
+use core::slice;
+
+use crate::bitmap::{OccupancyBitmap, SetBits};
+use crate::error::{InvariantViolation, SlotListError};
+use crate::index::SlotIndex;
+use crate::list::Slot;
+
+pub(crate) mod sealed {
+  pub trait Sealed {}
+}
+
+/// The memory backing a SlotList: the slots themselves, along with a record of
+/// which slots are in use. Everything else about the list, including the empty
+/// chain, the allocation policies, and the counters, is shared between every
+/// kind of storage.
+/// Slots are read and written whole, as `Slot` values, so that storage is free
+/// to keep them in a different form internally.
+/// This trait is sealed, and can't be implemented outside of this crate.
+pub trait Storage<T, I: SlotIndex = usize>: sealed::Sealed {
+  /// Iterator over the occupied slots, in increasing index order
+  type Iter<'a>: DoubleEndedIterator<Item = (usize, &'a T)> + Clone
+  where
+    Self: 'a,
+    T: 'a;
+
+  /// Iterator over the occupied slots, yielding mutable references
+  type IterMut<'a>: DoubleEndedIterator<Item = (usize, &'a mut T)>
+  where
+    Self: 'a,
+    T: 'a;
+
+  /// Iterator that consumes the storage, yielding the value of each occupied
+  /// slot
+  type IntoIter: DoubleEndedIterator<Item = (usize, T)>;
+
+  /// The most slots the storage can ever hold
+  const MAX_SLOTS: usize = usize::MAX;
+
+  /// The number of slots, whether or not they are occupied
+  fn slot_count(&self) -> usize;
+
+  /// Look at the contents of a slot, with any value borrowed
+  fn get(&self, index: usize) -> Option<Slot<&T, I>>;
+
+  /// Look up the value in a slot, if it is occupied
+  fn get_value(&self, index: usize) -> Option<&T> {
+    self.get(index).and_then(Slot::occupied)
+  }
+
+  /// Look up the value in a slot mutably, if it is occupied
+  fn get_mut(&mut self, index: usize) -> Option<&mut T>;
+
+  /// Overwrite a slot, returning its previous contents. Panics if the index is
+  /// out of bounds.
+  fn replace(&mut self, index: usize, slot: Slot<T, I>) -> Slot<T, I>;
+
+  /// Store a value in a slot, keeping its current generation. The previous
+  /// contents of the slot are returned.
+  fn fill(&mut self, index: usize, value: T) -> Slot<T, I> {
+    let slot = self.get(index).expect("slot index out of bounds");
+    let generation = slot.generation();
+    self.replace(index, Slot::Occupied { generation, value })
+  }
+
+  /// Empty a slot, returning its previous contents. If the slot was occupied,
+  /// its generation is advanced so that existing keys go stale.
+  fn take(&mut self, index: usize) -> Slot<T, I> {
+    let generation = match self.get(index).expect("slot index out of bounds") {
+      Slot::Occupied { generation, .. } => generation.wrapping_add(1),
+      slot => slot.generation(),
+    };
+    self.replace(index, Slot::unlinked(generation))
+  }
+
+  /// Link an empty slot to the next one in the empty chain. Fails if the slot
+  /// is out of bounds, occupied, or reserved.
+  fn set_next_empty(
+    &mut self,
+    index: usize,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError>;
+
+  /// Link an empty slot to the previous one in the empty chain. Fails if the
+  /// slot is out of bounds, occupied, or reserved.
+  fn set_prev_empty(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+  ) -> Result<(), SlotListError>;
+
+  /// Add a slot to the end. The list never pushes a slot without first making
+  /// room for it with `reserve`.
+  fn push(&mut self, slot: Slot<T, I>);
+
+  /// Remove the last slot
+  fn pop(&mut self) -> Option<Slot<T, I>>;
+
+  /// Make room for at least `additional` more slots, so that the next pushes
+  /// can't fail
+  fn reserve(&mut self, additional: usize) -> Result<(), SlotListError>;
+
+  /// The number of slots that can be stored without reserving more memory
+  fn capacity(&self) -> usize;
+
+  /// Release any memory not needed by the current slots
+  fn shrink_to_fit(&mut self);
+
+  /// Re-derive the record of which slots are in use from the slots themselves,
+  /// if the two are kept separately. Storage that keeps no other record can
+  /// only repair the parts of it that are derived from the rest.
+  fn rebuild_occupancy(&mut self);
+
+  /// Check the record of which slots are in use, for storage that keeps no
+  /// other record to compare it with or rebuild it from. It must agree with
+  /// itself, and with the list's counts of occupied and reserved slots.
+  fn check_occupancy(
+    &self,
+    _occupied: usize,
+    _reserved: usize,
+  ) -> Result<(), InvariantViolation> {
+    Ok(())
+  }
+
+  /// Whether a slot is recorded as being in use, which may disagree with its
+  /// contents if the record has been corrupted
+  fn is_marked_used(&self, index: usize) -> bool;
+
+  /// Find the lowest vacant slot at or after `from`, with an index less than
+  /// `limit`. Indices past the end of the storage count as vacant.
+  fn next_vacant(&self, from: usize, limit: usize) -> Option<usize>;
+
+  /// Find the lowest slot that is occupied or reserved at or after `from`,
+  /// with an index less than `limit`
+  fn next_used(&self, from: usize, limit: usize) -> Option<usize>;
+
+  /// Find the lowest vacant slot with an index less than `limit`
+  fn first_vacant(&self, limit: usize) -> Option<usize> {
+    self.next_vacant(0, limit)
+  }
+
+  fn iter(&self) -> Self::Iter<'_>;
+
+  fn iter_mut(&mut self) -> Self::IterMut<'_>;
+
+  fn into_iter(self) -> Self::IntoIter;
+}
+
+/// Heap-allocated storage, which grows as needed. This is what a SlotList uses
+/// unless told otherwise. Vacancy is tracked with an `OccupancyBitmap`, so that
+/// searching for the lowest vacant slot is fast even in very large lists, and
+/// iteration skips over runs of vacant slots a word at a time.
+#[derive(Clone)]
+pub struct VecStorage<T, I = usize> {
+  pub(crate) slots: Vec<Slot<T, I>>,
+  pub(crate) occupancy: OccupancyBitmap,
+}
+
+impl<T, I> VecStorage<T, I> {
+  pub const fn new() -> VecStorage<T, I> {
+    VecStorage {
+      slots: Vec::new(),
+      occupancy: OccupancyBitmap::new(),
+    }
+  }
+
+  pub fn with_capacity(capacity: usize) -> VecStorage<T, I> {
+    VecStorage {
+      slots: Vec::with_capacity(capacity),
+      occupancy: OccupancyBitmap::with_capacity(capacity),
+    }
+  }
+}
+
+impl<T, I> Default for VecStorage<T, I> {
+  fn default() -> VecStorage<T, I> {
+    VecStorage::new()
+  }
+}
+
+impl<T: core::fmt::Debug, I: SlotIndex> core::fmt::Debug for VecStorage<T, I> {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    formatter.debug_list()
+      .entries(self.slots.iter())
+      .finish()
+  }
+}
+
+impl<T, I> sealed::Sealed for VecStorage<T, I> {}
+
+impl<T, I: SlotIndex> Storage<T, I> for VecStorage<T, I> {
+  type Iter<'a> = UsedIter<'a, T, I> where T: 'a, I: 'a;
+  type IterMut<'a> = UsedIterMut<'a, T, I> where T: 'a, I: 'a;
+  type IntoIter = SlotsIntoIter<vec::IntoIter<Slot<T, I>>>;
+
+  fn slot_count(&self) -> usize {
+    self.slots.len()
+  }
+
+  fn get(&self, index: usize) -> Option<Slot<&T, I>> {
+    self.slots.get(index).map(Slot::as_ref)
+  }
+
+  fn get_value(&self, index: usize) -> Option<&T> {
+    self.slots.get(index)?.as_option_of_ref()
+  }
+
+  fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    self.slots.get_mut(index)?.as_mut()
+  }
+
+  fn replace(&mut self, index: usize, slot: Slot<T, I>) -> Slot<T, I> {
+    let used = !slot.is_vacant();
+    let prev = core::mem::replace(&mut self.slots[index], slot);
+    if used {
+      self.occupancy.set(index);
+    } else {
+      self.occupancy.clear(index);
+    }
+    prev
+  }
+
+  fn set_next_empty(
+    &mut self,
+    index: usize,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.slots
+      .get_mut(index)
+      .ok_or(SlotListError::OutOfBounds)?
+      .set_next_empty(next)
+  }
+
+  fn set_prev_empty(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.slots
+      .get_mut(index)
+      .ok_or(SlotListError::OutOfBounds)?
+      .set_prev_empty(prev)
+  }
+
+  fn push(&mut self, slot: Slot<T, I>) {
+    if !slot.is_vacant() {
+      self.occupancy.set(self.slots.len());
+    }
+    self.slots.push(slot);
+  }
+
+  fn pop(&mut self) -> Option<Slot<T, I>> {
+    let slot = self.slots.pop()?;
+    self.occupancy.clear(self.slots.len());
+    Some(slot)
+  }
+
+  fn reserve(&mut self, additional: usize) -> Result<(), SlotListError> {
+    self.slots.try_reserve(additional).map_err(|_| SlotListError::AllocFailed)?;
+    self.occupancy
+      .try_reserve(self.slots.len().saturating_add(additional))
+      .map_err(|_| SlotListError::AllocFailed)
+  }
+
+  fn capacity(&self) -> usize {
+    self.slots.capacity()
+  }
+
+  fn shrink_to_fit(&mut self) {
+    self.slots.shrink_to_fit();
+    self.occupancy.truncate(self.slots.len());
+  }
+
+  fn rebuild_occupancy(&mut self) {
+    self.occupancy.clear_all();
+    for (index, slot) in self.slots.iter().enumerate() {
+      if !slot.is_vacant() {
+        self.occupancy.set(index);
+      }
+    }
+  }
+
+  fn is_marked_used(&self, index: usize) -> bool {
+    self.occupancy.is_set(index)
+  }
+
+  fn next_vacant(&self, from: usize, limit: usize) -> Option<usize> {
+    self.occupancy.next_clear(from, limit)
+  }
+
+  fn next_used(&self, from: usize, limit: usize) -> Option<usize> {
+    self.occupancy.next_set(from, core::cmp::min(limit, self.slots.len()))
+  }
+
+  fn first_vacant(&self, limit: usize) -> Option<usize> {
+    self.occupancy.first_clear(limit)
+  }
+
+  fn iter(&self) -> UsedIter<'_, T, I> {
+    UsedIter {
+      slots: &self.slots,
+      used: self.occupancy.set_bits(self.slots.len()),
+    }
+  }
+
+  fn iter_mut(&mut self) -> UsedIterMut<'_, T, I> {
+    UsedIterMut {
+      used: self.occupancy.set_bits(self.slots.len()),
+      slots: SliceCursor::new(&mut self.slots),
+    }
+  }
+
+  fn into_iter(self) -> Self::IntoIter {
+    SlotsIntoIter::new(self.slots.into_iter())
+  }
+}
+
+/// Fixed-size storage held inline, which never allocates. The slots are few
+/// enough that vacancy is found by looking at the slots themselves, rather
+/// than by maintaining a separate bitmap.
+#[derive(Clone)]
+pub struct ArrayStorage<T, const N: usize, I = usize> {
+  slots: [Slot<T, I>; N],
+  len: usize,
+}
+
+impl<T, const N: usize, I: SlotIndex> ArrayStorage<T, N, I> {
+  pub const fn new() -> ArrayStorage<T, N, I> {
+    ArrayStorage {
+      slots: [const { Slot::unlinked(0) }; N],
+      len: 0,
+    }
+  }
+
+  fn slots(&self) -> &[Slot<T, I>] {
+    &self.slots[..self.len]
+  }
+
+  fn slots_mut(&mut self) -> &mut [Slot<T, I>] {
+    &mut self.slots[..self.len]
+  }
+}
+
+impl<T, const N: usize, I: SlotIndex> Default for ArrayStorage<T, N, I> {
+  fn default() -> ArrayStorage<T, N, I> {
+    ArrayStorage::new()
+  }
+}
+
+impl<T, const N: usize, I> core::fmt::Debug for ArrayStorage<T, N, I>
+where
+  T: core::fmt::Debug,
+  I: SlotIndex,
+{
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    formatter.debug_list()
+      .entries(self.slots().iter())
+      .finish()
+  }
+}
+
+impl<T, const N: usize, I> sealed::Sealed for ArrayStorage<T, N, I> {}
+
+impl<T, const N: usize, I: SlotIndex> Storage<T, I> for ArrayStorage<T, N, I> {
+  type Iter<'a> = SlotsIter<'a, T, I> where T: 'a, I: 'a;
+  type IterMut<'a> = SlotsIterMut<'a, T, I> where T: 'a, I: 'a;
+  type IntoIter =
+    SlotsIntoIter<core::iter::Take<core::array::IntoIter<Slot<T, I>, N>>>;
+
+  const MAX_SLOTS: usize = N;
+
+  fn slot_count(&self) -> usize {
+    self.len
+  }
+
+  fn get(&self, index: usize) -> Option<Slot<&T, I>> {
+    self.slots().get(index).map(Slot::as_ref)
+  }
+
+  fn get_value(&self, index: usize) -> Option<&T> {
+    self.slots().get(index)?.as_option_of_ref()
+  }
+
+  fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+    self.slots_mut().get_mut(index)?.as_mut()
+  }
+
+  fn replace(&mut self, index: usize, slot: Slot<T, I>) -> Slot<T, I> {
+    core::mem::replace(&mut self.slots_mut()[index], slot)
+  }
+
+  fn set_next_empty(
+    &mut self,
+    index: usize,
+    next: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.slots_mut()
+      .get_mut(index)
+      .ok_or(SlotListError::OutOfBounds)?
+      .set_next_empty(next)
+  }
+
+  fn set_prev_empty(
+    &mut self,
+    index: usize,
+    prev: Option<usize>,
+  ) -> Result<(), SlotListError> {
+    self.slots_mut()
+      .get_mut(index)
+      .ok_or(SlotListError::OutOfBounds)?
+      .set_prev_empty(prev)
+  }
+
+  fn push(&mut self, slot: Slot<T, I>) {
+    assert!(self.len < N, "ArrayStorage is full");
+    self.slots[self.len] = slot;
+    self.len += 1;
+  }
+
+  fn pop(&mut self) -> Option<Slot<T, I>> {
+    if self.len == 0 {
+      return None;
+    }
+    self.len -= 1;
+    Some(self.slots[self.len].take())
+  }
+
+  fn reserve(&mut self, _additional: usize) -> Result<(), SlotListError> {
+    // A list backed by an array has its limit set to the length of the array,
+    // so it never tries to push a slot that doesn't fit
+    Ok(())
+  }
+
+  fn capacity(&self) -> usize {
+    N
+  }
+
+  fn shrink_to_fit(&mut self) {}
+
+  fn rebuild_occupancy(&mut self) {}
+
+  fn is_marked_used(&self, index: usize) -> bool {
+    self.slots().get(index).is_some_and(|slot| !slot.is_vacant())
+  }
+
+  fn next_vacant(&self, from: usize, limit: usize) -> Option<usize> {
+    let found = self.slots()
+      .iter()
+      .enumerate()
+      .skip(from)
+      .find(|(_, slot)| slot.is_vacant())
+      .map_or(core::cmp::max(from, self.len), |(index, _)| index);
+    if found < limit {
+      Some(found)
+    } else {
+      None
+    }
+  }
+
+  fn next_used(&self, from: usize, limit: usize) -> Option<usize> {
+    let limit = core::cmp::min(limit, self.len);
+    (from..limit).find(|index| !self.slots[*index].is_vacant())
+  }
+
+  fn iter(&self) -> SlotsIter<'_, T, I> {
+    SlotsIter::new(self.slots())
+  }
+
+  fn iter_mut(&mut self) -> SlotsIterMut<'_, T, I> {
+    SlotsIterMut::new(self.slots_mut())
+  }
+
+  fn into_iter(self) -> Self::IntoIter {
+    let len = self.len;
+    SlotsIntoIter::new(IntoIterator::into_iter(self.slots).take(len))
+  }
+}
+
+/// Iterator over the occupied slots in a slice of `Slot`s
+pub struct SlotsIter<'a, T, I> {
+  slots: Enumerate<slice::Iter<'a, Slot<T, I>>>,
+}
+
+impl<'a, T, I> SlotsIter<'a, T, I> {
+  fn new(slots: &'a [Slot<T, I>]) -> SlotsIter<'a, T, I> {
+    SlotsIter {
+      slots: slots.iter().enumerate(),
+    }
+  }
+}
+
+impl<'a, T, I: SlotIndex> Iterator for SlotsIter<'a, T, I> {
+  type Item = (usize, &'a T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.slots
+      .by_ref()
+      .find_map(|(index, slot)| Some((index, slot.as_option_of_ref()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for SlotsIter<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.slots
+      .by_ref()
+      .rev()
+      .find_map(|(index, slot)| Some((index, slot.as_option_of_ref()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for SlotsIter<'a, T, I> {}
+
+impl<'a, T, I> Clone for SlotsIter<'a, T, I> {
+  fn clone(&self) -> Self {
+    SlotsIter {
+      slots: self.slots.clone(),
+    }
+  }
+}
+
+/// Iterator over the occupied slots in a slice of `Slot`s, yielding mutable
+/// references
+pub struct SlotsIterMut<'a, T, I> {
+  slots: Enumerate<slice::IterMut<'a, Slot<T, I>>>,
+}
+
+impl<'a, T, I> SlotsIterMut<'a, T, I> {
+  fn new(slots: &'a mut [Slot<T, I>]) -> SlotsIterMut<'a, T, I> {
+    SlotsIterMut {
+      slots: slots.iter_mut().enumerate(),
+    }
+  }
+}
+
+impl<'a, T, I: SlotIndex> Iterator for SlotsIterMut<'a, T, I> {
+  type Item = (usize, &'a mut T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.slots.by_ref().find_map(|(index, slot)| Some((index, slot.as_mut()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for SlotsIterMut<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.slots
+      .by_ref()
+      .rev()
+      .find_map(|(index, slot)| Some((index, slot.as_mut()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for SlotsIterMut<'a, T, I> {}
+
+/// Iterator over the occupied slots of a `VecStorage`, which visits only the
+/// slots marked as used in its bitmap
+pub struct UsedIter<'a, T, I> {
+  slots: &'a [Slot<T, I>],
+  used: SetBits<'a>,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for UsedIter<'a, T, I> {
+  type Item = (usize, &'a T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let slots = self.slots;
+    // Reserved slots are marked as used, but have no value to yield
+    self.used
+      .by_ref()
+      .find_map(|index| Some((index, slots[index].as_option_of_ref()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for UsedIter<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let slots = self.slots;
+    self.used
+      .by_ref()
+      .rev()
+      .find_map(|index| Some((index, slots[index].as_option_of_ref()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for UsedIter<'a, T, I> {}
+
+impl<'a, T, I> Clone for UsedIter<'a, T, I> {
+  fn clone(&self) -> Self {
+    UsedIter {
+      slots: self.slots,
+      used: self.used.clone(),
+    }
+  }
+}
+
+/// Iterator over the occupied slots of a `VecStorage`, yielding mutable
+/// references
+pub struct UsedIterMut<'a, T, I> {
+  slots: SliceCursor<'a, Slot<T, I>>,
+  used: SetBits<'a>,
+}
+
+impl<'a, T, I: SlotIndex> Iterator for UsedIterMut<'a, T, I> {
+  type Item = (usize, &'a mut T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let slots = &mut self.slots;
+    self.used
+      .by_ref()
+      .find_map(|index| Some((index, slots.take_front(index).as_mut()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> DoubleEndedIterator for UsedIterMut<'a, T, I> {
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let slots = &mut self.slots;
+    self.used
+      .by_ref()
+      .rev()
+      .find_map(|index| Some((index, slots.take_back(index).as_mut()?)))
+  }
+}
+
+impl<'a, T, I: SlotIndex> FusedIterator for UsedIterMut<'a, T, I> {}
+
+/// A mutable slice that hands out references to its elements, in increasing
+/// order from the front and decreasing order from the back. Each element can
+/// be handed out only once, so the references never alias.
+pub(crate) struct SliceCursor<'a, E> {
+  items: &'a mut [E],
+  /// The index of the first element still in `items`
+  offset: usize,
+}
+
+impl<'a, E> SliceCursor<'a, E> {
+  pub fn new(items: &'a mut [E]) -> SliceCursor<'a, E> {
+    SliceCursor {
+      items,
+      offset: 0,
+    }
+  }
+
+  /// Take the element at `index`, giving up every element before it. Panics
+  /// if the element has already been given up.
+  pub fn take_front(&mut self, index: usize) -> &'a mut E {
+    let items = core::mem::take(&mut self.items);
+    let (item, rest) = items[index - self.offset..]
+      .split_first_mut()
+      .expect("index already taken");
+    self.items = rest;
+    self.offset = index + 1;
+    item
+  }
+
+  /// Take the element at `index`, giving up every element after it. Panics if
+  /// the element has already been given up.
+  pub fn take_back(&mut self, index: usize) -> &'a mut E {
+    let items = core::mem::take(&mut self.items);
+    let (item, rest) = items[..index + 1 - self.offset]
+      .split_last_mut()
+      .expect("index already taken");
+    self.items = rest;
+    item
+  }
+}
+
+/// Iterator that consumes a sequence of `Slot`s, yielding the value of each
+/// occupied one
+pub struct SlotsIntoIter<S> {
+  slots: Enumerate<S>,
+}
+
+impl<S: Iterator> SlotsIntoIter<S> {
+  fn new(slots: S) -> SlotsIntoIter<S> {
+    SlotsIntoIter {
+      slots: slots.enumerate(),
+    }
+  }
+}
+
+impl<T, I, S> Iterator for SlotsIntoIter<S>
+where
+  I: SlotIndex,
+  S: Iterator<Item = Slot<T, I>>,
+{
+  type Item = (usize, T);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.slots
+      .by_ref()
+      .find_map(|(index, slot)| Some((index, slot.occupied()?)))
+  }
+}
+
+impl<T, I, S> DoubleEndedIterator for SlotsIntoIter<S>
+where
+  I: SlotIndex,
+  S: DoubleEndedIterator<Item = Slot<T, I>> + ExactSizeIterator,
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.slots
+      .by_ref()
+      .rev()
+      .find_map(|(index, slot)| Some((index, slot.occupied()?)))
+  }
+}